@@ -0,0 +1,94 @@
+//! Disassembles a ROM, either every bank or a number of instructions from an address
+
+use std::process;
+
+use gb_core::{disassemble, BankAddress};
+
+const ROM_BANK_SIZE: usize = 0x4000;
+const DEFAULT_COUNT: usize = 32;
+
+fn usage() -> ! {
+    eprintln!("usage: gb-disasm ROM [BB:AAAA [COUNT]]");
+    process::exit(1);
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let rom = match args.get(1) {
+        Some(path) => std::fs::read(path).unwrap_or_else(|e| {
+            eprintln!("Failed to read {}: {}", path, e);
+            process::exit(1);
+        }),
+        None => usage(),
+    };
+
+    match args.get(2) {
+        Some(start) => {
+            let start: BankAddress = start.parse().unwrap_or_else(|e: String| {
+                eprintln!("{}", e);
+                usage()
+            });
+            let count = match args.get(3) {
+                Some(count) => count.parse().unwrap_or_else(|_| usage()),
+                None => DEFAULT_COUNT,
+            };
+            print_range(&rom, start, count);
+        }
+        None => {
+            // bank 0 at 0x0000-0x3fff, every other bank at 0x4000-0x7fff
+            let banks = rom.len().div_ceil(ROM_BANK_SIZE);
+            for bank in 0..banks {
+                let address = if bank == 0 { 0x0000 } else { 0x4000 };
+                print_bank(&rom, bank as u16, address);
+            }
+        }
+    }
+}
+
+fn read(rom: &[u8], bank: u16, addr: u16) -> u8 {
+    let offset = match addr {
+        0x0000..=0x3fff => addr as usize,
+        _ => bank as usize * ROM_BANK_SIZE + (addr as usize & (ROM_BANK_SIZE - 1)),
+    };
+    rom.get(offset).copied().unwrap_or(0xff)
+}
+
+fn print_instruction(rom: &[u8], bank: u16, addr: u16) -> u16 {
+    let instruction = disassemble(addr, |addr| read(rom, bank, addr));
+    let bytes: Vec<String> = (0..instruction.length as u16)
+        .map(|offset| format!("{:02x}", read(rom, bank, addr.wrapping_add(offset))))
+        .collect();
+    let location = BankAddress {
+        bank: if addr < 0x4000 { 0 } else { bank },
+        address: addr,
+    };
+    match instruction.target {
+        // relative jumps get their destination as comment
+        Some(target) if instruction.text.starts_with("JR") => {
+            println!(
+                "{}  {:<8}  {:<20} ; {:04x}",
+                location,
+                bytes.join(" "),
+                instruction.text,
+                target
+            )
+        }
+        _ => println!("{}  {:<8}  {}", location, bytes.join(" "), instruction.text),
+    }
+    instruction.length as u16
+}
+
+fn print_bank(rom: &[u8], bank: u16, start: u16) {
+    let end = start as usize + ROM_BANK_SIZE;
+    let mut addr = start as usize;
+    while addr < end {
+        addr += print_instruction(rom, bank, addr as u16) as usize;
+    }
+}
+
+fn print_range(rom: &[u8], start: BankAddress, count: usize) {
+    let mut addr = start.address;
+    for _ in 0..count {
+        addr = addr.wrapping_add(print_instruction(rom, start.bank, addr));
+    }
+}
@@ -0,0 +1,77 @@
+//! Runs test roms headlessly and summarizes the results per suite. The suite is
+//! recognized by the path below a given directory, or the path of a given rom: roms below
+//! a `mooneye` directory are mooneye tests, roms named like `*-acid2` are compared
+//! against the PNG screenshot next to them, and all others are treated as blargg tests.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use gb_core::{find_test_roms, run_test_rom_file, TestOutcome, TestSuite};
+
+const DEFAULT_FRAMES: usize = 60 * 60;
+
+fn usage() -> ! {
+    eprintln!("usage: gb-testrom [--frames N] ROM|DIRECTORY...");
+    process::exit(1);
+}
+
+fn main() {
+    let mut frames = DEFAULT_FRAMES;
+    // roms relative to the directory they were found in. Roms given as files keep
+    // their path, which is then matched to find the suite.
+    let mut roms: Vec<(PathBuf, PathBuf)> = Vec::new();
+    let mut failed = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--frames" {
+            frames = args
+                .next()
+                .and_then(|n| n.parse().ok())
+                .unwrap_or_else(|| usage());
+        } else if Path::new(&arg).is_dir() {
+            match find_test_roms(Path::new(&arg)) {
+                Ok(found) => roms.extend(found.into_iter().map(|rom| (PathBuf::from(&arg), rom))),
+                Err(e) => {
+                    eprintln!("{}: {}", arg, e);
+                    failed = true;
+                }
+            }
+        } else {
+            roms.push((PathBuf::new(), PathBuf::from(arg)));
+        }
+    }
+    if roms.is_empty() {
+        usage();
+    }
+
+    // passed and total tests per suite
+    let mut summary: BTreeMap<TestSuite, (usize, usize)> = BTreeMap::new();
+    for (directory, rom) in &roms {
+        let path = directory.join(rom);
+        let (suite, outcome) = match run_test_rom_file(directory, rom, frames) {
+            Ok(result) => result,
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), e);
+                failed = true;
+                continue;
+            }
+        };
+        println!("{}: {:?}", path.display(), outcome);
+        let counts = summary.entry(suite).or_default();
+        counts.1 += 1;
+        if outcome == TestOutcome::Passed {
+            counts.0 += 1;
+        } else {
+            failed = true;
+        }
+    }
+
+    println!();
+    for (suite, (passed, total)) in summary {
+        println!("{:?}: {}/{} passed", suite, passed, total);
+    }
+    if failed {
+        process::exit(1);
+    }
+}
@@ -1,117 +1,861 @@
-use std::cell::RefCell;
-use std::rc::Rc;
+use alloc::{boxed::Box, vec, vec::Vec};
 
 use crate::cartridge::Cartridge;
 use crate::cpu::Cpu;
-use crate::irq::Irq;
-use crate::joypad::{Button, JoyPad};
+use crate::debugger::{Access, Breakpoint, CpuRegisters, StopReason, Watchpoint};
+use crate::disasm::{self, BankAddress, Instruction};
+use crate::gbs::Gbs;
+use crate::joypad::{Button, JoyPad, MAX_PLAYERS};
 use crate::mmu::Mmu;
-use crate::ppu::{Color, Ppu, HEIGHT, WIDTH};
-use crate::sound::Apu;
+use crate::model::Model;
+use crate::palette::{self, CompatPalette, DmgPalette};
+use crate::ppu::{Color, PixelFormat, Ppu, HEIGHT, WIDTH};
+use crate::rewind::Rewind;
+use crate::sgb::{Sgb, SCREEN_X, SCREEN_Y, SGB_HEIGHT, SGB_WIDTH};
+use crate::sound::{Apu, AudioStatus, Channel, DEFAULT_SAMPLE_RATE};
+use crate::state::{Snapshot, State};
+use crate::trace::{TraceFormat, TraceSink, Tracer};
 
+pub(crate) const FRAME_TICKS: usize = 70224;
+
+// opcode of LD B,B
+const LD_B_B: u8 = 0x40;
+
+// fails to compile if a component makes the board !Send
+const _: fn() = || {
+    fn assert_send<T: Send>() {}
+    assert_send::<Board>();
+};
+
+/// Called after the instruction during which VBlank started
+pub type VBlankHook = Box<dyn FnMut(&Board) + Send>;
+/// Called with the line after the instruction during which its HBlank started
+pub type HBlankHook = Box<dyn FnMut(&Board, u8) + Send>;
+/// Called with the bit of the interrupt, as in IE, after it was dispatched
+pub type InterruptHook = Box<dyn FnMut(&Board, u8) + Send>;
+
+/// Settings which are fixed for the lifetime of a board
+#[derive(Copy, Clone)]
+pub struct Config {
+    /// Rate in Hz at which audio samples are produced
+    pub sample_rate: usize,
+    /// Model to emulate, or `None` to choose between DMG and CGB by the CGB flag of the
+    /// cartridge header
+    pub model: Option<Model>,
+    /// Colors of monochrome games, or `None` for grey on the DMG and the palette the boot
+    /// rom picks on the CGB
+    pub dmg_palette: Option<DmgPalette>,
+    /// Shows CGB colors as the real LCD does, instead of scaling them to full brightness
+    pub color_correction: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            model: None,
+            dmg_palette: None,
+            color_correction: false,
+        }
+    }
+}
+
+/// The whole console. It owns all of its components, so it can be moved between threads.
 pub struct Board {
-    cpu: Cpu,
-    apu: Rc<RefCell<Apu>>,
-    ppu: Rc<RefCell<Ppu>>,
-    mmu: Rc<RefCell<Mmu>>,
-    joypad: Rc<RefCell<JoyPad>>,
+    // the CPU owns the memory map, which owns the other components
+    cpu: Cpu<Mmu>,
+    model: Model,
+    // whether a monochrome game runs on the CGB
+    compat_mode: bool,
+    // ticks into the current frame
     ticks: usize,
+    breakpoints: Vec<Breakpoint>,
+    // interrupts which stop the debugger when dispatched, as bits of IE
+    interrupt_breaks: u8,
+    // whether LD B,B stops the debugger
+    software_breakpoints: bool,
+    vblank_hook: Option<VBlankHook>,
+    hblank_hook: Option<HBlankHook>,
+    interrupt_hook: Option<InterruptHook>,
+    // lines whose HBlank started in the last step, kept to reuse the allocation
+    hblank_lines: Vec<u8>,
+    rewind: Option<Rewind>,
 }
 
 impl Board {
-    fn create(cartridge: Cartridge, boot: bool) -> Self {
-        let apu = Rc::new(RefCell::new(Apu::new()));
-        let irq = Rc::new(RefCell::new(Irq::new()));
-        let ppu = Rc::new(RefCell::new(Ppu::new(Rc::clone(&irq))));
-        let joypad = Rc::new(RefCell::new(JoyPad::new(Rc::clone(&irq))));
-
-        let mmu = Mmu::new(
-            Rc::clone(&apu),
-            Rc::clone(&irq),
-            Rc::clone(&ppu),
-            Rc::clone(&joypad),
-            cartridge,
-        );
+    fn create(mut cartridge: Cartridge, boot: bool, config: Config) -> Self {
+        let model = match config.model {
+            Some(model) => model,
+            None if cartridge.supports_cgb() => Model::Cgb,
+            None => Model::Dmg,
+        };
+        // monochrome games run in a compatibility mode on the CGB, which only colorizes them
+        let cgb = model.is_cgb() && cartridge.supports_cgb();
+        let compat_mode = model.is_cgb() && !cgb;
+        let sgb = if model.is_sgb() {
+            Some(Sgb::new(cartridge.supports_sgb()))
+        } else {
+            None
+        };
+        if boot {
+            cartridge.set_boot_rom_size(model.boot_rom_size());
+        }
+        let header = cartridge.header().to_vec();
 
-        let mmu = Rc::new(RefCell::new(mmu));
+        let apu = Apu::new(config.sample_rate);
+        let mut ppu = Ppu::new(cgb);
+        if let Some(dmg_palette) = config.dmg_palette {
+            ppu.set_dmg_palette(dmg_palette);
+        } else if compat_mode {
+            ppu.set_dmg_palette(palette::title_palette(&header));
+        }
+        ppu.set_color_correction(config.color_correction);
+        let joypad = JoyPad::new(sgb);
+        let mmu = Mmu::new(apu, ppu, joypad, cartridge, cgb);
 
         let cpu = if boot {
-            Cpu::new(Rc::clone(&irq), Rc::clone(&mmu))
+            Cpu::new(mmu)
         } else {
-            Cpu::no_boot(Rc::clone(&irq), Rc::clone(&mmu))
+            Cpu::no_boot(mmu, model, &header)
         };
 
         Self {
             cpu,
-            apu,
-            ppu,
-            mmu,
-            joypad,
+            model,
+            compat_mode,
             ticks: 0,
+            breakpoints: Vec::new(),
+            interrupt_breaks: 0,
+            software_breakpoints: false,
+            vblank_hook: None,
+            hblank_hook: None,
+            interrupt_hook: None,
+            hblank_lines: Vec::new(),
+            rewind: None,
         }
     }
 
-    pub fn new(boot: &[u8], game: &[u8]) -> Self {
+    pub fn new(boot: &[u8], game: &[u8], config: Config) -> Self {
         let cartridge = Cartridge::new(boot, game);
-        Self::create(cartridge, true)
+        Self::create(cartridge, true, config)
     }
 
     /// Creates a board which doesn't have a boot rom.
     /// The memory and registers will be initialized such as if the execution
     /// of the boot rom just ended
-    pub fn no_boot(game: &[u8]) -> Self {
+    pub fn no_boot(game: &[u8], config: Config) -> Self {
         let cartridge = Cartridge::no_boot(game);
-        let board = Self::create(cartridge, false);
+        let mut board = Self::create(cartridge, false, config);
+
+        {
+            let model = board.model;
+            let boot_divider = model.boot_divider(model.is_cgb() && !board.compat_mode);
+            let mmu = board.mmu_mut();
+            for &(addr, value) in model.boot_io() {
+                mmu.write_byte(addr, value);
+            }
+            mmu.set_divider(boot_divider);
+        }
+
+        board
+    }
+
+    /// Creates a board which plays a song of a GBS file instead of running a game
+    pub(crate) fn gbs(gbs: &Gbs, song: u8, config: Config) -> Self {
+        let mut board = Self::no_boot(&gbs.rom(song), config);
 
         {
-            let mut mmu = board.mmu.borrow_mut();
-            mmu.write_byte(0xff10, 0x80);
-            mmu.write_byte(0xff11, 0xbf);
-            mmu.write_byte(0xff12, 0xf3);
-            mmu.write_byte(0xff14, 0xbf);
-            mmu.write_byte(0xff16, 0x3f);
-            mmu.write_byte(0xff19, 0xbf);
-            mmu.write_byte(0xff1a, 0x7f);
-            mmu.write_byte(0xff1b, 0xff);
-            mmu.write_byte(0xff1c, 0x9f);
-            mmu.write_byte(0xff1e, 0xff);
-            mmu.write_byte(0xff20, 0xff);
-            mmu.write_byte(0xff23, 0xbf);
-            mmu.write_byte(0xff24, 0x77);
-            mmu.write_byte(0xff25, 0xf3);
-            mmu.write_byte(0xff26, 0xf1);
-            mmu.write_byte(0xff40, 0x91);
-            mmu.write_byte(0xff47, 0xfc);
-            mmu.write_byte(0xff48, 0xff);
-            mmu.write_byte(0xff49, 0xff);
+            let mmu = board.mmu_mut();
+            mmu.write_byte(0xff06, gbs.timer_modulo());
+            mmu.write_byte(0xff07, gbs.timer_control());
+            // the play routine is called by either the timer or the vblank interrupt
+            let interrupt = if gbs.uses_timer() { 1 << 2 } else { 1 };
+            mmu.write_byte(0xffff, interrupt);
         }
 
         board
     }
 
+    /// Takes over the audio settings of a board this one replaces
+    pub(crate) fn copy_audio_settings(&mut self, other: &Board) {
+        self.mmu_mut().apu_mut().copy_settings(other.mmu().apu());
+    }
+
     pub fn run_to_next_frame(&mut self) {
-        let mut leftticks = (70224 - self.ticks) as isize;
-        while leftticks > 0 {
-            let steps = self.cpu.step();
-            self.mmu.borrow_mut().step(steps);
-            leftticks -= steps as isize;
+        while self.ticks < FRAME_TICKS {
+            self.step();
+        }
+        self.end_frame();
+    }
+
+    /// Runs until the PPU enters VBlank, when a frame is complete, and returns true. As
+    /// there's no VBlank while the LCD is off, it returns false after the cycles of a
+    /// frame then.
+    pub fn run_until_vblank(&mut self) -> bool {
+        let mut cycles = 0;
+        let mut vblank = false;
+        while cycles < FRAME_TICKS && !vblank {
+            let (ticks, started) = self.step();
+            cycles += ticks;
+            vblank = started;
+        }
+        // the fixed frames of `run_to_next_frame` continue from here
+        self.ticks = 0;
+        self.mmu_mut().end_frame();
+        self.save_rewind_state();
+        vblank
+    }
+
+    /// Runs at least `cycles` clock cycles of the normal speed and returns how many ran,
+    /// which exceeds `cycles` by the rest of the last instruction
+    pub fn run_cycles(&mut self, cycles: usize) -> usize {
+        let mut elapsed = 0;
+        while elapsed < cycles {
+            elapsed += self.step().0;
+            if self.ticks >= FRAME_TICKS {
+                self.end_frame();
+            }
+        }
+        elapsed
+    }
+
+    /// Calls `hook` whenever VBlank starts, or stops calling one if `None` is given
+    pub fn set_vblank_hook(&mut self, hook: Option<VBlankHook>) {
+        self.vblank_hook = hook;
+    }
+
+    /// Calls `hook` whenever the HBlank of a line starts, which allows to sample the
+    /// state per line, or stops calling one if `None` is given
+    pub fn set_hblank_hook(&mut self, hook: Option<HBlankHook>) {
+        self.hblank_hook = hook;
+    }
+
+    /// Calls `hook` whenever an interrupt is dispatched, or stops calling one if `None`
+    /// is given
+    pub fn set_interrupt_hook(&mut self, hook: Option<InterruptHook>) {
+        self.interrupt_hook = hook;
+    }
+
+    // executes one instruction or dispatches an interrupt, calls the hooks, and returns
+    // the elapsed ticks and whether VBlank started
+    fn step(&mut self) -> (usize, bool) {
+        let interrupt = match self.interrupt_hook {
+            Some(_) => self.cpu.pending_interrupt(),
+            None => None,
+        };
+        self.cpu.step();
+
+        let mut lines = core::mem::take(&mut self.hblank_lines);
+        let mmu = self.mmu_mut();
+        let ticks = mmu.take_elapsed_ticks();
+        let vblank = mmu.take_vblank_started();
+        mmu.take_hblank_lines(&mut lines);
+        self.ticks += ticks;
+
+        // the hooks are taken out while they are called, as they get the whole board
+        if let (Some(bit), Some(mut hook)) = (interrupt, self.interrupt_hook.take()) {
+            hook(self, bit);
+            self.interrupt_hook = Some(hook);
+        }
+        if let Some(mut hook) = self.hblank_hook.take() {
+            for &line in &lines {
+                hook(self, line);
+            }
+            self.hblank_hook = Some(hook);
+        }
+        if vblank {
+            if let Some(mut hook) = self.vblank_hook.take() {
+                hook(self);
+                self.vblank_hook = Some(hook);
+            }
+        }
+        lines.clear();
+        self.hblank_lines = lines;
+
+        (ticks, vblank)
+    }
+
+    fn end_frame(&mut self) {
+        self.ticks -= FRAME_TICKS;
+        self.mmu_mut().end_frame();
+        self.save_rewind_state();
+    }
+
+    /// Keeps a snapshot at the end of every `interval` frames, so that they can be stepped
+    /// back to with `rewind`. The oldest snapshots are dropped once they take more than
+    /// `capacity` bytes, so nothing is kept if a single snapshot is larger.
+    pub fn enable_rewind(&mut self, capacity: usize, interval: usize) {
+        self.rewind = Some(Rewind::new(capacity, interval));
+    }
+
+    /// Stops taking snapshots and frees the ones taken so far
+    pub fn disable_rewind(&mut self) {
+        self.rewind = None;
+    }
+
+    /// Restores the previous snapshot and returns true, or returns false if there is none
+    /// left. The screen shows the frame of the snapshot and the game continues from it.
+    pub fn rewind(&mut self) -> bool {
+        let mut rewind = match self.rewind.take() {
+            Some(rewind) => rewind,
+            None => return false,
+        };
+        let restored = match rewind.pop() {
+            Some(snapshot) => {
+                self.snapshot(&mut State::Load(snapshot));
+                true
+            }
+            None => false,
+        };
+        self.rewind = Some(rewind);
+        restored
+    }
+
+    /// Number of snapshots `rewind` can step back to
+    pub fn rewind_len(&self) -> usize {
+        self.rewind.as_ref().map_or(0, Rewind::len)
+    }
+
+    fn save_rewind_state(&mut self) {
+        if let Some(mut rewind) = self.rewind.take() {
+            if rewind.frame_ended() {
+                let mut snapshot = Vec::new();
+                self.snapshot(&mut State::Save(&mut snapshot));
+                rewind.push(snapshot);
+            }
+            self.rewind = Some(rewind);
         }
-        self.ticks = leftticks.abs() as usize;
+    }
+
+    fn mmu(&self) -> &Mmu {
+        self.cpu.bus()
+    }
+
+    fn mmu_mut(&mut self) -> &mut Mmu {
+        self.cpu.bus_mut()
+    }
+
+    /// Executes a single instruction, or dispatches an interrupt
+    pub fn step_instruction(&mut self) -> StopReason {
+        self.debug_step().0
+    }
+
+    // steps like `step_instruction` and returns the elapsed ticks too
+    fn debug_step(&mut self) -> (StopReason, usize) {
+        // hits from running without the debugger are stale
+        self.mmu().take_watch_hit();
+        let interrupt = self.cpu.pending_interrupt();
+        let (ticks, _) = self.step();
+        if self.ticks >= FRAME_TICKS {
+            self.end_frame();
+        }
+
+        let reason = match (self.mmu().take_watch_hit(), interrupt) {
+            (Some(hit), _) => hit,
+            (None, Some(bit)) if self.interrupt_breaks & (1 << bit) != 0 => {
+                StopReason::Interrupt(bit)
+            }
+            _ => StopReason::Step,
+        };
+        (reason, ticks)
+    }
+
+    /// Runs until a breakpoint, watchpoint or interrupt stops execution, or at least
+    /// `max_cycles` elapsed. Breakpoints at the current instruction are skipped, so that
+    /// execution can continue from them.
+    pub fn run_until_break(&mut self, max_cycles: usize) -> StopReason {
+        let mut cycles = 0;
+        loop {
+            let (reason, ticks) = self.debug_step();
+            cycles += ticks;
+            if reason != StopReason::Step {
+                return reason;
+            }
+
+            let registers = self.cpu.registers();
+            if self.breakpoints.iter().any(|b| b.hit(&registers)) {
+                return StopReason::Breakpoint(registers.pc);
+            }
+            let pc = registers.pc;
+            let mmu = self.mmu();
+            if self.software_breakpoints && mmu.peek(pc) == LD_B_B {
+                return StopReason::SoftwareBreakpoint(pc);
+            }
+            mmu.watch(pc, Access::Execute, mmu.peek(pc));
+            if let Some(hit) = mmu.take_watch_hit() {
+                return hit;
+            }
+            if cycles >= max_cycles {
+                return StopReason::CycleLimit;
+            }
+        }
+    }
+
+    pub fn registers(&self) -> CpuRegisters {
+        self.cpu.registers()
+    }
+
+    /// Reads memory as the CPU sees it, without side effects
+    pub fn peek(&self, addr: u16) -> u8 {
+        self.mmu().peek(addr)
+    }
+
+    /// Returns the bytes which were sent over the link cable since the last call
+    pub fn take_serial_output(&mut self) -> Vec<u8> {
+        self.mmu_mut().take_serial_output()
+    }
+
+    /// Passes a line with the CPU state to `sink` before every instruction, or stops
+    /// tracing if `None` is given. Gameboy Doctor expects LY to always read 0x90, which
+    /// has to be patched into the ROM or emulator for a matching log.
+    pub fn set_trace(&mut self, sink: Option<TraceSink>, format: TraceFormat) {
+        let tracer = sink.map(|sink| Tracer::new(sink, format));
+        self.cpu.set_tracer(tracer);
+    }
+
+    /// Writes memory as the CPU does, without triggering watchpoints
+    pub fn poke(&mut self, addr: u16, value: u8) {
+        self.mmu_mut().poke(addr, value);
+    }
+
+    pub fn set_registers(&mut self, registers: CpuRegisters) {
+        self.cpu.set_registers(registers);
+    }
+
+    /// Qualifies the address with the bank which is currently mapped at it
+    pub fn bank_address(&self, addr: u16) -> BankAddress {
+        BankAddress {
+            bank: self.mmu().bank(addr),
+            address: addr,
+        }
+    }
+
+    /// Decodes the instruction at the address
+    pub fn disassemble(&self, addr: u16) -> Instruction {
+        let mmu = self.mmu();
+        disasm::disassemble(addr, |addr| mmu.peek(addr))
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.push(breakpoint);
+    }
+
+    /// Removes all breakpoints at the address
+    pub fn remove_breakpoint(&mut self, address: u16) {
+        self.breakpoints.retain(|b| b.address != address);
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.mmu_mut().add_watchpoint(watchpoint);
+    }
+
+    pub fn remove_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.mmu_mut().remove_watchpoint(watchpoint);
+    }
+
+    pub fn clear_watchpoints(&mut self) {
+        self.mmu_mut().clear_watchpoints();
+    }
+
+    /// Stops the debugger before `LD B,B`, which emulators traditionally treat as
+    /// breakpoint
+    pub fn set_software_breakpoints(&mut self, enabled: bool) {
+        self.software_breakpoints = enabled;
+    }
+
+    /// Stops the debugger when one of the interrupts is dispatched, given as bits in the
+    /// layout of IE: vblank, LCD status, timer, serial and joypad
+    pub fn set_interrupt_breaks(&mut self, interrupts: u8) {
+        self.interrupt_breaks = interrupts;
+    }
+
+    /// Replaces the colorization of a monochrome game on the CGB, as if the button
+    /// combination was held during the boot logo. Has no effect in other modes.
+    pub fn set_compat_palette(&mut self, palette: CompatPalette) {
+        if self.compat_mode {
+            self.mmu_mut().ppu_mut().set_dmg_palette(palette.palette());
+        }
+    }
+
+    /// Replaces the colors of a monochrome game
+    pub fn set_dmg_palette(&mut self, palette: DmgPalette) {
+        self.mmu_mut().ppu_mut().set_dmg_palette(palette);
     }
 
     pub fn frame(&self) -> [[Color; WIDTH]; HEIGHT] {
-        self.ppu.borrow().frame()
+        let mmu = self.mmu();
+        match mmu.joypad().sgb() {
+            Some(sgb) => sgb.screen(mmu.ppu().shades()),
+            None => *mmu.ppu().frame(),
+        }
+    }
+
+    /// Writes the screen into `out` without copying it first, such as directly into a
+    /// texture. Rows start `pitch` bytes apart. Panics if `out` can't hold `HEIGHT` rows
+    /// of `WIDTH` pixels of the format.
+    pub fn render_into(&self, out: &mut [u8], pitch: usize, format: PixelFormat) {
+        assert_render_size(out, pitch, WIDTH, HEIGHT, format);
+        let mmu = self.mmu();
+        let shades = mmu.ppu().shades();
+        match mmu.joypad().sgb() {
+            Some(sgb) => {
+                let pixels = (0..HEIGHT).flat_map(|y| {
+                    (0..WIDTH).map(move |x| (sgb.screen_pixel(shades, x, y), shades[y][x]))
+                });
+                format.write(pixels, WIDTH, out, pitch);
+            }
+            None => {
+                let screen = mmu.ppu().frame();
+                let pixels = screen.iter().flatten().copied();
+                format.write(
+                    pixels.zip(shades.iter().flatten().copied()),
+                    WIDTH,
+                    out,
+                    pitch,
+                );
+            }
+        }
+    }
+
+    /// Writes the screen inside its border into `out` like `render_into`, if the board is
+    /// a SGB, and returns whether it did. The border has shade 0. Panics if `out` can't
+    /// hold `SGB_HEIGHT` rows of `SGB_WIDTH` pixels of the format.
+    pub fn render_sgb_into(&self, out: &mut [u8], pitch: usize, format: PixelFormat) -> bool {
+        let mmu = self.mmu();
+        let sgb = match mmu.joypad().sgb() {
+            Some(sgb) => sgb,
+            None => return false,
+        };
+        assert_render_size(out, pitch, SGB_WIDTH, SGB_HEIGHT, format);
+        let shades = mmu.ppu().shades();
+        let pixels = (0..SGB_HEIGHT).flat_map(|y| {
+            (0..SGB_WIDTH).map(move |x| {
+                let shade = match (y.checked_sub(SCREEN_Y), x.checked_sub(SCREEN_X)) {
+                    (Some(y), Some(x)) if y < HEIGHT && x < WIDTH => shades[y][x],
+                    _ => 0,
+                };
+                (sgb.frame_pixel(shades, x, y), shade)
+            })
+        });
+        format.write(pixels, SGB_WIDTH, out, pitch);
+        true
     }
 
+    /// Returns the screen inside its border, if the board is a SGB
+    pub fn sgb_frame(&self) -> Option<[[Color; SGB_WIDTH]; SGB_HEIGHT]> {
+        let mmu = self.mmu();
+        let sgb = mmu.joypad().sgb()?;
+        Some(sgb.frame(mmu.ppu().shades()))
+    }
+
+    /// Returns all buffered audio as interleaved stereo samples
     pub fn audio(&mut self) -> Vec<f32> {
-        self.apu.borrow_mut().audio_buffer()
+        let mut buffer = vec![0.0; self.audio_status().buffered];
+        self.audio_into(&mut buffer, 0);
+        buffer
+    }
+
+    /// Moves buffered audio as interleaved stereo samples into `out` and returns how many
+    /// samples were written. `needed` is how many samples the caller must have right away,
+    /// for example to keep its audio device from running dry. Those which are missing
+    /// count as underrun, while space left in `out` doesn't.
+    pub fn audio_into(&mut self, out: &mut [f32], needed: usize) -> usize {
+        self.mmu_mut().apu_mut().audio_into(out, needed)
+    }
+
+    /// Returns the fill level of the audio buffer, and how often it over- or underran
+    pub fn audio_status(&self) -> AudioStatus {
+        self.mmu().apu().audio_status()
+    }
+
+    /// Produces `ratio` times as many audio samples per emulated second, which allows
+    /// frontends to keep the audio buffer at a stable fill level. Only small deviations
+    /// from 1 are accepted.
+    pub fn adjust_audio_rate(&mut self, ratio: f64) {
+        self.mmu_mut().apu_mut().adjust_rate(ratio);
+    }
+
+    /// Starts or stops buffering the output of every channel for `channel_audio`. Taps
+    /// are off by default. Turning them on drops the samples of an earlier tap, so the
+    /// channels start in step with the mixed output of `audio`.
+    pub fn set_channel_taps(&mut self, enabled: bool) {
+        self.mmu_mut().apu_mut().set_channel_taps(enabled);
+    }
+
+    /// Returns the buffered mono output of a single channel while the taps are on,
+    /// independent of whether the channel is muted
+    pub fn channel_audio(&mut self, channel: Channel) -> Vec<f32> {
+        let apu = self.mmu_mut().apu_mut();
+        let mut buffer = vec![0.0; apu.channel_audio_len(channel)];
+        apu.channel_audio_into(channel, &mut buffer);
+        buffer
+    }
+
+    /// Moves the buffered mono output of a single channel into `out` and returns how many
+    /// samples were written
+    pub fn channel_audio_into(&mut self, channel: Channel, out: &mut [f32]) -> usize {
+        self.mmu_mut().apu_mut().channel_audio_into(channel, out)
+    }
+
+    /// Removes a channel from the mixed output of `audio`
+    pub fn set_channel_muted(&mut self, channel: Channel, muted: bool) {
+        self.mmu_mut().apu_mut().set_muted(channel, muted);
+    }
+
+    /// Restricts the mixed output of `audio` to a single channel, or restores the
+    /// regular mix if `None` is given
+    pub fn solo_channel(&mut self, channel: Option<Channel>) {
+        self.mmu_mut().apu_mut().set_solo(channel);
+    }
+
+    /// Starts recording all writes to the sound registers
+    pub fn start_vgm_log(&mut self) {
+        self.mmu_mut().apu_mut().start_vgm_log();
+    }
+
+    /// Stops recording writes to the sound registers and returns them as VGM 1.61 file,
+    /// or `None` if no recording was running
+    pub fn stop_vgm_log(&mut self) -> Option<Vec<u8>> {
+        self.mmu_mut().apu_mut().stop_vgm_log()
     }
 
     pub fn button_pressed(&mut self, button: Button) {
-        self.joypad.borrow_mut().button_pressed(button);
+        self.player_button_pressed(0, button);
     }
 
     pub fn button_released(&mut self, button: Button) {
-        self.joypad.borrow_mut().button_released(button);
+        self.player_button_released(0, button);
+    }
+
+    /// Presses a button of one of the joypads the SGB supports, counted from 0
+    pub fn player_button_pressed(&mut self, player: usize, button: Button) {
+        if player < MAX_PLAYERS {
+            self.mmu_mut().button_pressed(player, button);
+        }
+    }
+
+    pub fn player_button_released(&mut self, player: usize, button: Button) {
+        if player < MAX_PLAYERS {
+            self.mmu_mut().button_released(player, button);
+        }
+    }
+}
+
+impl Snapshot for Board {
+    fn snapshot(&mut self, state: &mut State) {
+        self.cpu.snapshot(state);
+        state.usize(&mut self.ticks);
+    }
+}
+
+fn assert_render_size(out: &[u8], pitch: usize, width: usize, height: usize, format: PixelFormat) {
+    let row_size = width * format.bytes_per_pixel();
+    assert!(
+        pitch >= row_size,
+        "pitch {} is below a row of {} bytes",
+        pitch,
+        row_size
+    );
+    assert!(out.len() >= pitch * (height - 1) + row_size);
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, vec::Vec};
+    #[cfg(feature = "std")]
+    use std::{
+        boxed::Box,
+        sync::{Arc, Mutex},
+    };
+
+    use super::{Board, Config};
+    use crate::model::Model;
+    use crate::ppu::{Color, PixelFormat, HEIGHT, WIDTH};
+    use crate::sgb::{SGB_HEIGHT, SGB_WIDTH};
+    use crate::sound::Channel;
+
+    // loops forever with the LCD on
+    fn idle_game() -> Vec<u8> {
+        let mut game = vec![0u8; 0x8000];
+        game[0x0100..0x0102].copy_from_slice(&[0x18, 0xfe]); // JR -2
+        game
+    }
+
+    // the hooks have to be Send, so they share their results through a Mutex
+    #[cfg(feature = "std")]
+    #[test]
+    fn vblank_and_hooks() {
+        let mut board = Board::no_boot(&idle_game(), Config::default());
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let hook_lines = Arc::clone(&lines);
+        board.set_hblank_hook(Some(Box::new(move |_, line| {
+            hook_lines.lock().unwrap().push(line)
+        })));
+
+        assert!(board.run_until_vblank());
+        let expected: Vec<u8> = (0..HEIGHT as u8).collect();
+        assert_eq!(*lines.lock().unwrap(), expected);
+        assert_eq!(board.peek(0xff44), HEIGHT as u8);
+
+        assert!(board.run_cycles(100) >= 100);
+
+        // without the LCD, a frame passes without VBlank
+        board.poke(0xff40, 0);
+        assert!(!board.run_until_vblank());
+    }
+
+    // splits rows of RGB pixels which start `pitch` bytes apart
+    fn rgb_rows(out: &[u8], pitch: usize, width: usize) -> Vec<Vec<Color>> {
+        let pixel = |rgb: &[u8]| Color::rgb(u32::from_be_bytes([0, rgb[0], rgb[1], rgb[2]]));
+        out.chunks(pitch)
+            .map(|row| row[..3 * width].chunks_exact(3).map(pixel).collect())
+            .collect()
+    }
+
+    #[test]
+    fn render_with_pitch() {
+        let mut board = Board::no_boot(&idle_game(), Config::default());
+        board.run_until_vblank();
+        let pitch = 3 * WIDTH + 5;
+        let mut out = vec![0; pitch * (HEIGHT - 1) + 3 * WIDTH];
+        board.render_into(&mut out, pitch, PixelFormat::Rgb24);
+        assert_eq!(rgb_rows(&out, pitch, WIDTH), board.frame().map(Vec::from));
+        assert!(!board.render_sgb_into(&mut out, pitch, PixelFormat::Rgb24));
+
+        let config = Config {
+            model: Some(Model::Sgb),
+            ..Config::default()
+        };
+        let mut board = Board::no_boot(&idle_game(), config);
+        board.run_until_vblank();
+        let pitch = 3 * SGB_WIDTH + 5;
+        let mut out = vec![0; pitch * (SGB_HEIGHT - 1) + 3 * SGB_WIDTH];
+        assert!(board.render_sgb_into(&mut out, pitch, PixelFormat::Rgb24));
+        let frame = board.sgb_frame().unwrap();
+        assert_eq!(rgb_rows(&out, pitch, SGB_WIDTH), frame.map(Vec::from));
+        board.render_into(&mut out, pitch, PixelFormat::Rgb24);
+        assert_eq!(
+            rgb_rows(&out, pitch, WIDTH)[..HEIGHT],
+            board.frame().map(Vec::from)
+        );
+    }
+
+    #[test]
+    fn peek_and_poke_io_registers() {
+        for model in [Model::Dmg, Model::Cgb] {
+            let config = Config {
+                model: Some(model),
+                ..Config::default()
+            };
+            let mut board = Board::no_boot(&idle_game(), config);
+            // the boot rom register is write only
+            assert_eq!(board.peek(0xff50), 0xff);
+            for addr in 0xff00..=0xffff {
+                let value = board.peek(addr);
+                board.poke(addr, value);
+            }
+            assert_eq!(board.peek(0xff03), 0xff);
+        }
+    }
+
+    #[test]
+    fn boot_state_per_model() {
+        // model, A, DIV, NR52 and SC
+        let expected = [
+            (Model::Dmg0, 0x01, 0x18, 0xf1, 0x7e),
+            (Model::Dmg, 0x01, 0xab, 0xf1, 0x7e),
+            (Model::Mgb, 0xff, 0xab, 0xf1, 0x7e),
+            (Model::Sgb, 0x01, 0xd8, 0xf0, 0x7e),
+            (Model::Sgb2, 0xff, 0xd8, 0xf0, 0x7e),
+            (Model::Cgb0, 0x11, 0x26, 0xf1, 0x7f),
+            (Model::Cgb, 0x11, 0x26, 0xf1, 0x7f),
+            (Model::Agb, 0x11, 0x26, 0xf1, 0x7f),
+        ];
+        for (model, a, div, nr52, sc) in expected {
+            let config = Config {
+                model: Some(model),
+                ..Config::default()
+            };
+            let board = Board::no_boot(&idle_game(), config);
+            assert_eq!(board.registers().a, a, "{:?}", model);
+            assert_eq!(board.peek(0xff04), div, "{:?}", model);
+            assert_eq!(board.peek(0xff26), nr52, "{:?}", model);
+            assert_eq!(board.peek(0xff02), sc, "{:?}", model);
+        }
+
+        let mut game = idle_game();
+        game[0x0143] = 0x80;
+        let config = Config {
+            model: Some(Model::Cgb),
+            ..Config::default()
+        };
+        assert_eq!(Board::no_boot(&game, config).peek(0xff04), 0x1e);
+    }
+
+    #[test]
+    fn compat_mode_tile_map_games() {
+        let config = Config {
+            model: Some(Model::Cgb),
+            ..Config::default()
+        };
+        let hl = |title: &[u8]| {
+            let mut game = idle_game();
+            game[0x0134..0x0134 + title.len()].copy_from_slice(title);
+            game[0x014b] = 0x01;
+            let registers = Board::no_boot(&game, config).registers();
+            (registers.b, u16::from_be_bytes([registers.h, registers.l]))
+        };
+        // the title checksum of "X" is 0x58
+        assert_eq!(hl(b"X"), (0x58, 0x991a));
+        assert_eq!(hl(b"POKEMON RED"), (0x14, 0x007c));
+    }
+
+    #[test]
+    fn channel_taps_follow_the_mix() {
+        let mut board = Board::no_boot(&idle_game(), Config::default());
+        board.run_until_vblank();
+        assert!(board.channel_audio(Channel::Pulse1).is_empty());
+
+        board.audio();
+        board.set_channel_taps(true);
+        board.run_until_vblank();
+        let mixed = board.audio();
+        assert!(!mixed.is_empty());
+        for channel in Channel::ALL {
+            assert_eq!(board.channel_audio(channel).len() * 2, mixed.len());
+        }
+    }
+
+    #[test]
+    fn rewind_frames() {
+        let mut game = vec![0u8; 0x8000];
+        // INC A, LD ($c000),A, JR -6
+        game[0x0100..0x0106].copy_from_slice(&[0x3c, 0xea, 0x00, 0xc0, 0x18, 0xfa]);
+        let mut board = Board::no_boot(&game, Config::default());
+        board.enable_rewind(1 << 20, 1);
+
+        let mut frames = Vec::new();
+        for _ in 0..4 {
+            board.run_until_vblank();
+            frames.push((board.registers(), board.peek(0xc000), board.frame()));
+        }
+        assert_eq!(board.rewind_len(), 3);
+
+        for expected in frames.iter().rev().skip(1) {
+            assert!(board.rewind());
+            assert_eq!(
+                &(board.registers(), board.peek(0xc000), board.frame()),
+                expected
+            );
+        }
+        assert!(!board.rewind());
+
+        // the game continues from the oldest frame
+        board.run_until_vblank();
+        assert_eq!(board.registers(), frames[1].0);
     }
 }
@@ -0,0 +1,30 @@
+/// Everything the CPU is connected to. The board's memory map is one bus, but the CPU
+/// runs just as well against plain RAM, a GBS player or a bus which records accesses.
+pub trait Bus {
+    fn read(&mut self, addr: u16) -> u8;
+
+    /// Reads an opcode or its operands. Unlike other reads, it doesn't trigger read
+    /// watchpoints.
+    fn fetch(&mut self, addr: u16) -> u8 {
+        self.read(addr)
+    }
+
+    fn write(&mut self, addr: u16, value: u8);
+
+    /// Advances the rest of the system by one M-cycle, which is 4 clock cycles
+    fn tick(&mut self);
+
+    /// Reads a byte without side effects, for tracing and debugging
+    fn peek(&self, addr: u16) -> u8;
+
+    /// Interrupts which are both requested and enabled, as bits of IF and IE
+    fn pending_interrupts(&self) -> u8 {
+        0
+    }
+
+    /// Clears the request of the interrupt the CPU dispatches
+    fn acknowledge_interrupt(&mut self, _bit: u8) {}
+
+    /// Executes STOP, which switches the CGB speed if armed
+    fn stop(&mut self) {}
+}
@@ -1,54 +1,114 @@
+use crate::state::{Snapshot, State};
+
+use alloc::vec::Vec;
+
+const ROM_BANK_SIZE: usize = 0x4000;
+const RAM_SIZE: usize = 0x2000;
+
 pub(crate) struct Cartridge {
-    boot_rom: [u8; 0x100],
+    // mapped to 0x0000-0x00ff, and 0x0200-0x08ff for the larger CGB boot roms
+    boot_rom: Vec<u8>,
     // TODO: split into mbc
-    game_data: [u8; 0x8000],
+    game_data: Vec<u8>,
+    // bank mapped into 0x4000-0x7fff
+    rom_bank: usize,
+    ram: [u8; RAM_SIZE],
     use_boot_rom: bool,
 }
 
 impl Cartridge {
     pub fn new(boot: &[u8], game: &[u8]) -> Self {
-        let mut boot_rom = [0u8; 0x100];
-        for (&x, p) in boot.iter().zip(boot_rom.iter_mut()) {
-            *p = x;
-        }
-        let mut game_data = [0u8; 0x8000];
-        for (&x, p) in game.iter().zip(game_data.iter_mut()) {
-            *p = x;
-        }
-
         Self {
-            boot_rom,
-            game_data,
+            boot_rom: boot.to_vec(),
+            game_data: Self::game_data(game),
+            rom_bank: 1,
+            ram: [0; RAM_SIZE],
             use_boot_rom: true,
         }
     }
 
     pub fn no_boot(game: &[u8]) -> Self {
-        let mut game_data = [0u8; 0x8000];
-        for (&x, p) in game.iter().zip(game_data.iter_mut()) {
-            *p = x;
-        }
-
         Self {
-            boot_rom: [0u8; 0x100],
-            game_data,
+            boot_rom: Vec::new(),
+            game_data: Self::game_data(game),
+            rom_bank: 1,
+            ram: [0; RAM_SIZE],
             use_boot_rom: false,
         }
     }
 
+    /// Pads the game data to whole banks, but at least to the two banks which are
+    /// always mapped
+    fn game_data(game: &[u8]) -> Vec<u8> {
+        let banks = game.len().div_ceil(ROM_BANK_SIZE).max(2);
+        let mut game_data = game.to_vec();
+        game_data.resize(banks * ROM_BANK_SIZE, 0);
+        game_data
+    }
+
+    /// Whether the header flags the game as enhanced for or exclusive to the CGB
+    pub fn supports_cgb(&self) -> bool {
+        (self.game_data[0x0143] & 0x80) != 0
+    }
+
+    /// Whether the game uses SGB functions, which the SGB only accepts from games with
+    /// the SGB flag and the licensee code 0x33
+    pub fn supports_sgb(&self) -> bool {
+        self.game_data[0x0146] == 0x03 && self.game_data[0x014b] == 0x33
+    }
+
+    /// Cuts or pads the boot rom to the size the model maps
+    pub fn set_boot_rom_size(&mut self, size: usize) {
+        self.boot_rom.resize(size, 0);
+    }
+
+    /// The bank mapped into 0x4000-0x7fff
+    pub fn rom_bank(&self) -> usize {
+        self.rom_bank
+    }
+
+    /// The header at 0x0100-0x014f
+    pub fn header(&self) -> &[u8] {
+        &self.game_data[0x0100..0x0150]
+    }
+
     pub fn read_byte(&self, addr: u16) -> u8 {
-        if self.use_boot_rom && addr < 0x100 {
-            self.boot_rom[addr as usize]
-        } else {
-            self.game_data[addr as usize]
+        let boot_rom_size = self.boot_rom.len() as u16;
+        match addr {
+            0x0000..=0x00ff if self.use_boot_rom => self.boot_rom[addr as usize],
+            0x0200..=0x08ff if self.use_boot_rom && addr < boot_rom_size => {
+                self.boot_rom[addr as usize]
+            }
+            0x0000..=0x3fff => self.game_data[addr as usize],
+            0x4000..=0x7fff => {
+                let offset = self.rom_bank * ROM_BANK_SIZE + (addr as usize - 0x4000);
+                self.game_data[offset % self.game_data.len()]
+            }
+            0xa000..=0xbfff => self.ram[addr as usize - 0xa000],
+            _ => 0xff,
         }
     }
 
     pub fn write_byte(&mut self, addr: u16, val: u8) {
         match addr {
+            // Bank switching as done by MBC1 and expected by GBS rips. Smaller games
+            // don't have any banks to switch.
+            0x2000..=0x3fff if self.game_data.len() > 2 * ROM_BANK_SIZE => {
+                self.rom_bank = (val as usize).max(1);
+            }
             0x0000..=0x7fff => {}
+            0xa000..=0xbfff => self.ram[addr as usize - 0xa000] = val,
             0xff50 => self.use_boot_rom = false,
-            _ => self.game_data[addr as usize] = val,
+            _ => {}
         }
     }
 }
+
+/// The ROM itself never changes, only the mapping and the cartridge RAM
+impl Snapshot for Cartridge {
+    fn snapshot(&mut self, state: &mut State) {
+        state.usize(&mut self.rom_bank);
+        state.bytes(&mut self.ram);
+        state.bool(&mut self.use_boot_rom);
+    }
+}
@@ -1,64 +1,141 @@
-use std::cell::RefCell;
-use std::rc::Rc;
-
-use log::{error, trace};
+use log::{error, log_enabled, trace, Level};
 
 use crate::alu;
-use crate::irq::Irq;
-use crate::mmu::Mmu;
+use crate::bus::Bus;
+use crate::debugger::CpuRegisters;
+use crate::disasm;
+use crate::model::Model;
 use crate::registers::Registers;
+use crate::state::{Snapshot, State};
+use crate::trace::Tracer;
 
-pub(crate) struct Cpu {
+/// The SM83 CPU, which executes instructions against any [`Bus`]
+pub struct Cpu<B: Bus> {
     registers: Registers,
-    irq: Rc<RefCell<Irq>>,
-    mmu: Rc<RefCell<Mmu>>,
+    bus: B,
 
     // interrupt master enabled flag
     ime: bool,
+    // EI sets the flag only after the next instruction
+    ime_pending: bool,
+    // clock cycles executed so far
+    cycles: u64,
+    // M-cycles which the bus was ticked in the current step
+    step_ticks: u8,
+    tracer: Option<Tracer>,
 }
 
-impl Cpu {
-    pub fn new(irq: Rc<RefCell<Irq>>, mmu: Rc<RefCell<Mmu>>) -> Self {
+impl<B: Bus> Cpu<B> {
+    /// Creates a CPU in the state at power on, as the boot rom expects it
+    pub fn new(bus: B) -> Self {
         Cpu {
-            irq,
             registers: Registers::new(),
-            mmu,
+            bus,
             ime: false,
+            ime_pending: false,
+            cycles: 0,
+            step_ticks: 0,
+            tracer: None,
         }
     }
 
-    pub fn no_boot(irq: Rc<RefCell<Irq>>, mmu: Rc<RefCell<Mmu>>) -> Self {
+    pub(crate) fn no_boot(bus: B, model: Model, header: &[u8]) -> Self {
         Cpu {
-            irq,
-            registers: Registers::no_boot(),
-            mmu,
+            registers: Registers::no_boot(model, header),
+            bus,
             ime: false,
+            ime_pending: false,
+            cycles: 0,
+            step_ticks: 0,
+            tracer: None,
         }
     }
 
+    /// Executes one instruction or dispatches an interrupt and returns the elapsed clock
+    /// cycles. The bus is ticked before every memory access, so each access happens in
+    /// its own M-cycle, and for the remaining internal M-cycles at the end.
     pub fn step(&mut self) -> u8 {
-        if self.handle_interrupt() {
-            16
+        self.step_ticks = 0;
+        let enable_ime = self.ime_pending;
+        let cycles = if self.handle_interrupt() {
+            20
         } else {
+            self.trace();
             self.execute()
+        };
+        // unless the instruction after EI was DI
+        if enable_ime && self.ime_pending {
+            self.ime = true;
+            self.ime_pending = false;
         }
+        for _ in self.step_ticks..cycles / 4 {
+            self.bus.tick();
+        }
+        self.cycles += cycles as u64;
+        cycles
     }
 
-    fn handle_interrupt(&mut self) -> bool {
-        let interrupt_enable = self.mmu.borrow().interrupt_enable();
-        let interrupt_flag = self.irq.borrow().interrupt_flag();
-        let interrupts = interrupt_enable & interrupt_flag;
+    pub fn bus(&self) -> &B {
+        &self.bus
+    }
+
+    pub fn bus_mut(&mut self) -> &mut B {
+        &mut self.bus
+    }
+
+    pub(crate) fn set_tracer(&mut self, tracer: Option<Tracer>) {
+        self.tracer = tracer;
+    }
 
-        if !self.ime || interrupts == 0 {
-            return false;
+    fn trace(&mut self) {
+        if let Some(tracer) = self.tracer.as_mut() {
+            let registers = CpuRegisters::new(&self.registers, self.ime);
+            let bus = &self.bus;
+            let pc = registers.pc;
+            let pc_memory = [0, 1, 2, 3].map(|offset| bus.peek(pc.wrapping_add(offset)));
+            tracer.trace(&registers, pc_memory, self.cycles, bus.peek(0xff44));
         }
+    }
 
-        self.ime = false;
+    pub fn registers(&self) -> CpuRegisters {
+        CpuRegisters::new(&self.registers, self.ime)
+    }
+
+    pub fn set_registers(&mut self, registers: CpuRegisters) {
+        self.registers
+            .set_af(u16::from_be_bytes([registers.a, registers.f]));
+        self.registers.set_b(registers.b);
+        self.registers.set_c(registers.c);
+        self.registers.set_d(registers.d);
+        self.registers.set_e(registers.e);
+        self.registers.set_h(registers.h);
+        self.registers.set_l(registers.l);
+        self.registers.set_sp(registers.sp);
+        self.registers.set_pc(registers.pc);
+        self.ime = registers.ime;
+    }
 
-        let bit = interrupts.trailing_zeros() as u8;
-        let interrupt_flag = interrupt_flag & !(1 << bit);
-        self.irq.borrow_mut().set_interrupt_flag(interrupt_flag);
+    /// The interrupt which the next step dispatches, if any
+    pub fn pending_interrupt(&self) -> Option<u8> {
+        let interrupts = self.bus.pending_interrupts();
+        if self.ime && interrupts != 0 {
+            Some(interrupts.trailing_zeros() as u8)
+        } else {
+            None
+        }
+    }
+
+    fn handle_interrupt(&mut self) -> bool {
+        let bit = match self.pending_interrupt() {
+            Some(bit) => bit,
+            None => return false,
+        };
+
+        self.ime = false;
+        self.bus.acknowledge_interrupt(bit);
 
+        // two internal M-cycles, the second of which is part of the push
+        self.tick();
         self.push(self.registers.pc());
         let pc = 0x40 + bit * 0x08;
         self.registers.set_pc(pc as u16);
@@ -66,32 +143,75 @@ impl Cpu {
         true
     }
 
+    // Spends an M-cycle, either for a memory access or internally
+    fn tick(&mut self) {
+        self.bus.tick();
+        self.step_ticks += 1;
+    }
+
+    fn read(&mut self, addr: u16) -> u8 {
+        self.tick();
+        self.bus.read(addr)
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        self.tick();
+        self.bus.write(addr, value);
+    }
+
+    fn fetch(&mut self, addr: u16) -> u8 {
+        self.tick();
+        self.bus.fetch(addr)
+    }
+
+    fn read_word(&mut self, addr: u16) -> u16 {
+        let low = self.read(addr);
+        let high = self.read(addr.wrapping_add(1));
+        u16::from_le_bytes([low, high])
+    }
+
+    fn write_word(&mut self, addr: u16, value: u16) {
+        let [low, high] = value.to_le_bytes();
+        self.write(addr, low);
+        self.write(addr.wrapping_add(1), high);
+    }
+
     fn fetch_byte(&mut self) -> u8 {
         let pc = self.registers.pc();
         self.registers.inc_pc(1);
-        self.mmu.borrow().read_byte(pc)
+        self.fetch(pc)
     }
 
     fn fetch_word(&mut self) -> u16 {
-        let pc = self.registers.pc();
-        self.registers.inc_pc(2);
-        self.mmu.borrow().read_word(pc)
+        let low = self.fetch_byte();
+        let high = self.fetch_byte();
+        u16::from_le_bytes([low, high])
     }
 
+    // Pushes after an internal M-cycle which decrements SP, writing the high byte first
     fn push(&mut self, value: u16) {
-        let sp = self.registers.sp() - 2;
-        self.mmu.borrow_mut().write_word(sp, value);
-        self.registers.set_sp(sp);
+        self.tick();
+        let [low, high] = value.to_le_bytes();
+        let sp = self.registers.sp();
+        self.write(sp.wrapping_sub(1), high);
+        self.write(sp.wrapping_sub(2), low);
+        self.registers.set_sp(sp.wrapping_sub(2));
     }
 
     fn pop(&mut self) -> u16 {
         let sp = self.registers.sp();
-        let value = self.mmu.borrow().read_word(sp);
+        let value = self.read_word(sp);
         self.registers.set_sp(sp + 2);
         value
     }
 
     fn execute(&mut self) -> u8 {
+        if log_enabled!(Level::Trace) {
+            let pc = self.registers.pc();
+            let bus = &self.bus;
+            let instruction = disasm::disassemble(pc, |addr| bus.peek(addr));
+            trace!("{:04x}: {}", pc, instruction.text);
+        }
         let opcode = self.fetch_byte();
         let res = match opcode {
             0x00 => self.op_0000(),
@@ -611,15 +731,11 @@ impl Cpu {
 
     /// NOP
     fn op_0000(&mut self) -> u8 {
-        trace!("NOP");
-
         4
     }
 
     /// LD BC,d16
     fn op_0001(&mut self) -> u8 {
-        trace!("LD BC,d16");
-
         let val = self.fetch_word();
         self.registers.set_bc(val);
 
@@ -628,19 +744,15 @@ impl Cpu {
 
     /// LD (BC),A
     fn op_0002(&mut self) -> u8 {
-        trace!("LD (BC),A");
-
         let val = self.registers.a();
         let addr = self.registers.bc();
-        self.mmu.borrow_mut().write_byte(addr, val);
+        self.write(addr, val);
 
         8
     }
 
     /// INC BC
     fn op_0003(&mut self) -> u8 {
-        trace!("INC BC");
-
         let val = self.registers.bc().wrapping_add(1);
         self.registers.set_bc(val);
 
@@ -659,8 +771,6 @@ impl Cpu {
 
     /// INC B
     fn op_0004(&mut self) -> u8 {
-        trace!("INC B");
-
         let val = self.registers.b();
         let res = self.inc_8bit(val);
         self.registers.set_b(res);
@@ -680,8 +790,6 @@ impl Cpu {
 
     /// DEC B
     fn op_0005(&mut self) -> u8 {
-        trace!("DEC B");
-
         let val = self.registers.b();
         let res = self.dec_8bit(val);
         self.registers.set_b(res);
@@ -691,8 +799,6 @@ impl Cpu {
 
     /// LD B,d8
     fn op_0006(&mut self) -> u8 {
-        trace!("LD B,d8");
-
         let val = self.fetch_byte();
         self.registers.set_b(val);
 
@@ -701,8 +807,6 @@ impl Cpu {
 
     /// RLCA
     fn op_0007(&mut self) -> u8 {
-        trace!("RLCA");
-
         let res = self.registers.a().rotate_left(1);
         let carry = (res & 0x01) != 0;
 
@@ -717,19 +821,15 @@ impl Cpu {
 
     /// LD (a16),SP
     fn op_0008(&mut self) -> u8 {
-        trace!("LD (a16),SP");
-
         let addr = self.fetch_word();
         let sp = self.registers.sp();
-        self.mmu.borrow_mut().write_word(addr, sp);
+        self.write_word(addr, sp);
 
         20
     }
 
     /// ADD HL,BC
     fn op_0009(&mut self) -> u8 {
-        trace!("ADD HL,BC");
-
         let hl = self.registers.hl();
         let bc = self.registers.bc();
         let (res, carry, half_carry) = alu::add2_16bit(hl, bc);
@@ -744,10 +844,8 @@ impl Cpu {
 
     /// LD A,(BC)
     fn op_000a(&mut self) -> u8 {
-        trace!("LD A,(BC)");
-
         let addr = self.registers.bc();
-        let val = self.mmu.borrow().read_byte(addr);
+        let val = self.read(addr);
         self.registers.set_a(val);
 
         8
@@ -755,8 +853,6 @@ impl Cpu {
 
     /// DEC BC
     fn op_000b(&mut self) -> u8 {
-        trace!("DEC BC");
-
         let val = self.registers.bc().wrapping_sub(1);
         self.registers.set_bc(val);
 
@@ -765,8 +861,6 @@ impl Cpu {
 
     /// INC C
     fn op_000c(&mut self) -> u8 {
-        trace!("INC C");
-
         let val = self.registers.c();
         let res = self.inc_8bit(val);
         self.registers.set_c(res);
@@ -776,8 +870,6 @@ impl Cpu {
 
     /// DEC C
     fn op_000d(&mut self) -> u8 {
-        trace!("DEC C");
-
         let val = self.registers.c();
         let res = self.dec_8bit(val);
         self.registers.set_c(res);
@@ -787,8 +879,6 @@ impl Cpu {
 
     /// LD C,d8
     fn op_000e(&mut self) -> u8 {
-        trace!("LD C,d8");
-
         let val = self.fetch_byte();
         self.registers.set_c(val);
 
@@ -797,8 +887,6 @@ impl Cpu {
 
     /// RRCA
     fn op_000f(&mut self) -> u8 {
-        trace!("RRCA");
-
         let res = self.registers.a().rotate_right(1);
 
         self.registers.set_a(res);
@@ -812,17 +900,18 @@ impl Cpu {
 
     /// STOP 0
     fn op_0010(&mut self) -> u8 {
-        trace!("STOP 0");
+        // the byte after STOP is skipped
+        self.registers.inc_pc(1);
+        // on the CGB, STOP switches the speed if KEY1 prepared it
+        self.bus.stop();
 
-        // TODO
+        // TODO: stop until a button is pressed
 
         4
     }
 
     /// LD DE,d16
     fn op_0011(&mut self) -> u8 {
-        trace!("LD DE,d16");
-
         let val = self.fetch_word();
         self.registers.set_de(val);
 
@@ -831,19 +920,15 @@ impl Cpu {
 
     /// LD (DE),A
     fn op_0012(&mut self) -> u8 {
-        trace!("LD (DE),A");
-
         let val = self.registers.a();
         let addr = self.registers.de();
-        self.mmu.borrow_mut().write_byte(addr, val);
+        self.write(addr, val);
 
         8
     }
 
     /// INC DE
     fn op_0013(&mut self) -> u8 {
-        trace!("INC DE");
-
         let val = self.registers.de().wrapping_add(1);
         self.registers.set_de(val);
 
@@ -852,8 +937,6 @@ impl Cpu {
 
     /// INC D
     fn op_0014(&mut self) -> u8 {
-        trace!("INC D");
-
         let val = self.registers.d();
         let res = self.inc_8bit(val);
         self.registers.set_d(res);
@@ -863,8 +946,6 @@ impl Cpu {
 
     /// DEC D
     fn op_0015(&mut self) -> u8 {
-        trace!("DEC D");
-
         let val = self.registers.d();
         let res = self.dec_8bit(val);
         self.registers.set_d(res);
@@ -874,8 +955,6 @@ impl Cpu {
 
     /// LD D,d8
     fn op_0016(&mut self) -> u8 {
-        trace!("LD D,d8");
-
         let val = self.fetch_byte();
         self.registers.set_d(val);
 
@@ -884,8 +963,6 @@ impl Cpu {
 
     /// RLA
     fn op_0017(&mut self) -> u8 {
-        trace!("RLA");
-
         let a = self.registers.a();
         let carry = (a & 0x80) != 0;
         let prev_carry = self.registers.carry_flag() as u8;
@@ -901,8 +978,6 @@ impl Cpu {
 
     /// JR r8
     fn op_0018(&mut self) -> u8 {
-        trace!("JR r8");
-
         let jump = alu::signed_byte_to_u16(self.fetch_byte());
         self.registers.inc_pc(jump);
 
@@ -911,8 +986,6 @@ impl Cpu {
 
     /// ADD HL,DE
     fn op_0019(&mut self) -> u8 {
-        trace!("ADD HL,DE");
-
         let hl = self.registers.hl();
         let de = self.registers.de();
         let (res, carry, half_carry) = alu::add2_16bit(hl, de);
@@ -927,10 +1000,8 @@ impl Cpu {
 
     /// LD A,(DE)
     fn op_001a(&mut self) -> u8 {
-        trace!("LD A,(DE)");
-
         let addr = self.registers.de();
-        let val = self.mmu.borrow().read_byte(addr);
+        let val = self.read(addr);
         self.registers.set_a(val);
 
         8
@@ -938,8 +1009,6 @@ impl Cpu {
 
     /// DEC DE
     fn op_001b(&mut self) -> u8 {
-        trace!("DEC DE");
-
         let val = self.registers.de().wrapping_sub(1);
         self.registers.set_de(val);
 
@@ -948,8 +1017,6 @@ impl Cpu {
 
     /// INC E
     fn op_001c(&mut self) -> u8 {
-        trace!("INC E");
-
         let val = self.registers.e();
         let res = self.inc_8bit(val);
         self.registers.set_e(res);
@@ -959,8 +1026,6 @@ impl Cpu {
 
     /// DEC E
     fn op_001d(&mut self) -> u8 {
-        trace!("DEC E");
-
         let val = self.registers.e();
         let res = self.dec_8bit(val);
         self.registers.set_e(res);
@@ -970,8 +1035,6 @@ impl Cpu {
 
     /// LD E,d8
     fn op_001e(&mut self) -> u8 {
-        trace!("LD E,d8");
-
         let val = self.fetch_byte();
         self.registers.set_e(val);
 
@@ -980,8 +1043,6 @@ impl Cpu {
 
     /// RRA
     fn op_001f(&mut self) -> u8 {
-        trace!("RRA");
-
         let val = self.registers.a();
         let carry = (val & 0x01) != 0;
         let prev_carry = self.registers.carry_flag() as u8;
@@ -998,8 +1059,6 @@ impl Cpu {
 
     /// JR NZ,r8
     fn op_0020(&mut self) -> u8 {
-        trace!("JR NZ,r8");
-
         let jump = alu::signed_byte_to_u16(self.fetch_byte());
         if !self.registers.zero_flag() {
             self.registers.inc_pc(jump);
@@ -1011,8 +1070,6 @@ impl Cpu {
 
     /// LD HL,d16
     fn op_0021(&mut self) -> u8 {
-        trace!("LD HL,d16");
-
         let val = self.fetch_word();
         self.registers.set_hl(val);
 
@@ -1021,11 +1078,9 @@ impl Cpu {
 
     /// LD (HL+),A
     fn op_0022(&mut self) -> u8 {
-        trace!("LD (HL+),A");
-
         let val = self.registers.a();
         let addr = self.registers.hl();
-        self.mmu.borrow_mut().write_byte(addr, val);
+        self.write(addr, val);
 
         let hl = addr.wrapping_add(1);
         self.registers.set_hl(hl);
@@ -1035,8 +1090,6 @@ impl Cpu {
 
     /// INC HL
     fn op_0023(&mut self) -> u8 {
-        trace!("INC HL");
-
         let val = self.registers.hl().wrapping_add(1);
         self.registers.set_hl(val);
 
@@ -1045,8 +1098,6 @@ impl Cpu {
 
     /// INC H
     fn op_0024(&mut self) -> u8 {
-        trace!("INC H");
-
         let val = self.registers.h();
         let res = self.inc_8bit(val);
         self.registers.set_h(res);
@@ -1056,8 +1107,6 @@ impl Cpu {
 
     /// DEC H
     fn op_0025(&mut self) -> u8 {
-        trace!("DEC H");
-
         let val = self.registers.h();
         let res = self.dec_8bit(val);
         self.registers.set_h(res);
@@ -1067,8 +1116,6 @@ impl Cpu {
 
     /// LD H,d8
     fn op_0026(&mut self) -> u8 {
-        trace!("LD H,d8");
-
         let val = self.fetch_byte();
         self.registers.set_h(val);
 
@@ -1077,8 +1124,6 @@ impl Cpu {
 
     /// DAA
     fn op_0027(&mut self) -> u8 {
-        trace!("DAA");
-
         let value = self.registers.a();
         let half_carry = self.registers.half_carry_flag();
         let negative = self.registers.negative_flag();
@@ -1100,7 +1145,7 @@ impl Cpu {
             value.wrapping_sub(adjust)
         } else {
             value.wrapping_add(adjust)
-        } as u8;
+        };
         let new_zero = new_value == 0;
 
         self.registers.set_a(new_value);
@@ -1114,8 +1159,6 @@ impl Cpu {
 
     /// JR Z,r8
     fn op_0028(&mut self) -> u8 {
-        trace!("JR Z,r8");
-
         let jump = alu::signed_byte_to_u16(self.fetch_byte());
         if self.registers.zero_flag() {
             self.registers.inc_pc(jump);
@@ -1127,8 +1170,6 @@ impl Cpu {
 
     /// ADD HL,HL
     fn op_0029(&mut self) -> u8 {
-        trace!("ADD HL,HL");
-
         let hl = self.registers.hl();
         let (res, carry, half_carry) = alu::add2_16bit(hl, hl);
 
@@ -1142,12 +1183,10 @@ impl Cpu {
 
     /// LD A,(HL+)
     fn op_002a(&mut self) -> u8 {
-        trace!("LD A,(HL+)");
-
         let addr = self.registers.hl();
         let hl = self.registers.hl().wrapping_add(1);
         self.registers.set_hl(hl);
-        let val = self.mmu.borrow().read_byte(addr);
+        let val = self.read(addr);
         self.registers.set_a(val);
 
         8
@@ -1155,8 +1194,6 @@ impl Cpu {
 
     /// DEC HL
     fn op_002b(&mut self) -> u8 {
-        trace!("DEC HL");
-
         let val = self.registers.hl().wrapping_sub(1);
         self.registers.set_hl(val);
 
@@ -1165,8 +1202,6 @@ impl Cpu {
 
     /// INC L
     fn op_002c(&mut self) -> u8 {
-        trace!("INC L");
-
         let val = self.registers.l();
         let res = self.inc_8bit(val);
         self.registers.set_l(res);
@@ -1176,8 +1211,6 @@ impl Cpu {
 
     /// DEC L
     fn op_002d(&mut self) -> u8 {
-        trace!("DEC L");
-
         let val = self.registers.l();
         let res = self.dec_8bit(val);
         self.registers.set_l(res);
@@ -1187,8 +1220,6 @@ impl Cpu {
 
     /// LD L,d8
     fn op_002e(&mut self) -> u8 {
-        trace!("LD L,d8");
-
         let val = self.fetch_byte();
         self.registers.set_l(val);
 
@@ -1197,8 +1228,6 @@ impl Cpu {
 
     /// CPL
     fn op_002f(&mut self) -> u8 {
-        trace!("CPL");
-
         let a = !self.registers.a();
 
         self.registers.set_a(a);
@@ -1210,8 +1239,6 @@ impl Cpu {
 
     /// JR NC,r8
     fn op_0030(&mut self) -> u8 {
-        trace!("JR NC,r8");
-
         let jump = alu::signed_byte_to_u16(self.fetch_byte());
         if !self.registers.carry_flag() {
             self.registers.inc_pc(jump);
@@ -1223,8 +1250,6 @@ impl Cpu {
 
     /// LD SP,d16
     fn op_0031(&mut self) -> u8 {
-        trace!("LD SP,d16");
-
         let val = self.fetch_word();
         self.registers.set_sp(val);
 
@@ -1233,11 +1258,9 @@ impl Cpu {
 
     /// LD (HL-),A
     fn op_0032(&mut self) -> u8 {
-        trace!("LD (HL-),A");
-
         let val = self.registers.a();
         let addr = self.registers.hl();
-        self.mmu.borrow_mut().write_byte(addr, val);
+        self.write(addr, val);
 
         let hl = self.registers.hl().wrapping_sub(1);
         self.registers.set_hl(hl);
@@ -1247,8 +1270,6 @@ impl Cpu {
 
     /// INC SP
     fn op_0033(&mut self) -> u8 {
-        trace!("INC SP");
-
         let val = self.registers.sp().wrapping_add(1);
         self.registers.set_sp(val);
 
@@ -1257,43 +1278,35 @@ impl Cpu {
 
     /// INC (HL)
     fn op_0034(&mut self) -> u8 {
-        trace!("INC (HL)");
-
         let addr = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(addr);
+        let val = self.read(addr);
         let res = self.inc_8bit(val);
-        self.mmu.borrow_mut().write_byte(addr, res);
+        self.write(addr, res);
 
         12
     }
 
     /// DEC (HL)
     fn op_0035(&mut self) -> u8 {
-        trace!("DEC (HL)");
-
         let addr = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(addr);
+        let val = self.read(addr);
         let res = self.dec_8bit(val);
-        self.mmu.borrow_mut().write_byte(addr, res);
+        self.write(addr, res);
 
         12
     }
 
     /// LD (HL),d8
     fn op_0036(&mut self) -> u8 {
-        trace!("LD (HL),d8");
-
         let val = self.fetch_byte();
         let addr = self.registers.hl();
-        self.mmu.borrow_mut().write_byte(addr, val);
+        self.write(addr, val);
 
         12
     }
 
     /// SCF
     fn op_0037(&mut self) -> u8 {
-        trace!("SCF");
-
         self.registers.set_negative_flag(false);
         self.registers.set_half_carry_flag(false);
         self.registers.set_carry_flag(true);
@@ -1303,8 +1316,6 @@ impl Cpu {
 
     /// JR C,r8
     fn op_0038(&mut self) -> u8 {
-        trace!("JR C,r8");
-
         let jump = alu::signed_byte_to_u16(self.fetch_byte());
         if self.registers.carry_flag() {
             self.registers.inc_pc(jump);
@@ -1316,8 +1327,6 @@ impl Cpu {
 
     /// ADD HL,SP
     fn op_0039(&mut self) -> u8 {
-        trace!("ADD HL,SP");
-
         let hl = self.registers.hl();
         let sp = self.registers.sp();
         let (res, carry, half_carry) = alu::add2_16bit(hl, sp);
@@ -1332,12 +1341,10 @@ impl Cpu {
 
     /// LD A,(HL-)
     fn op_003a(&mut self) -> u8 {
-        trace!("LD A,(HL-)");
-
         let addr = self.registers.hl();
         let hl = self.registers.hl().wrapping_sub(1);
         self.registers.set_hl(hl);
-        let val = self.mmu.borrow().read_byte(addr);
+        let val = self.read(addr);
         self.registers.set_a(val);
 
         8
@@ -1345,8 +1352,6 @@ impl Cpu {
 
     /// DEC SP
     fn op_003b(&mut self) -> u8 {
-        trace!("DEC SP");
-
         let val = self.registers.sp().wrapping_sub(1);
         self.registers.set_sp(val);
 
@@ -1355,8 +1360,6 @@ impl Cpu {
 
     /// INC A
     fn op_003c(&mut self) -> u8 {
-        trace!("INC A");
-
         let val = self.registers.a();
         let res = self.inc_8bit(val);
         self.registers.set_a(res);
@@ -1366,8 +1369,6 @@ impl Cpu {
 
     /// DEC A
     fn op_003d(&mut self) -> u8 {
-        trace!("DEC A");
-
         let val = self.registers.a();
         let res = self.dec_8bit(val);
         self.registers.set_a(res);
@@ -1377,8 +1378,6 @@ impl Cpu {
 
     /// LD A,d8
     fn op_003e(&mut self) -> u8 {
-        trace!("LD A,d8");
-
         let val = self.fetch_byte();
         self.registers.set_a(val);
 
@@ -1387,8 +1386,6 @@ impl Cpu {
 
     /// CCF
     fn op_003f(&mut self) -> u8 {
-        trace!("CCF");
-
         let carry = !self.registers.carry_flag();
         self.registers.set_negative_flag(false);
         self.registers.set_half_carry_flag(false);
@@ -1399,8 +1396,6 @@ impl Cpu {
 
     /// LD B,B
     fn op_0040(&mut self) -> u8 {
-        trace!("LD B,B");
-
         let val = self.registers.b();
         self.registers.set_b(val);
 
@@ -1409,8 +1404,6 @@ impl Cpu {
 
     /// LD B,C
     fn op_0041(&mut self) -> u8 {
-        trace!("LD B,C");
-
         let val = self.registers.c();
         self.registers.set_b(val);
 
@@ -1419,8 +1412,6 @@ impl Cpu {
 
     /// LD B,D
     fn op_0042(&mut self) -> u8 {
-        trace!("LD B,D");
-
         let val = self.registers.d();
         self.registers.set_b(val);
 
@@ -1429,8 +1420,6 @@ impl Cpu {
 
     /// LD B,E
     fn op_0043(&mut self) -> u8 {
-        trace!("LD B,E");
-
         let val = self.registers.e();
         self.registers.set_b(val);
 
@@ -1439,8 +1428,6 @@ impl Cpu {
 
     /// LD B,H
     fn op_0044(&mut self) -> u8 {
-        trace!("LD B,H");
-
         let val = self.registers.h();
         self.registers.set_b(val);
 
@@ -1449,8 +1436,6 @@ impl Cpu {
 
     /// LD B,L
     fn op_0045(&mut self) -> u8 {
-        trace!("LD B,L");
-
         let val = self.registers.l();
         self.registers.set_b(val);
 
@@ -1459,10 +1444,8 @@ impl Cpu {
 
     /// LD B,(HL)
     fn op_0046(&mut self) -> u8 {
-        trace!("LD B,(HL)");
-
         let addr = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(addr);
+        let val = self.read(addr);
         self.registers.set_b(val);
 
         8
@@ -1470,8 +1453,6 @@ impl Cpu {
 
     /// LD B,A
     fn op_0047(&mut self) -> u8 {
-        trace!("LD B,A");
-
         let val = self.registers.a();
         self.registers.set_b(val);
 
@@ -1480,8 +1461,6 @@ impl Cpu {
 
     /// LD C,B
     fn op_0048(&mut self) -> u8 {
-        trace!("LD C,B");
-
         let val = self.registers.b();
         self.registers.set_c(val);
 
@@ -1490,8 +1469,6 @@ impl Cpu {
 
     /// LD C,C
     fn op_0049(&mut self) -> u8 {
-        trace!("LD C,C");
-
         let val = self.registers.c();
         self.registers.set_c(val);
 
@@ -1500,8 +1477,6 @@ impl Cpu {
 
     /// LD C,D
     fn op_004a(&mut self) -> u8 {
-        trace!("LD C,D");
-
         let val = self.registers.d();
         self.registers.set_c(val);
 
@@ -1510,8 +1485,6 @@ impl Cpu {
 
     /// LD C,E
     fn op_004b(&mut self) -> u8 {
-        trace!("LD C,E");
-
         let val = self.registers.e();
         self.registers.set_c(val);
 
@@ -1520,8 +1493,6 @@ impl Cpu {
 
     /// LD C,H
     fn op_004c(&mut self) -> u8 {
-        trace!("LD C,H");
-
         let val = self.registers.h();
         self.registers.set_c(val);
 
@@ -1530,8 +1501,6 @@ impl Cpu {
 
     /// LD C,L
     fn op_004d(&mut self) -> u8 {
-        trace!("LD C,L");
-
         let val = self.registers.l();
         self.registers.set_c(val);
 
@@ -1540,10 +1509,8 @@ impl Cpu {
 
     /// LD C,(HL)
     fn op_004e(&mut self) -> u8 {
-        trace!("LD C,(HL)");
-
         let addr = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(addr);
+        let val = self.read(addr);
         self.registers.set_c(val);
 
         8
@@ -1551,8 +1518,6 @@ impl Cpu {
 
     /// LD C,A
     fn op_004f(&mut self) -> u8 {
-        trace!("LD C,A");
-
         let val = self.registers.a();
         self.registers.set_c(val);
 
@@ -1561,8 +1526,6 @@ impl Cpu {
 
     /// LD D,B
     fn op_0050(&mut self) -> u8 {
-        trace!("LD D,B");
-
         let val = self.registers.b();
         self.registers.set_d(val);
 
@@ -1571,8 +1534,6 @@ impl Cpu {
 
     /// LD D,C
     fn op_0051(&mut self) -> u8 {
-        trace!("LD D,C");
-
         let val = self.registers.c();
         self.registers.set_d(val);
 
@@ -1581,8 +1542,6 @@ impl Cpu {
 
     /// LD D,D
     fn op_0052(&mut self) -> u8 {
-        trace!("LD D,D");
-
         let val = self.registers.d();
         self.registers.set_d(val);
 
@@ -1591,8 +1550,6 @@ impl Cpu {
 
     /// LD D,E
     fn op_0053(&mut self) -> u8 {
-        trace!("LD D,E");
-
         let val = self.registers.e();
         self.registers.set_d(val);
 
@@ -1601,8 +1558,6 @@ impl Cpu {
 
     /// LD D,H
     fn op_0054(&mut self) -> u8 {
-        trace!("LD D,H");
-
         let val = self.registers.h();
         self.registers.set_d(val);
 
@@ -1611,8 +1566,6 @@ impl Cpu {
 
     /// LD D,L
     fn op_0055(&mut self) -> u8 {
-        trace!("LD D,L");
-
         let val = self.registers.l();
         self.registers.set_d(val);
 
@@ -1621,10 +1574,8 @@ impl Cpu {
 
     /// LD D,(HL)
     fn op_0056(&mut self) -> u8 {
-        trace!("LD D,(HL)");
-
         let addr = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(addr);
+        let val = self.read(addr);
         self.registers.set_d(val);
 
         8
@@ -1632,8 +1583,6 @@ impl Cpu {
 
     /// LD D,A
     fn op_0057(&mut self) -> u8 {
-        trace!("LD D,A");
-
         let val = self.registers.a();
         self.registers.set_d(val);
 
@@ -1642,8 +1591,6 @@ impl Cpu {
 
     /// LD E,B
     fn op_0058(&mut self) -> u8 {
-        trace!("LD E,B");
-
         let val = self.registers.b();
         self.registers.set_e(val);
 
@@ -1652,8 +1599,6 @@ impl Cpu {
 
     /// LD E,C
     fn op_0059(&mut self) -> u8 {
-        trace!("LD E,C");
-
         let val = self.registers.c();
         self.registers.set_e(val);
 
@@ -1662,8 +1607,6 @@ impl Cpu {
 
     /// LD E,D
     fn op_005a(&mut self) -> u8 {
-        trace!("LD E,D");
-
         let val = self.registers.d();
         self.registers.set_e(val);
 
@@ -1672,8 +1615,6 @@ impl Cpu {
 
     /// LD E,E
     fn op_005b(&mut self) -> u8 {
-        trace!("LD E,E");
-
         let val = self.registers.e();
         self.registers.set_e(val);
 
@@ -1682,8 +1623,6 @@ impl Cpu {
 
     /// LD E,H
     fn op_005c(&mut self) -> u8 {
-        trace!("LD E,H");
-
         let val = self.registers.h();
         self.registers.set_e(val);
 
@@ -1692,8 +1631,6 @@ impl Cpu {
 
     /// LD E,L
     fn op_005d(&mut self) -> u8 {
-        trace!("LD E,L");
-
         let val = self.registers.l();
         self.registers.set_e(val);
 
@@ -1702,10 +1639,8 @@ impl Cpu {
 
     /// LD E,(HL)
     fn op_005e(&mut self) -> u8 {
-        trace!("LD E,(HL)");
-
         let addr = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(addr);
+        let val = self.read(addr);
         self.registers.set_e(val);
 
         8
@@ -1713,8 +1648,6 @@ impl Cpu {
 
     /// LD E,A
     fn op_005f(&mut self) -> u8 {
-        trace!("LD E,A");
-
         let val = self.registers.a();
         self.registers.set_e(val);
 
@@ -1723,8 +1656,6 @@ impl Cpu {
 
     /// LD H,B
     fn op_0060(&mut self) -> u8 {
-        trace!("LD H,B");
-
         let val = self.registers.b();
         self.registers.set_h(val);
 
@@ -1733,8 +1664,6 @@ impl Cpu {
 
     /// LD H,C
     fn op_0061(&mut self) -> u8 {
-        trace!("LD H,C");
-
         let val = self.registers.c();
         self.registers.set_h(val);
 
@@ -1743,8 +1672,6 @@ impl Cpu {
 
     /// LD H,D
     fn op_0062(&mut self) -> u8 {
-        trace!("LD H,D");
-
         let val = self.registers.d();
         self.registers.set_h(val);
 
@@ -1753,8 +1680,6 @@ impl Cpu {
 
     /// LD H,E
     fn op_0063(&mut self) -> u8 {
-        trace!("LD H,E");
-
         let val = self.registers.e();
         self.registers.set_h(val);
 
@@ -1763,8 +1688,6 @@ impl Cpu {
 
     /// LD H,H
     fn op_0064(&mut self) -> u8 {
-        trace!("LD H,H");
-
         let val = self.registers.h();
         self.registers.set_h(val);
 
@@ -1773,8 +1696,6 @@ impl Cpu {
 
     /// LD H,L
     fn op_0065(&mut self) -> u8 {
-        trace!("LD H,L");
-
         let val = self.registers.l();
         self.registers.set_h(val);
 
@@ -1783,10 +1704,8 @@ impl Cpu {
 
     /// LD H,(HL)
     fn op_0066(&mut self) -> u8 {
-        trace!("LD H,(HL)");
-
         let addr = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(addr);
+        let val = self.read(addr);
         self.registers.set_h(val);
 
         8
@@ -1794,8 +1713,6 @@ impl Cpu {
 
     /// LD H,A
     fn op_0067(&mut self) -> u8 {
-        trace!("LD H,A");
-
         let val = self.registers.a();
         self.registers.set_h(val);
 
@@ -1804,8 +1721,6 @@ impl Cpu {
 
     /// LD L,B
     fn op_0068(&mut self) -> u8 {
-        trace!("LD L,B");
-
         let val = self.registers.b();
         self.registers.set_l(val);
 
@@ -1814,8 +1729,6 @@ impl Cpu {
 
     /// LD L,C
     fn op_0069(&mut self) -> u8 {
-        trace!("LD L,C");
-
         let val = self.registers.c();
         self.registers.set_l(val);
 
@@ -1824,8 +1737,6 @@ impl Cpu {
 
     /// LD L,D
     fn op_006a(&mut self) -> u8 {
-        trace!("LD L,D");
-
         let val = self.registers.d();
         self.registers.set_l(val);
 
@@ -1834,8 +1745,6 @@ impl Cpu {
 
     /// LD L,E
     fn op_006b(&mut self) -> u8 {
-        trace!("LD L,E");
-
         let val = self.registers.e();
         self.registers.set_l(val);
 
@@ -1844,8 +1753,6 @@ impl Cpu {
 
     /// LD L,H
     fn op_006c(&mut self) -> u8 {
-        trace!("LD L,H");
-
         let val = self.registers.h();
         self.registers.set_l(val);
 
@@ -1854,8 +1761,6 @@ impl Cpu {
 
     /// LD L,L
     fn op_006d(&mut self) -> u8 {
-        trace!("LD L,L");
-
         let val = self.registers.l();
         self.registers.set_l(val);
 
@@ -1864,10 +1769,8 @@ impl Cpu {
 
     /// LD L,(HL)
     fn op_006e(&mut self) -> u8 {
-        trace!("LD L,(HL)");
-
         let addr = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(addr);
+        let val = self.read(addr);
         self.registers.set_l(val);
 
         8
@@ -1875,8 +1778,6 @@ impl Cpu {
 
     /// LD L,A
     fn op_006f(&mut self) -> u8 {
-        trace!("LD L,A");
-
         let val = self.registers.a();
         self.registers.set_l(val);
 
@@ -1885,92 +1786,75 @@ impl Cpu {
 
     /// LD (HL),B
     fn op_0070(&mut self) -> u8 {
-        trace!("LD (HL),B");
-
         let val = self.registers.b();
         let addr = self.registers.hl();
-        self.mmu.borrow_mut().write_byte(addr, val);
+        self.write(addr, val);
 
         8
     }
 
     /// LD (HL),C
     fn op_0071(&mut self) -> u8 {
-        trace!("LD (HL),C");
-
         let val = self.registers.c();
         let addr = self.registers.hl();
-        self.mmu.borrow_mut().write_byte(addr, val);
+        self.write(addr, val);
 
         8
     }
 
     /// LD (HL),D
     fn op_0072(&mut self) -> u8 {
-        trace!("LD (HL),D");
-
         let val = self.registers.d();
         let addr = self.registers.hl();
-        self.mmu.borrow_mut().write_byte(addr, val);
+        self.write(addr, val);
 
         8
     }
 
     /// LD (HL),E
     fn op_0073(&mut self) -> u8 {
-        trace!("LD (HL),E");
-
         let val = self.registers.e();
         let addr = self.registers.hl();
-        self.mmu.borrow_mut().write_byte(addr, val);
+        self.write(addr, val);
 
         8
     }
 
     /// LD (HL),H
     fn op_0074(&mut self) -> u8 {
-        trace!("LD (HL),H");
-
         let val = self.registers.h();
         let addr = self.registers.hl();
-        self.mmu.borrow_mut().write_byte(addr, val);
+        self.write(addr, val);
 
         8
     }
 
     /// LD (HL),L
     fn op_0075(&mut self) -> u8 {
-        trace!("LD (HL),L");
-
         let val = self.registers.l();
         let addr = self.registers.hl();
-        self.mmu.borrow_mut().write_byte(addr, val);
+        self.write(addr, val);
 
         8
     }
 
     /// HALT
     fn op_0076(&mut self) -> u8 {
-        trace!("HALT");
         // TODO
         4
     }
 
     /// LD (HL),A
     fn op_0077(&mut self) -> u8 {
-        trace!("LD (HL),A");
-
         let val = self.registers.a();
         let addr = self.registers.hl();
-        self.mmu.borrow_mut().write_byte(addr, val);
+        self.write(addr, val);
 
         8
     }
 
     /// LD A,B
     fn op_0078(&mut self) -> u8 {
-        trace!("LD A,B");
-
         let val = self.registers.b();
         self.registers.set_a(val);
 
@@ -1979,8 +1863,6 @@ impl Cpu {
 
     /// LD A,C
     fn op_0079(&mut self) -> u8 {
-        trace!("LD A,C");
-
         let val = self.registers.c();
         self.registers.set_a(val);
 
@@ -1989,8 +1871,6 @@ impl Cpu {
 
     /// LD A,D
     fn op_007a(&mut self) -> u8 {
-        trace!("LD A,D");
-
         let val = self.registers.d();
         self.registers.set_a(val);
 
@@ -1999,8 +1879,6 @@ impl Cpu {
 
     /// LD A,E
     fn op_007b(&mut self) -> u8 {
-        trace!("LD A,E");
-
         let val = self.registers.e();
         self.registers.set_a(val);
 
@@ -2009,8 +1887,6 @@ impl Cpu {
 
     /// LD A,H
     fn op_007c(&mut self) -> u8 {
-        trace!("LD A,H");
-
         let val = self.registers.h();
         self.registers.set_a(val);
 
@@ -2019,8 +1895,6 @@ impl Cpu {
 
     /// LD A,L
     fn op_007d(&mut self) -> u8 {
-        trace!("LD A,L");
-
         let val = self.registers.l();
         self.registers.set_a(val);
 
@@ -2029,10 +1903,8 @@ impl Cpu {
 
     /// LD A,(HL)
     fn op_007e(&mut self) -> u8 {
-        trace!("LD A,(HL)");
-
         let addr = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(addr);
+        let val = self.read(addr);
         self.registers.set_a(val);
 
         8
@@ -2040,8 +1912,6 @@ impl Cpu {
 
     /// LD A,A
     fn op_007f(&mut self) -> u8 {
-        trace!("LD A,A");
-
         let val = self.registers.a();
         self.registers.set_a(val);
 
@@ -2050,8 +1920,6 @@ impl Cpu {
 
     /// ADD A,B
     fn op_0080(&mut self) -> u8 {
-        trace!("ADD A,B");
-
         let a = self.registers.a();
         let operand = self.registers.b();
         let (res, carry, half_carry) = alu::add2_8bit(a, operand);
@@ -2067,8 +1935,6 @@ impl Cpu {
 
     /// ADD A,C
     fn op_0081(&mut self) -> u8 {
-        trace!("ADD A,C");
-
         let a = self.registers.a();
         let operand = self.registers.c();
         let (res, carry, half_carry) = alu::add2_8bit(a, operand);
@@ -2084,8 +1950,6 @@ impl Cpu {
 
     /// ADD A,D
     fn op_0082(&mut self) -> u8 {
-        trace!("ADD A,D");
-
         let a = self.registers.a();
         let operand = self.registers.d();
         let (res, carry, half_carry) = alu::add2_8bit(a, operand);
@@ -2101,8 +1965,6 @@ impl Cpu {
 
     /// ADD A,E
     fn op_0083(&mut self) -> u8 {
-        trace!("ADD A,E");
-
         let a = self.registers.a();
         let operand = self.registers.e();
         let (res, carry, half_carry) = alu::add2_8bit(a, operand);
@@ -2118,8 +1980,6 @@ impl Cpu {
 
     /// ADD A,H
     fn op_0084(&mut self) -> u8 {
-        trace!("ADD A,H");
-
         let a = self.registers.a();
         let operand = self.registers.h();
         let (res, carry, half_carry) = alu::add2_8bit(a, operand);
@@ -2135,8 +1995,6 @@ impl Cpu {
 
     /// ADD A,L
     fn op_0085(&mut self) -> u8 {
-        trace!("ADD A,L");
-
         let a = self.registers.a();
         let operand = self.registers.l();
         let (res, carry, half_carry) = alu::add2_8bit(a, operand);
@@ -2152,10 +2010,8 @@ impl Cpu {
 
     /// ADD A,(HL)
     fn op_0086(&mut self) -> u8 {
-        trace!("ADD A,(HL)");
-
         let a = self.registers.a();
-        let operand = self.mmu.borrow().read_byte(self.registers.hl());
+        let operand = self.read(self.registers.hl());
         let (res, carry, half_carry) = alu::add2_8bit(a, operand);
         self.registers.set_a(res);
 
@@ -2169,8 +2025,6 @@ impl Cpu {
 
     /// ADD A,A
     fn op_0087(&mut self) -> u8 {
-        trace!("ADD A,A");
-
         let a = self.registers.a();
         let operand = self.registers.a();
         let (res, carry, half_carry) = alu::add2_8bit(a, operand);
@@ -2186,8 +2040,6 @@ impl Cpu {
 
     /// ADC A,B
     fn op_0088(&mut self) -> u8 {
-        trace!("ADC A,B");
-
         let a = self.registers.a();
         let operand = self.registers.b();
         let (res, carry, half_carry) =
@@ -2204,8 +2056,6 @@ impl Cpu {
 
     /// ADC A,C
     fn op_0089(&mut self) -> u8 {
-        trace!("ADC A,C");
-
         let a = self.registers.a();
         let operand = self.registers.c();
         let (res, carry, half_carry) =
@@ -2222,8 +2072,6 @@ impl Cpu {
 
     /// ADC A,D
     fn op_008a(&mut self) -> u8 {
-        trace!("ADC A,D");
-
         let a = self.registers.a();
         let operand = self.registers.d();
         let (res, carry, half_carry) =
@@ -2240,8 +2088,6 @@ impl Cpu {
 
     /// ADC A,E
     fn op_008b(&mut self) -> u8 {
-        trace!("ADC A,E");
-
         let a = self.registers.a();
         let operand = self.registers.e();
         let (res, carry, half_carry) =
@@ -2258,8 +2104,6 @@ impl Cpu {
 
     /// ADC A,H
     fn op_008c(&mut self) -> u8 {
-        trace!("ADC A,H");
-
         let a = self.registers.a();
         let operand = self.registers.h();
         let (res, carry, half_carry) =
@@ -2276,8 +2120,6 @@ impl Cpu {
 
     /// ADC A,L
     fn op_008d(&mut self) -> u8 {
-        trace!("ADC A,L");
-
         let a = self.registers.a();
         let operand = self.registers.l();
         let (res, carry, half_carry) =
@@ -2294,10 +2136,8 @@ impl Cpu {
 
     /// ADC A,(HL)
     fn op_008e(&mut self) -> u8 {
-        trace!("ADC A,(HL)");
-
         let a = self.registers.a();
-        let operand = self.mmu.borrow().read_byte(self.registers.hl());
+        let operand = self.read(self.registers.hl());
         let (res, carry, half_carry) =
             alu::add3_8bit(a, operand, self.registers.carry_flag() as u8);
         self.registers.set_a(res);
@@ -2312,8 +2152,6 @@ impl Cpu {
 
     /// ADC A,A
     fn op_008f(&mut self) -> u8 {
-        trace!("ADC A,A");
-
         let a = self.registers.a();
         let operand = self.registers.a();
         let (res, carry, half_carry) =
@@ -2330,8 +2168,6 @@ impl Cpu {
 
     /// SUB B
     fn op_0090(&mut self) -> u8 {
-        trace!("SUB B");
-
         let a = self.registers.a();
         let operand = self.registers.b();
         let (res, carry, half_carry) = alu::sub2_8bit(a, operand);
@@ -2347,8 +2183,6 @@ impl Cpu {
 
     /// SUB C
     fn op_0091(&mut self) -> u8 {
-        trace!("SUB C");
-
         let a = self.registers.a();
         let operand = self.registers.c();
         let (res, carry, half_carry) = alu::sub2_8bit(a, operand);
@@ -2364,8 +2198,6 @@ impl Cpu {
 
     /// SUB D
     fn op_0092(&mut self) -> u8 {
-        trace!("SUB D");
-
         let a = self.registers.a();
         let operand = self.registers.d();
         let (res, carry, half_carry) = alu::sub2_8bit(a, operand);
@@ -2381,8 +2213,6 @@ impl Cpu {
 
     /// SUB E
     fn op_0093(&mut self) -> u8 {
-        trace!("SUB E");
-
         let a = self.registers.a();
         let operand = self.registers.e();
         let (res, carry, half_carry) = alu::sub2_8bit(a, operand);
@@ -2398,8 +2228,6 @@ impl Cpu {
 
     /// SUB H
     fn op_0094(&mut self) -> u8 {
-        trace!("SUB H");
-
         let a = self.registers.a();
         let operand = self.registers.h();
         let (res, carry, half_carry) = alu::sub2_8bit(a, operand);
@@ -2415,8 +2243,6 @@ impl Cpu {
 
     /// SUB L
     fn op_0095(&mut self) -> u8 {
-        trace!("SUB L");
-
         let a = self.registers.a();
         let operand = self.registers.l();
         let (res, carry, half_carry) = alu::sub2_8bit(a, operand);
@@ -2432,10 +2258,8 @@ impl Cpu {
 
     /// SUB (HL)
     fn op_0096(&mut self) -> u8 {
-        trace!("SUB (HL)");
-
         let a = self.registers.a();
-        let operand = self.mmu.borrow().read_byte(self.registers.hl());
+        let operand = self.read(self.registers.hl());
         let (res, carry, half_carry) = alu::sub2_8bit(a, operand);
         self.registers.set_a(res);
 
@@ -2449,8 +2273,6 @@ impl Cpu {
 
     /// SUB A
     fn op_0097(&mut self) -> u8 {
-        trace!("SUB A");
-
         let a = self.registers.a();
         let operand = self.registers.a();
         let (res, carry, half_carry) = alu::sub2_8bit(a, operand);
@@ -2466,8 +2288,6 @@ impl Cpu {
 
     /// SBC A,B
     fn op_0098(&mut self) -> u8 {
-        trace!("SBC A,B");
-
         let a = self.registers.a();
         let operand = self.registers.b();
         let (res, carry, half_carry) =
@@ -2484,8 +2304,6 @@ impl Cpu {
 
     /// SBC A,C
     fn op_0099(&mut self) -> u8 {
-        trace!("SBC A,C");
-
         let a = self.registers.a();
         let operand = self.registers.c();
         let (res, carry, half_carry) =
@@ -2502,8 +2320,6 @@ impl Cpu {
 
     /// SBC A,D
     fn op_009a(&mut self) -> u8 {
-        trace!("SBC A,D");
-
         let a = self.registers.a();
         let operand = self.registers.d();
         let (res, carry, half_carry) =
@@ -2520,8 +2336,6 @@ impl Cpu {
 
     /// SBC A,E
     fn op_009b(&mut self) -> u8 {
-        trace!("SBC A,E");
-
         let a = self.registers.a();
         let operand = self.registers.e();
         let (res, carry, half_carry) =
@@ -2538,8 +2352,6 @@ impl Cpu {
 
     /// SBC A,H
     fn op_009c(&mut self) -> u8 {
-        trace!("SBC A,H");
-
         let a = self.registers.a();
         let operand = self.registers.h();
         let (res, carry, half_carry) =
@@ -2556,8 +2368,6 @@ impl Cpu {
 
     /// SBC A,L
     fn op_009d(&mut self) -> u8 {
-        trace!("SBC A,L");
-
         let a = self.registers.a();
         let operand = self.registers.l();
         let (res, carry, half_carry) =
@@ -2574,10 +2384,8 @@ impl Cpu {
 
     /// SBC A,(HL)
     fn op_009e(&mut self) -> u8 {
-        trace!("SBC A,(HL)");
-
         let a = self.registers.a();
-        let operand = self.mmu.borrow().read_byte(self.registers.hl());
+        let operand = self.read(self.registers.hl());
         let (res, carry, half_carry) =
             alu::sub3_8bit(a, operand, self.registers.carry_flag() as u8);
         self.registers.set_a(res);
@@ -2592,8 +2400,6 @@ impl Cpu {
 
     /// SBC A,A
     fn op_009f(&mut self) -> u8 {
-        trace!("SBC A,A");
-
         let a = self.registers.a();
         let operand = self.registers.a();
         let (res, carry, half_carry) =
@@ -2610,8 +2416,6 @@ impl Cpu {
 
     /// AND B
     fn op_00a0(&mut self) -> u8 {
-        trace!("AND B");
-
         let a = self.registers.a();
         let operand = self.registers.b();
         let res = a & operand;
@@ -2627,8 +2431,6 @@ impl Cpu {
 
     /// AND C
     fn op_00a1(&mut self) -> u8 {
-        trace!("AND C");
-
         let a = self.registers.a();
         let operand = self.registers.c();
         let res = a & operand;
@@ -2644,8 +2446,6 @@ impl Cpu {
 
     /// AND D
     fn op_00a2(&mut self) -> u8 {
-        trace!("AND D");
-
         let a = self.registers.a();
         let operand = self.registers.d();
         let res = a & operand;
@@ -2661,8 +2461,6 @@ impl Cpu {
 
     /// AND E
     fn op_00a3(&mut self) -> u8 {
-        trace!("AND E");
-
         let a = self.registers.a();
         let operand = self.registers.e();
         let res = a & operand;
@@ -2678,8 +2476,6 @@ impl Cpu {
 
     /// AND H
     fn op_00a4(&mut self) -> u8 {
-        trace!("AND H");
-
         let a = self.registers.a();
         let operand = self.registers.h();
         let res = a & operand;
@@ -2695,8 +2491,6 @@ impl Cpu {
 
     /// AND L
     fn op_00a5(&mut self) -> u8 {
-        trace!("AND L");
-
         let a = self.registers.a();
         let operand = self.registers.l();
         let res = a & operand;
@@ -2712,10 +2506,8 @@ impl Cpu {
 
     /// AND (HL)
     fn op_00a6(&mut self) -> u8 {
-        trace!("AND (HL)");
-
         let a = self.registers.a();
-        let operand = self.mmu.borrow().read_byte(self.registers.hl());
+        let operand = self.read(self.registers.hl());
         let res = a & operand;
         self.registers.set_a(res);
 
@@ -2729,8 +2521,6 @@ impl Cpu {
 
     /// AND A
     fn op_00a7(&mut self) -> u8 {
-        trace!("AND A");
-
         let a = self.registers.a();
         let operand = self.registers.a();
         let res = a & operand;
@@ -2746,8 +2536,6 @@ impl Cpu {
 
     /// XOR B
     fn op_00a8(&mut self) -> u8 {
-        trace!("XOR B");
-
         let a = self.registers.a();
         let operand = self.registers.b();
         let res = a ^ operand;
@@ -2763,8 +2551,6 @@ impl Cpu {
 
     /// XOR C
     fn op_00a9(&mut self) -> u8 {
-        trace!("XOR C");
-
         let a = self.registers.a();
         let operand = self.registers.c();
         let res = a ^ operand;
@@ -2780,8 +2566,6 @@ impl Cpu {
 
     /// XOR D
     fn op_00aa(&mut self) -> u8 {
-        trace!("XOR D");
-
         let a = self.registers.a();
         let operand = self.registers.d();
         let res = a ^ operand;
@@ -2797,8 +2581,6 @@ impl Cpu {
 
     /// XOR E
     fn op_00ab(&mut self) -> u8 {
-        trace!("XOR E");
-
         let a = self.registers.a();
         let operand = self.registers.e();
         let res = a ^ operand;
@@ -2814,8 +2596,6 @@ impl Cpu {
 
     /// XOR H
     fn op_00ac(&mut self) -> u8 {
-        trace!("XOR H");
-
         let a = self.registers.a();
         let operand = self.registers.h();
         let res = a ^ operand;
@@ -2831,8 +2611,6 @@ impl Cpu {
 
     /// XOR L
     fn op_00ad(&mut self) -> u8 {
-        trace!("XOR L");
-
         let a = self.registers.a();
         let operand = self.registers.l();
         let res = a ^ operand;
@@ -2848,10 +2626,8 @@ impl Cpu {
 
     /// XOR (HL)
     fn op_00ae(&mut self) -> u8 {
-        trace!("XOR (HL)");
-
         let a = self.registers.a();
-        let operand = self.mmu.borrow().read_byte(self.registers.hl());
+        let operand = self.read(self.registers.hl());
         let res = a ^ operand;
         self.registers.set_a(res);
 
@@ -2865,8 +2641,6 @@ impl Cpu {
 
     /// XOR A
     fn op_00af(&mut self) -> u8 {
-        trace!("XOR A");
-
         let a = self.registers.a();
         let operand = self.registers.a();
         let res = a ^ operand;
@@ -2882,8 +2656,6 @@ impl Cpu {
 
     /// OR B
     fn op_00b0(&mut self) -> u8 {
-        trace!("OR B");
-
         let a = self.registers.a();
         let operand = self.registers.b();
         let res = a | operand;
@@ -2899,8 +2671,6 @@ impl Cpu {
 
     /// OR C
     fn op_00b1(&mut self) -> u8 {
-        trace!("OR C");
-
         let a = self.registers.a();
         let operand = self.registers.c();
         let res = a | operand;
@@ -2916,8 +2686,6 @@ impl Cpu {
 
     /// OR D
     fn op_00b2(&mut self) -> u8 {
-        trace!("OR D");
-
         let a = self.registers.a();
         let operand = self.registers.d();
         let res = a | operand;
@@ -2933,8 +2701,6 @@ impl Cpu {
 
     /// OR E
     fn op_00b3(&mut self) -> u8 {
-        trace!("OR E");
-
         let a = self.registers.a();
         let operand = self.registers.e();
         let res = a | operand;
@@ -2950,8 +2716,6 @@ impl Cpu {
 
     /// OR H
     fn op_00b4(&mut self) -> u8 {
-        trace!("OR H");
-
         let a = self.registers.a();
         let operand = self.registers.h();
         let res = a | operand;
@@ -2967,8 +2731,6 @@ impl Cpu {
 
     /// OR L
     fn op_00b5(&mut self) -> u8 {
-        trace!("OR L");
-
         let a = self.registers.a();
         let operand = self.registers.l();
         let res = a | operand;
@@ -2984,10 +2746,8 @@ impl Cpu {
 
     /// OR (HL)
     fn op_00b6(&mut self) -> u8 {
-        trace!("OR (HL)");
-
         let a = self.registers.a();
-        let operand = self.mmu.borrow().read_byte(self.registers.hl());
+        let operand = self.read(self.registers.hl());
         let res = a | operand;
         self.registers.set_a(res);
 
@@ -3001,8 +2761,6 @@ impl Cpu {
 
     /// OR A
     fn op_00b7(&mut self) -> u8 {
-        trace!("OR A");
-
         let a = self.registers.a();
         let operand = self.registers.a();
         let res = a | operand;
@@ -3018,8 +2776,6 @@ impl Cpu {
 
     /// CP B
     fn op_00b8(&mut self) -> u8 {
-        trace!("CP B");
-
         let a = self.registers.a();
         let b = self.registers.b();
 
@@ -3034,8 +2790,6 @@ impl Cpu {
 
     /// CP C
     fn op_00b9(&mut self) -> u8 {
-        trace!("CP C");
-
         let a = self.registers.a();
         let c = self.registers.c();
 
@@ -3049,8 +2803,6 @@ impl Cpu {
 
     /// CP D
     fn op_00ba(&mut self) -> u8 {
-        trace!("CP D");
-
         let a = self.registers.a();
         let d = self.registers.d();
 
@@ -3065,8 +2817,6 @@ impl Cpu {
 
     /// CP E
     fn op_00bb(&mut self) -> u8 {
-        trace!("CP E");
-
         let a = self.registers.a();
         let e = self.registers.e();
 
@@ -3081,8 +2831,6 @@ impl Cpu {
 
     /// CP H
     fn op_00bc(&mut self) -> u8 {
-        trace!("CP H");
-
         let a = self.registers.a();
         let h = self.registers.h();
 
@@ -3097,8 +2845,6 @@ impl Cpu {
 
     /// CP L
     fn op_00bd(&mut self) -> u8 {
-        trace!("CP L");
-
         let a = self.registers.a();
         let l = self.registers.l();
 
@@ -3113,11 +2859,9 @@ impl Cpu {
 
     /// CP (HL)
     fn op_00be(&mut self) -> u8 {
-        trace!("CP (HL)");
-
         let a = self.registers.a();
         let addr = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(addr);
+        let val = self.read(addr);
 
         let (res, carry, half_carry) = alu::sub2_8bit(a, val);
         self.registers.set_zero_flag(res == 0);
@@ -3129,8 +2873,6 @@ impl Cpu {
 
     /// CP A
     fn op_00bf(&mut self) -> u8 {
-        trace!("CP A");
-
         let a = self.registers.a();
 
         let (res, carry, half_carry) = alu::sub2_8bit(a, a);
@@ -3144,8 +2886,7 @@ impl Cpu {
 
     /// RET NZ
     fn op_00c0(&mut self) -> u8 {
-        trace!("RET NZ");
-
+        self.tick();
         if !self.registers.zero_flag() {
             let pc = self.pop();
             self.registers.set_pc(pc);
@@ -3157,8 +2898,6 @@ impl Cpu {
 
     /// POP BC
     fn op_00c1(&mut self) -> u8 {
-        trace!("POP BC");
-
         let val = self.pop();
         self.registers.set_bc(val);
 
@@ -3167,8 +2906,6 @@ impl Cpu {
 
     /// JP NZ,a16
     fn op_00c2(&mut self) -> u8 {
-        trace!("JP NZ,a16");
-
         let addr = self.fetch_word();
         if !self.registers.zero_flag() {
             self.registers.set_pc(addr);
@@ -3180,8 +2917,6 @@ impl Cpu {
 
     /// JP a16
     fn op_00c3(&mut self) -> u8 {
-        trace!("JP a16");
-
         let addr = self.fetch_word();
         self.registers.set_pc(addr);
 
@@ -3190,8 +2925,6 @@ impl Cpu {
 
     /// CALL NZ,a16
     fn op_00c4(&mut self) -> u8 {
-        trace!("CALL NZ,a16");
-
         let addr = self.fetch_word();
         if !self.registers.zero_flag() {
             let pc = self.registers.pc();
@@ -3205,8 +2938,6 @@ impl Cpu {
 
     /// PUSH BC
     fn op_00c5(&mut self) -> u8 {
-        trace!("PUSH BC");
-
         self.push(self.registers.bc());
 
         16
@@ -3214,8 +2945,6 @@ impl Cpu {
 
     /// ADD A,d8
     fn op_00c6(&mut self) -> u8 {
-        trace!("ADD A,d8");
-
         let a = self.registers.a();
         let operand = self.fetch_byte();
         let (res, carry, half_carry) = alu::add2_8bit(a, operand);
@@ -3231,8 +2960,6 @@ impl Cpu {
 
     /// RST 00H
     fn op_00c7(&mut self) -> u8 {
-        trace!("RST 00H");
-
         self.push(self.registers.pc());
         self.registers.set_pc(0x00);
 
@@ -3241,8 +2968,7 @@ impl Cpu {
 
     /// RET Z
     fn op_00c8(&mut self) -> u8 {
-        trace!("RET Z");
-
+        self.tick();
         if self.registers.zero_flag() {
             let pc = self.pop();
             self.registers.set_pc(pc);
@@ -3254,8 +2980,6 @@ impl Cpu {
 
     /// RET
     fn op_00c9(&mut self) -> u8 {
-        trace!("RET");
-
         let pc = self.pop();
         self.registers.set_pc(pc);
 
@@ -3264,8 +2988,6 @@ impl Cpu {
 
     /// JP Z,a16
     fn op_00ca(&mut self) -> u8 {
-        trace!("JP Z,a16");
-
         let addr = self.fetch_word();
         if self.registers.zero_flag() {
             self.registers.set_pc(addr);
@@ -3277,8 +2999,6 @@ impl Cpu {
 
     /// CALL Z,a16
     fn op_00cc(&mut self) -> u8 {
-        trace!("CALL Z,a16");
-
         let addr = self.fetch_word();
         if self.registers.zero_flag() {
             let pc = self.registers.pc();
@@ -3292,8 +3012,6 @@ impl Cpu {
 
     /// CALL a16
     fn op_00cd(&mut self) -> u8 {
-        trace!("CALL a16");
-
         let addr = self.fetch_word();
         let pc = self.registers.pc();
         self.push(pc);
@@ -3303,8 +3021,6 @@ impl Cpu {
 
     /// ADC A,d8
     fn op_00ce(&mut self) -> u8 {
-        trace!("ADC A,d8");
-
         let a = self.registers.a();
         let operand = self.fetch_byte();
         let (res, carry, half_carry) =
@@ -3321,8 +3037,6 @@ impl Cpu {
 
     /// RST 08H
     fn op_00cf(&mut self) -> u8 {
-        trace!("RST 08H");
-
         self.push(self.registers.pc());
         self.registers.set_pc(0x08);
 
@@ -3331,8 +3045,7 @@ impl Cpu {
 
     /// RET NC
     fn op_00d0(&mut self) -> u8 {
-        trace!("RET NC");
-
+        self.tick();
         if !self.registers.carry_flag() {
             let pc = self.pop();
             self.registers.set_pc(pc);
@@ -3344,8 +3057,6 @@ impl Cpu {
 
     /// POP DE
     fn op_00d1(&mut self) -> u8 {
-        trace!("POP DE");
-
         let val = self.pop();
         self.registers.set_de(val);
 
@@ -3354,8 +3065,6 @@ impl Cpu {
 
     /// JP NC,a16
     fn op_00d2(&mut self) -> u8 {
-        trace!("JP NC,a16");
-
         let addr = self.fetch_word();
         if !self.registers.carry_flag() {
             self.registers.set_pc(addr);
@@ -3367,8 +3076,6 @@ impl Cpu {
 
     /// CALL NC,a16
     fn op_00d4(&mut self) -> u8 {
-        trace!("CALL NC,a16");
-
         let addr = self.fetch_word();
         if !self.registers.carry_flag() {
             let pc = self.registers.pc();
@@ -3382,8 +3089,6 @@ impl Cpu {
 
     /// PUSH DE
     fn op_00d5(&mut self) -> u8 {
-        trace!("PUSH DE");
-
         self.push(self.registers.de());
 
         16
@@ -3391,8 +3096,6 @@ impl Cpu {
 
     /// SUB d8
     fn op_00d6(&mut self) -> u8 {
-        trace!("SUB d8");
-
         let a = self.registers.a();
         let operand = self.fetch_byte();
         let (res, carry, half_carry) = alu::sub2_8bit(a, operand);
@@ -3408,8 +3111,6 @@ impl Cpu {
 
     /// RST 10H
     fn op_00d7(&mut self) -> u8 {
-        trace!("RST 10H");
-
         self.push(self.registers.pc());
         self.registers.set_pc(0x10);
 
@@ -3418,8 +3119,7 @@ impl Cpu {
 
     /// RET C
     fn op_00d8(&mut self) -> u8 {
-        trace!("RET C");
-
+        self.tick();
         if self.registers.carry_flag() {
             let pc = self.pop();
             self.registers.set_pc(pc);
@@ -3431,8 +3131,6 @@ impl Cpu {
 
     /// RETI
     fn op_00d9(&mut self) -> u8 {
-        trace!("RETI");
-
         let pc = self.pop();
         self.registers.set_pc(pc);
         self.ime = true;
@@ -3442,8 +3140,6 @@ impl Cpu {
 
     /// JP C,a16
     fn op_00da(&mut self) -> u8 {
-        trace!("JP C,a16");
-
         let addr = self.fetch_word();
         if self.registers.carry_flag() {
             self.registers.set_pc(addr);
@@ -3455,8 +3151,6 @@ impl Cpu {
 
     /// CALL C,a16
     fn op_00dc(&mut self) -> u8 {
-        trace!("CALL C,a16");
-
         let addr = self.fetch_word();
         if self.registers.carry_flag() {
             let pc = self.registers.pc();
@@ -3470,8 +3164,6 @@ impl Cpu {
 
     /// SBC A,d8
     fn op_00de(&mut self) -> u8 {
-        trace!("SBC A,d8");
-
         let a = self.registers.a();
         let operand = self.fetch_byte();
         let (res, carry, half_carry) =
@@ -3488,8 +3180,6 @@ impl Cpu {
 
     /// RST 18H
     fn op_00df(&mut self) -> u8 {
-        trace!("RST 18H");
-
         self.push(self.registers.pc());
         self.registers.set_pc(0x18);
 
@@ -3498,19 +3188,15 @@ impl Cpu {
 
     /// LDH (a8),A
     fn op_00e0(&mut self) -> u8 {
-        trace!("LDH (a8),A");
-
         let offset = self.fetch_byte();
         let addr = 0xff00 | offset as u16;
-        self.mmu.borrow_mut().write_byte(addr, self.registers.a());
+        self.write(addr, self.registers.a());
 
         12
     }
 
     /// POP HL
     fn op_00e1(&mut self) -> u8 {
-        trace!("POP HL");
-
         let val = self.pop();
         self.registers.set_hl(val);
 
@@ -3519,20 +3205,16 @@ impl Cpu {
 
     /// LD (C),A
     fn op_00e2(&mut self) -> u8 {
-        trace!("LD (C),A");
-
         let val = self.registers.a();
 
         let addr = 0xff00 | self.registers.c() as u16;
-        self.mmu.borrow_mut().write_byte(addr, val);
+        self.write(addr, val);
 
         8
     }
 
     /// PUSH HL
     fn op_00e5(&mut self) -> u8 {
-        trace!("PUSH HL");
-
         self.push(self.registers.hl());
 
         16
@@ -3540,8 +3222,6 @@ impl Cpu {
 
     /// AND d8
     fn op_00e6(&mut self) -> u8 {
-        trace!("AND d8");
-
         let a = self.registers.a();
         let operand = self.fetch_byte();
         let res = a & operand;
@@ -3557,8 +3237,6 @@ impl Cpu {
 
     /// RST 20H
     fn op_00e7(&mut self) -> u8 {
-        trace!("RST 20H");
-
         self.push(self.registers.pc());
         self.registers.set_pc(0x20);
 
@@ -3567,8 +3245,6 @@ impl Cpu {
 
     /// ADD SP,r8
     fn op_00e8(&mut self) -> u8 {
-        trace!("ADD SP,r8");
-
         let offset = alu::signed_byte_to_u16(self.fetch_byte());
         let sp = self.registers.sp();
         let res = sp.wrapping_add(offset);
@@ -3586,8 +3262,6 @@ impl Cpu {
 
     /// JP (HL)
     fn op_00e9(&mut self) -> u8 {
-        trace!("JP (HL)");
-
         let val = self.registers.hl();
         self.registers.set_pc(val);
 
@@ -3596,20 +3270,16 @@ impl Cpu {
 
     /// LD (a16),A
     fn op_00ea(&mut self) -> u8 {
-        trace!("LD (a16),A");
-
         let val = self.registers.a();
 
         let addr = self.fetch_word();
-        self.mmu.borrow_mut().write_byte(addr, val);
+        self.write(addr, val);
 
         16
     }
 
     /// XOR d8
     fn op_00ee(&mut self) -> u8 {
-        trace!("XOR d8");
-
         let a = self.registers.a();
         let operand = self.fetch_byte();
         let res = a ^ operand;
@@ -3625,8 +3295,6 @@ impl Cpu {
 
     /// RST 28H
     fn op_00ef(&mut self) -> u8 {
-        trace!("RST 28H");
-
         self.push(self.registers.pc());
         self.registers.set_pc(0x28);
 
@@ -3635,10 +3303,8 @@ impl Cpu {
 
     /// LDH A,(a8)
     fn op_00f0(&mut self) -> u8 {
-        trace!("LDH A,(a8)");
-
         let addr = 0xff00 | self.fetch_byte() as u16;
-        let val = self.mmu.borrow().read_byte(addr);
+        let val = self.read(addr);
         self.registers.set_a(val);
 
         12
@@ -3646,8 +3312,6 @@ impl Cpu {
 
     /// POP AF
     fn op_00f1(&mut self) -> u8 {
-        trace!("POP AF");
-
         let val = self.pop();
         self.registers.set_af(val);
 
@@ -3656,10 +3320,8 @@ impl Cpu {
 
     /// LD A,(C)
     fn op_00f2(&mut self) -> u8 {
-        trace!("LD A,(C)");
-
         let addr = 0xff00 | self.registers.c() as u16;
-        let val = self.mmu.borrow().read_byte(addr);
+        let val = self.read(addr);
 
         self.registers.set_a(val);
 
@@ -3668,17 +3330,14 @@ impl Cpu {
 
     /// DI
     fn op_00f3(&mut self) -> u8 {
-        trace!("DI");
-
         self.ime = false;
+        self.ime_pending = false;
 
         4
     }
 
     /// PUSH AF
     fn op_00f5(&mut self) -> u8 {
-        trace!("PUSH AF");
-
         self.push(self.registers.af());
 
         16
@@ -3686,8 +3345,6 @@ impl Cpu {
 
     /// OR d8
     fn op_00f6(&mut self) -> u8 {
-        trace!("OR d8");
-
         let a = self.registers.a();
         let operand = self.fetch_byte();
         let res = a | operand;
@@ -3703,8 +3360,6 @@ impl Cpu {
 
     /// RST 30H
     fn op_00f7(&mut self) -> u8 {
-        trace!("RST 30H");
-
         self.push(self.registers.pc());
         self.registers.set_pc(0x30);
 
@@ -3713,8 +3368,6 @@ impl Cpu {
 
     /// LD HL,SP+r8
     fn op_00f8(&mut self) -> u8 {
-        trace!("LD HL,SP+r8");
-
         let offset = alu::signed_byte_to_u16(self.fetch_byte());
         let sp = self.registers.sp();
         let res = sp.wrapping_add(offset);
@@ -3732,8 +3385,6 @@ impl Cpu {
 
     /// LD SP,HL
     fn op_00f9(&mut self) -> u8 {
-        trace!("LD SP,HL");
-
         let val = self.registers.hl();
         self.registers.set_sp(val);
 
@@ -3742,10 +3393,8 @@ impl Cpu {
 
     /// LD A,(a16)
     fn op_00fa(&mut self) -> u8 {
-        trace!("LD A,(a16)");
-
         let addr = self.fetch_word();
-        let val = self.mmu.borrow().read_byte(addr);
+        let val = self.read(addr);
 
         self.registers.set_a(val);
 
@@ -3754,17 +3403,13 @@ impl Cpu {
 
     /// EI
     fn op_00fb(&mut self) -> u8 {
-        trace!("EI");
-
-        self.ime = true;
+        self.ime_pending = true;
 
         4
     }
 
     /// CP d8
     fn op_00fe(&mut self) -> u8 {
-        trace!("CP d8");
-
         let a = self.registers.a();
         let val = self.fetch_byte();
 
@@ -3779,8 +3424,6 @@ impl Cpu {
 
     /// RST 38H
     fn op_00ff(&mut self) -> u8 {
-        trace!("RST 38H");
-
         self.push(self.registers.pc());
         self.registers.set_pc(0x38);
 
@@ -3789,8 +3432,6 @@ impl Cpu {
 
     /// RLC B
     fn op_cb00(&mut self) -> u8 {
-        trace!("RLC B");
-
         let res = self.registers.b().rotate_left(1);
 
         self.registers.set_b(res);
@@ -3804,8 +3445,6 @@ impl Cpu {
 
     /// RLC C
     fn op_cb01(&mut self) -> u8 {
-        trace!("RLC C");
-
         let res = self.registers.c().rotate_left(1);
 
         self.registers.set_c(res);
@@ -3819,8 +3458,6 @@ impl Cpu {
 
     /// RLC D
     fn op_cb02(&mut self) -> u8 {
-        trace!("RLC D");
-
         let res = self.registers.d().rotate_left(1);
 
         self.registers.set_d(res);
@@ -3834,8 +3471,6 @@ impl Cpu {
 
     /// RLC E
     fn op_cb03(&mut self) -> u8 {
-        trace!("RLC E");
-
         let res = self.registers.e().rotate_left(1);
 
         self.registers.set_e(res);
@@ -3849,8 +3484,6 @@ impl Cpu {
 
     /// RLC H
     fn op_cb04(&mut self) -> u8 {
-        trace!("RLC H");
-
         let res = self.registers.h().rotate_left(1);
 
         self.registers.set_h(res);
@@ -3864,8 +3497,6 @@ impl Cpu {
 
     /// RLC L
     fn op_cb05(&mut self) -> u8 {
-        trace!("RLC L");
-
         let res = self.registers.l().rotate_left(1);
 
         self.registers.set_l(res);
@@ -3879,12 +3510,10 @@ impl Cpu {
 
     /// RLC (HL)
     fn op_cb06(&mut self) -> u8 {
-        trace!("RLC (HL)");
-
         let addr = self.registers.hl();
-        let res = self.mmu.borrow().read_byte(addr).rotate_left(1);
+        let res = self.read(addr).rotate_left(1);
 
-        self.mmu.borrow_mut().write_byte(addr, res);
+        self.write(addr, res);
         self.registers.set_zero_flag(res == 0);
         self.registers.set_negative_flag(false);
         self.registers.set_half_carry_flag(false);
@@ -3895,8 +3524,6 @@ impl Cpu {
 
     /// RLC A
     fn op_cb07(&mut self) -> u8 {
-        trace!("RLC A");
-
         let res = self.registers.a().rotate_left(1);
 
         self.registers.set_a(res);
@@ -3910,8 +3537,6 @@ impl Cpu {
 
     /// RRC B
     fn op_cb08(&mut self) -> u8 {
-        trace!("RRC B");
-
         let res = self.registers.b().rotate_right(1);
 
         self.registers.set_b(res);
@@ -3925,8 +3550,6 @@ impl Cpu {
 
     /// RRC C
     fn op_cb09(&mut self) -> u8 {
-        trace!("RRC C");
-
         let res = self.registers.c().rotate_right(1);
 
         self.registers.set_c(res);
@@ -3940,8 +3563,6 @@ impl Cpu {
 
     /// RRC D
     fn op_cb0a(&mut self) -> u8 {
-        trace!("RRC D");
-
         let res = self.registers.d().rotate_right(1);
 
         self.registers.set_d(res);
@@ -3955,8 +3576,6 @@ impl Cpu {
 
     /// RRC E
     fn op_cb0b(&mut self) -> u8 {
-        trace!("RRC E");
-
         let res = self.registers.e().rotate_right(1);
 
         self.registers.set_e(res);
@@ -3970,8 +3589,6 @@ impl Cpu {
 
     /// RRC H
     fn op_cb0c(&mut self) -> u8 {
-        trace!("RRC H");
-
         let res = self.registers.h().rotate_right(1);
 
         self.registers.set_h(res);
@@ -3985,8 +3602,6 @@ impl Cpu {
 
     /// RRC L
     fn op_cb0d(&mut self) -> u8 {
-        trace!("RRC L");
-
         let res = self.registers.l().rotate_right(1);
 
         self.registers.set_l(res);
@@ -4000,12 +3615,10 @@ impl Cpu {
 
     /// RRC (HL)
     fn op_cb0e(&mut self) -> u8 {
-        trace!("RRC (HL)");
-
         let addr = self.registers.hl();
-        let res = self.mmu.borrow().read_byte(addr).rotate_right(1);
+        let res = self.read(addr).rotate_right(1);
 
-        self.mmu.borrow_mut().write_byte(addr, res);
+        self.write(addr, res);
         self.registers.set_zero_flag(res == 0);
         self.registers.set_negative_flag(false);
         self.registers.set_half_carry_flag(false);
@@ -4016,8 +3629,6 @@ impl Cpu {
 
     /// RRC A
     fn op_cb0f(&mut self) -> u8 {
-        trace!("RRC A");
-
         let res = self.registers.a().rotate_right(1);
 
         self.registers.set_a(res);
@@ -4031,8 +3642,6 @@ impl Cpu {
 
     /// RL B
     fn op_cb10(&mut self) -> u8 {
-        trace!("RL B");
-
         let b = self.registers.b();
         let carry = (b & 0x80) != 0;
         let prev_carry = self.registers.carry_flag() as u8;
@@ -4049,8 +3658,6 @@ impl Cpu {
 
     /// RL C
     fn op_cb11(&mut self) -> u8 {
-        trace!("RL C");
-
         let c = self.registers.c();
         let carry = (c & 0x80) != 0;
         let prev_carry = self.registers.carry_flag() as u8;
@@ -4067,8 +3674,6 @@ impl Cpu {
 
     /// RL D
     fn op_cb12(&mut self) -> u8 {
-        trace!("RL D");
-
         let d = self.registers.d();
         let carry = (d & 0x80) != 0;
         let prev_carry = self.registers.carry_flag() as u8;
@@ -4085,8 +3690,6 @@ impl Cpu {
 
     /// RL E
     fn op_cb13(&mut self) -> u8 {
-        trace!("RL E");
-
         let e = self.registers.e();
         let carry = (e & 0x80) != 0;
         let prev_carry = self.registers.carry_flag() as u8;
@@ -4103,8 +3706,6 @@ impl Cpu {
 
     /// RL H
     fn op_cb14(&mut self) -> u8 {
-        trace!("RL H");
-
         let h = self.registers.h();
         let carry = (h & 0x80) != 0;
         let prev_carry = self.registers.carry_flag() as u8;
@@ -4121,8 +3722,6 @@ impl Cpu {
 
     /// RL L
     fn op_cb15(&mut self) -> u8 {
-        trace!("RL L");
-
         let l = self.registers.l();
         let carry = (l & 0x80) != 0;
         let prev_carry = self.registers.carry_flag() as u8;
@@ -4139,15 +3738,13 @@ impl Cpu {
 
     /// RL (HL)
     fn op_cb16(&mut self) -> u8 {
-        trace!("RL (HL)");
-
         let addr = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(addr);
+        let val = self.read(addr);
         let carry = (val & 0x80) != 0;
         let prev_carry = self.registers.carry_flag() as u8;
         let res = val.wrapping_shl(1) | prev_carry;
 
-        self.mmu.borrow_mut().write_byte(addr, res);
+        self.write(addr, res);
         self.registers.set_zero_flag(res == 0);
         self.registers.set_negative_flag(false);
         self.registers.set_half_carry_flag(false);
@@ -4158,8 +3755,6 @@ impl Cpu {
 
     /// RL A
     fn op_cb17(&mut self) -> u8 {
-        trace!("RL A");
-
         let a = self.registers.a();
         let carry = (a & 0x80) != 0;
         let prev_carry = self.registers.carry_flag() as u8;
@@ -4176,8 +3771,6 @@ impl Cpu {
 
     /// RR B
     fn op_cb18(&mut self) -> u8 {
-        trace!("RR B");
-
         let val = self.registers.b();
         let carry = (val & 0x01) != 0;
         let prev_carry = self.registers.carry_flag() as u8;
@@ -4194,8 +3787,6 @@ impl Cpu {
 
     /// RR C
     fn op_cb19(&mut self) -> u8 {
-        trace!("RR C");
-
         let val = self.registers.c();
         let carry = (val & 0x01) != 0;
         let prev_carry = self.registers.carry_flag() as u8;
@@ -4212,8 +3803,6 @@ impl Cpu {
 
     /// RR D
     fn op_cb1a(&mut self) -> u8 {
-        trace!("RR D");
-
         let val = self.registers.d();
         let carry = (val & 0x01) != 0;
         let prev_carry = self.registers.carry_flag() as u8;
@@ -4230,8 +3819,6 @@ impl Cpu {
 
     /// RR E
     fn op_cb1b(&mut self) -> u8 {
-        trace!("RR E");
-
         let val = self.registers.e();
         let carry = (val & 0x01) != 0;
         let prev_carry = self.registers.carry_flag() as u8;
@@ -4248,8 +3835,6 @@ impl Cpu {
 
     /// RR H
     fn op_cb1c(&mut self) -> u8 {
-        trace!("RR H");
-
         let val = self.registers.h();
         let carry = (val & 0x01) != 0;
         let prev_carry = self.registers.carry_flag() as u8;
@@ -4266,8 +3851,6 @@ impl Cpu {
 
     /// RR L
     fn op_cb1d(&mut self) -> u8 {
-        trace!("RR L");
-
         let val = self.registers.l();
         let carry = (val & 0x01) != 0;
         let prev_carry = self.registers.carry_flag() as u8;
@@ -4284,15 +3867,13 @@ impl Cpu {
 
     /// RR (HL)
     fn op_cb1e(&mut self) -> u8 {
-        trace!("RR (HL)");
-
         let addr = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(addr);
+        let val = self.read(addr);
         let carry = (val & 0x01) != 0;
         let prev_carry = self.registers.carry_flag() as u8;
         let res = val.wrapping_shr(1) | (prev_carry << 7);
 
-        self.mmu.borrow_mut().write_byte(addr, res);
+        self.write(addr, res);
         self.registers.set_zero_flag(res == 0);
         self.registers.set_negative_flag(false);
         self.registers.set_half_carry_flag(false);
@@ -4303,8 +3884,6 @@ impl Cpu {
 
     /// RR A
     fn op_cb1f(&mut self) -> u8 {
-        trace!("RR A");
-
         let val = self.registers.a();
         let carry = (val & 0x01) != 0;
         let prev_carry = self.registers.carry_flag() as u8;
@@ -4321,8 +3900,6 @@ impl Cpu {
 
     /// SLA B
     fn op_cb20(&mut self) -> u8 {
-        trace!("SLA B");
-
         let val = self.registers.b();
         let carry = (val & 0x80) != 0;
         let val = val.wrapping_shl(1);
@@ -4338,8 +3915,6 @@ impl Cpu {
 
     /// SLA C
     fn op_cb21(&mut self) -> u8 {
-        trace!("SLA C");
-
         let val = self.registers.c();
         let carry = (val & 0x80) != 0;
         let val = val.wrapping_shl(1);
@@ -4355,8 +3930,6 @@ impl Cpu {
 
     /// SLA D
     fn op_cb22(&mut self) -> u8 {
-        trace!("SLA D");
-
         let val = self.registers.d();
         let carry = (val & 0x80) != 0;
         let val = val.wrapping_shl(1);
@@ -4372,8 +3945,6 @@ impl Cpu {
 
     /// SLA E
     fn op_cb23(&mut self) -> u8 {
-        trace!("SLA E");
-
         let val = self.registers.e();
         let carry = (val & 0x80) != 0;
         let val = val.wrapping_shl(1);
@@ -4389,8 +3960,6 @@ impl Cpu {
 
     /// SLA H
     fn op_cb24(&mut self) -> u8 {
-        trace!("SLA H");
-
         let val = self.registers.h();
         let carry = (val & 0x80) != 0;
         let val = val.wrapping_shl(1);
@@ -4406,8 +3975,6 @@ impl Cpu {
 
     /// SLA L
     fn op_cb25(&mut self) -> u8 {
-        trace!("SLA L");
-
         let val = self.registers.l();
         let carry = (val & 0x80) != 0;
         let val = val.wrapping_shl(1);
@@ -4423,14 +3990,12 @@ impl Cpu {
 
     /// SLA (HL)
     fn op_cb26(&mut self) -> u8 {
-        trace!("SLA (HL)");
-
         let addr = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(addr);
+        let val = self.read(addr);
         let carry = (val & 0x80) != 0;
         let val = val.wrapping_shl(1);
 
-        self.mmu.borrow_mut().write_byte(addr, val);
+        self.write(addr, val);
         self.registers.set_zero_flag(val == 0);
         self.registers.set_negative_flag(false);
         self.registers.set_half_carry_flag(false);
@@ -4441,8 +4006,6 @@ impl Cpu {
 
     /// SLA A
     fn op_cb27(&mut self) -> u8 {
-        trace!("SLA A");
-
         let val = self.registers.a();
         let carry = (val & 0x80) != 0;
         let val = val.wrapping_shl(1);
@@ -4471,8 +4034,6 @@ impl Cpu {
 
     /// SRA B
     fn op_cb28(&mut self) -> u8 {
-        trace!("SRA B");
-
         let val = self.registers.b();
         let res = self.sra(val);
         self.registers.set_b(res);
@@ -4482,8 +4043,6 @@ impl Cpu {
 
     /// SRA C
     fn op_cb29(&mut self) -> u8 {
-        trace!("SRA C");
-
         let val = self.registers.c();
         let res = self.sra(val);
         self.registers.set_c(res);
@@ -4493,8 +4052,6 @@ impl Cpu {
 
     /// SRA D
     fn op_cb2a(&mut self) -> u8 {
-        trace!("SRA D");
-
         let val = self.registers.d();
         let res = self.sra(val);
         self.registers.set_d(res);
@@ -4504,8 +4061,6 @@ impl Cpu {
 
     /// SRA E
     fn op_cb2b(&mut self) -> u8 {
-        trace!("SRA E");
-
         let val = self.registers.e();
         let res = self.sra(val);
         self.registers.set_e(res);
@@ -4515,8 +4070,6 @@ impl Cpu {
 
     /// SRA H
     fn op_cb2c(&mut self) -> u8 {
-        trace!("SRA H");
-
         let val = self.registers.h();
         let res = self.sra(val);
         self.registers.set_h(res);
@@ -4526,8 +4079,6 @@ impl Cpu {
 
     /// SRA L
     fn op_cb2d(&mut self) -> u8 {
-        trace!("SRA L");
-
         let val = self.registers.l();
         let res = self.sra(val);
         self.registers.set_l(res);
@@ -4537,20 +4088,16 @@ impl Cpu {
 
     /// SRA (HL)
     fn op_cb2e(&mut self) -> u8 {
-        trace!("SRA (HL)");
-
         let addr = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(addr);
+        let val = self.read(addr);
         let res = self.sra(val);
-        self.mmu.borrow_mut().write_byte(addr, res);
+        self.write(addr, res);
 
         16
     }
 
     /// SRA A
     fn op_cb2f(&mut self) -> u8 {
-        trace!("SRA A");
-
         let val = self.registers.a();
         let res = self.sra(val);
         self.registers.set_a(res);
@@ -4560,8 +4107,6 @@ impl Cpu {
 
     /// SWAP B
     fn op_cb30(&mut self) -> u8 {
-        trace!("SWAP B");
-
         let val = self.registers.b().rotate_left(4);
         self.registers.set_b(val);
         self.registers.set_zero_flag(val == 0);
@@ -4574,8 +4119,6 @@ impl Cpu {
 
     /// SWAP C
     fn op_cb31(&mut self) -> u8 {
-        trace!("SWAP C");
-
         let val = self.registers.c().rotate_left(4);
         self.registers.set_c(val);
         self.registers.set_zero_flag(val == 0);
@@ -4588,8 +4131,6 @@ impl Cpu {
 
     /// SWAP D
     fn op_cb32(&mut self) -> u8 {
-        trace!("SWAP D");
-
         let val = self.registers.d().rotate_left(4);
         self.registers.set_d(val);
         self.registers.set_zero_flag(val == 0);
@@ -4602,8 +4143,6 @@ impl Cpu {
 
     /// SWAP E
     fn op_cb33(&mut self) -> u8 {
-        trace!("SWAP E");
-
         let val = self.registers.e().rotate_left(4);
         self.registers.set_e(val);
         self.registers.set_zero_flag(val == 0);
@@ -4616,8 +4155,6 @@ impl Cpu {
 
     /// SWAP H
     fn op_cb34(&mut self) -> u8 {
-        trace!("SWAP H");
-
         let val = self.registers.h().rotate_left(4);
         self.registers.set_h(val);
         self.registers.set_zero_flag(val == 0);
@@ -4630,8 +4167,6 @@ impl Cpu {
 
     /// SWAP L
     fn op_cb35(&mut self) -> u8 {
-        trace!("SWAP L");
-
         let val = self.registers.l().rotate_left(4);
         self.registers.set_l(val);
         self.registers.set_zero_flag(val == 0);
@@ -4644,11 +4179,9 @@ impl Cpu {
 
     /// SWAP (HL)
     fn op_cb36(&mut self) -> u8 {
-        trace!("SWAP (HL)");
-
         let addr = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(addr).rotate_left(4);
-        self.mmu.borrow_mut().write_byte(addr, val);
+        let val = self.read(addr).rotate_left(4);
+        self.write(addr, val);
         self.registers.set_zero_flag(val == 0);
         self.registers.set_negative_flag(false);
         self.registers.set_half_carry_flag(false);
@@ -4659,8 +4192,6 @@ impl Cpu {
 
     /// SWAP A
     fn op_cb37(&mut self) -> u8 {
-        trace!("SWAP A");
-
         let val = self.registers.a().rotate_left(4);
         self.registers.set_a(val);
         self.registers.set_zero_flag(val == 0);
@@ -4684,8 +4215,6 @@ impl Cpu {
 
     /// SRL B
     fn op_cb38(&mut self) -> u8 {
-        trace!("SRL B");
-
         let val = self.registers.b();
         let res = self.srl(val);
         self.registers.set_b(res);
@@ -4695,8 +4224,6 @@ impl Cpu {
 
     /// SRL C
     fn op_cb39(&mut self) -> u8 {
-        trace!("SRL C");
-
         let val = self.registers.c();
         let res = self.srl(val);
         self.registers.set_c(res);
@@ -4706,8 +4233,6 @@ impl Cpu {
 
     /// SRL D
     fn op_cb3a(&mut self) -> u8 {
-        trace!("SRL D");
-
         let val = self.registers.d();
         let res = self.srl(val);
         self.registers.set_d(res);
@@ -4717,8 +4242,6 @@ impl Cpu {
 
     /// SRL E
     fn op_cb3b(&mut self) -> u8 {
-        trace!("SRL E");
-
         let val = self.registers.e();
         let res = self.srl(val);
         self.registers.set_e(res);
@@ -4728,8 +4251,6 @@ impl Cpu {
 
     /// SRL H
     fn op_cb3c(&mut self) -> u8 {
-        trace!("SRL H");
-
         let val = self.registers.h();
         let res = self.srl(val);
         self.registers.set_h(res);
@@ -4739,8 +4260,6 @@ impl Cpu {
 
     /// SRL L
     fn op_cb3d(&mut self) -> u8 {
-        trace!("SRL L");
-
         let val = self.registers.l();
         let res = self.srl(val);
         self.registers.set_l(res);
@@ -4750,20 +4269,16 @@ impl Cpu {
 
     /// SRL (HL)
     fn op_cb3e(&mut self) -> u8 {
-        trace!("SRL (HL)");
-
         let addr = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(addr);
+        let val = self.read(addr);
         let res = self.srl(val);
-        self.mmu.borrow_mut().write_byte(addr, res);
+        self.write(addr, res);
 
         16
     }
 
     /// SRL A
     fn op_cb3f(&mut self) -> u8 {
-        trace!("SRL A");
-
         let val = self.registers.a();
         let res = self.srl(val);
         self.registers.set_a(res);
@@ -4773,8 +4288,6 @@ impl Cpu {
 
     /// BIT 0,B
     fn op_cb40(&mut self) -> u8 {
-        trace!("BIT 0,B");
-
         let val = self.registers.b();
         let zf = val & (1 << 0);
         self.registers.set_zero_flag(zf == 0);
@@ -4786,8 +4299,6 @@ impl Cpu {
 
     /// BIT 0,C
     fn op_cb41(&mut self) -> u8 {
-        trace!("BIT 0,C");
-
         let val = self.registers.c();
         let zf = val & (1 << 0);
         self.registers.set_zero_flag(zf == 0);
@@ -4799,8 +4310,6 @@ impl Cpu {
 
     /// BIT 0,D
     fn op_cb42(&mut self) -> u8 {
-        trace!("BIT 0,D");
-
         let val = self.registers.d();
         let zf = val & (1 << 0);
         self.registers.set_zero_flag(zf == 0);
@@ -4812,8 +4321,6 @@ impl Cpu {
 
     /// BIT 0,E
     fn op_cb43(&mut self) -> u8 {
-        trace!("BIT 0,E");
-
         let val = self.registers.e();
         let zf = val & (1 << 0);
         self.registers.set_zero_flag(zf == 0);
@@ -4825,8 +4332,6 @@ impl Cpu {
 
     /// BIT 0,H
     fn op_cb44(&mut self) -> u8 {
-        trace!("BIT 0,H");
-
         let val = self.registers.h();
         let zf = val & (1 << 0);
         self.registers.set_zero_flag(zf == 0);
@@ -4838,8 +4343,6 @@ impl Cpu {
 
     /// BIT 0,L
     fn op_cb45(&mut self) -> u8 {
-        trace!("BIT 0,L");
-
         let val = self.registers.l();
         let zf = val & (1 << 0);
         self.registers.set_zero_flag(zf == 0);
@@ -4851,22 +4354,18 @@ impl Cpu {
 
     /// BIT 0,(HL)
     fn op_cb46(&mut self) -> u8 {
-        trace!("BIT 0,(HL)");
-
         let hl = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(hl);
+        let val = self.read(hl);
         let zf = val & (1 << 0);
         self.registers.set_zero_flag(zf == 0);
         self.registers.set_negative_flag(false);
         self.registers.set_half_carry_flag(true);
 
-        16
+        12
     }
 
     /// BIT 0,A
     fn op_cb47(&mut self) -> u8 {
-        trace!("BIT 0,A");
-
         let val = self.registers.a();
         let zf = val & (1 << 0);
         self.registers.set_zero_flag(zf == 0);
@@ -4878,8 +4377,6 @@ impl Cpu {
 
     /// BIT 1,B
     fn op_cb48(&mut self) -> u8 {
-        trace!("BIT 1,B");
-
         let val = self.registers.b();
         let zf = val & (1 << 1);
         self.registers.set_zero_flag(zf == 0);
@@ -4891,8 +4388,6 @@ impl Cpu {
 
     /// BIT 1,C
     fn op_cb49(&mut self) -> u8 {
-        trace!("BIT 1,C");
-
         let val = self.registers.c();
         let zf = val & (1 << 1);
         self.registers.set_zero_flag(zf == 0);
@@ -4904,8 +4399,6 @@ impl Cpu {
 
     /// BIT 1,D
     fn op_cb4a(&mut self) -> u8 {
-        trace!("BIT 1,D");
-
         let val = self.registers.d();
         let zf = val & (1 << 1);
         self.registers.set_zero_flag(zf == 0);
@@ -4917,8 +4410,6 @@ impl Cpu {
 
     /// BIT 1,E
     fn op_cb4b(&mut self) -> u8 {
-        trace!("BIT 1,E");
-
         let val = self.registers.e();
         let zf = val & (1 << 1);
         self.registers.set_zero_flag(zf == 0);
@@ -4930,8 +4421,6 @@ impl Cpu {
 
     /// BIT 1,H
     fn op_cb4c(&mut self) -> u8 {
-        trace!("BIT 1,H");
-
         let val = self.registers.h();
         let zf = val & (1 << 1);
         self.registers.set_zero_flag(zf == 0);
@@ -4943,8 +4432,6 @@ impl Cpu {
 
     /// BIT 1,L
     fn op_cb4d(&mut self) -> u8 {
-        trace!("BIT 1,L");
-
         let val = self.registers.l();
         let zf = val & (1 << 1);
         self.registers.set_zero_flag(zf == 0);
@@ -4956,22 +4443,18 @@ impl Cpu {
 
     /// BIT 1,(HL)
     fn op_cb4e(&mut self) -> u8 {
-        trace!("BIT 1,(HL)");
-
         let hl = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(hl);
+        let val = self.read(hl);
         let zf = val & (1 << 1);
         self.registers.set_zero_flag(zf == 0);
         self.registers.set_negative_flag(false);
         self.registers.set_half_carry_flag(true);
 
-        16
+        12
     }
 
     /// BIT 1,A
     fn op_cb4f(&mut self) -> u8 {
-        trace!("BIT 1,A");
-
         let val = self.registers.a();
         let zf = val & (1 << 1);
         self.registers.set_zero_flag(zf == 0);
@@ -4983,8 +4466,6 @@ impl Cpu {
 
     /// BIT 2,B
     fn op_cb50(&mut self) -> u8 {
-        trace!("BIT 2,B");
-
         let val = self.registers.b();
         let zf = val & (1 << 2);
         self.registers.set_zero_flag(zf == 0);
@@ -4996,8 +4477,6 @@ impl Cpu {
 
     /// BIT 2,C
     fn op_cb51(&mut self) -> u8 {
-        trace!("BIT 2,C");
-
         let val = self.registers.c();
         let zf = val & (1 << 2);
         self.registers.set_zero_flag(zf == 0);
@@ -5009,8 +4488,6 @@ impl Cpu {
 
     /// BIT 2,D
     fn op_cb52(&mut self) -> u8 {
-        trace!("BIT 2,D");
-
         let val = self.registers.d();
         let zf = val & (1 << 2);
         self.registers.set_zero_flag(zf == 0);
@@ -5022,8 +4499,6 @@ impl Cpu {
 
     /// BIT 2,E
     fn op_cb53(&mut self) -> u8 {
-        trace!("BIT 2,E");
-
         let val = self.registers.e();
         let zf = val & (1 << 2);
         self.registers.set_zero_flag(zf == 0);
@@ -5035,8 +4510,6 @@ impl Cpu {
 
     /// BIT 2,H
     fn op_cb54(&mut self) -> u8 {
-        trace!("BIT 2,H");
-
         let val = self.registers.h();
         let zf = val & (1 << 2);
         self.registers.set_zero_flag(zf == 0);
@@ -5048,8 +4521,6 @@ impl Cpu {
 
     /// BIT 2,L
     fn op_cb55(&mut self) -> u8 {
-        trace!("BIT 2,L");
-
         let val = self.registers.l();
         let zf = val & (1 << 2);
         self.registers.set_zero_flag(zf == 0);
@@ -5061,22 +4532,18 @@ impl Cpu {
 
     /// BIT 2,(HL)
     fn op_cb56(&mut self) -> u8 {
-        trace!("BIT 2,(HL)");
-
         let hl = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(hl);
+        let val = self.read(hl);
         let zf = val & (1 << 2);
         self.registers.set_zero_flag(zf == 0);
         self.registers.set_negative_flag(false);
         self.registers.set_half_carry_flag(true);
 
-        16
+        12
     }
 
     /// BIT 2,A
     fn op_cb57(&mut self) -> u8 {
-        trace!("BIT 2,A");
-
         let val = self.registers.a();
         let zf = val & (1 << 2);
         self.registers.set_zero_flag(zf == 0);
@@ -5088,8 +4555,6 @@ impl Cpu {
 
     /// BIT 3,B
     fn op_cb58(&mut self) -> u8 {
-        trace!("BIT 3,B");
-
         let val = self.registers.b();
         let zf = val & (1 << 3);
         self.registers.set_zero_flag(zf == 0);
@@ -5101,8 +4566,6 @@ impl Cpu {
 
     /// BIT 3,C
     fn op_cb59(&mut self) -> u8 {
-        trace!("BIT 3,C");
-
         let val = self.registers.c();
         let zf = val & (1 << 3);
         self.registers.set_zero_flag(zf == 0);
@@ -5114,8 +4577,6 @@ impl Cpu {
 
     /// BIT 3,D
     fn op_cb5a(&mut self) -> u8 {
-        trace!("BIT 3,D");
-
         let val = self.registers.d();
         let zf = val & (1 << 3);
         self.registers.set_zero_flag(zf == 0);
@@ -5127,8 +4588,6 @@ impl Cpu {
 
     /// BIT 3,E
     fn op_cb5b(&mut self) -> u8 {
-        trace!("BIT 3,E");
-
         let val = self.registers.e();
         let zf = val & (1 << 3);
         self.registers.set_zero_flag(zf == 0);
@@ -5140,8 +4599,6 @@ impl Cpu {
 
     /// BIT 3,H
     fn op_cb5c(&mut self) -> u8 {
-        trace!("BIT 3,H");
-
         let val = self.registers.h();
         let zf = val & (1 << 3);
         self.registers.set_zero_flag(zf == 0);
@@ -5153,8 +4610,6 @@ impl Cpu {
 
     /// BIT 3,L
     fn op_cb5d(&mut self) -> u8 {
-        trace!("BIT 3,L");
-
         let val = self.registers.l();
         let zf = val & (1 << 3);
         self.registers.set_zero_flag(zf == 0);
@@ -5166,22 +4621,18 @@ impl Cpu {
 
     /// BIT 3,(HL)
     fn op_cb5e(&mut self) -> u8 {
-        trace!("BIT 3,(HL)");
-
         let hl = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(hl);
+        let val = self.read(hl);
         let zf = val & (1 << 3);
         self.registers.set_zero_flag(zf == 0);
         self.registers.set_negative_flag(false);
         self.registers.set_half_carry_flag(true);
 
-        16
+        12
     }
 
     /// BIT 3,A
     fn op_cb5f(&mut self) -> u8 {
-        trace!("BIT 3,A");
-
         let val = self.registers.a();
         let zf = val & (1 << 3);
         self.registers.set_zero_flag(zf == 0);
@@ -5193,8 +4644,6 @@ impl Cpu {
 
     /// BIT 4,B
     fn op_cb60(&mut self) -> u8 {
-        trace!("BIT 4,B");
-
         let val = self.registers.b();
         let zf = val & (1 << 4);
         self.registers.set_zero_flag(zf == 0);
@@ -5206,8 +4655,6 @@ impl Cpu {
 
     /// BIT 4,C
     fn op_cb61(&mut self) -> u8 {
-        trace!("BIT 4,C");
-
         let val = self.registers.c();
         let zf = val & (1 << 4);
         self.registers.set_zero_flag(zf == 0);
@@ -5219,8 +4666,6 @@ impl Cpu {
 
     /// BIT 4,D
     fn op_cb62(&mut self) -> u8 {
-        trace!("BIT 4,D");
-
         let val = self.registers.d();
         let zf = val & (1 << 4);
         self.registers.set_zero_flag(zf == 0);
@@ -5232,8 +4677,6 @@ impl Cpu {
 
     /// BIT 4,E
     fn op_cb63(&mut self) -> u8 {
-        trace!("BIT 4,E");
-
         let val = self.registers.e();
         let zf = val & (1 << 4);
         self.registers.set_zero_flag(zf == 0);
@@ -5245,8 +4688,6 @@ impl Cpu {
 
     /// BIT 4,H
     fn op_cb64(&mut self) -> u8 {
-        trace!("BIT 4,H");
-
         let val = self.registers.h();
         let zf = val & (1 << 4);
         self.registers.set_zero_flag(zf == 0);
@@ -5258,8 +4699,6 @@ impl Cpu {
 
     /// BIT 4,L
     fn op_cb65(&mut self) -> u8 {
-        trace!("BIT 4,L");
-
         let val = self.registers.l();
         let zf = val & (1 << 4);
         self.registers.set_zero_flag(zf == 0);
@@ -5271,22 +4710,18 @@ impl Cpu {
 
     /// BIT 4,(HL)
     fn op_cb66(&mut self) -> u8 {
-        trace!("BIT 4,(HL)");
-
         let hl = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(hl);
+        let val = self.read(hl);
         let zf = val & (1 << 4);
         self.registers.set_zero_flag(zf == 0);
         self.registers.set_negative_flag(false);
         self.registers.set_half_carry_flag(true);
 
-        16
+        12
     }
 
     /// BIT 4,A
     fn op_cb67(&mut self) -> u8 {
-        trace!("BIT 4,A");
-
         let val = self.registers.a();
         let zf = val & (1 << 4);
         self.registers.set_zero_flag(zf == 0);
@@ -5298,8 +4733,6 @@ impl Cpu {
 
     /// BIT 5,B
     fn op_cb68(&mut self) -> u8 {
-        trace!("BIT 5,B");
-
         let val = self.registers.b();
         let zf = val & (1 << 5);
         self.registers.set_zero_flag(zf == 0);
@@ -5311,8 +4744,6 @@ impl Cpu {
 
     /// BIT 5,C
     fn op_cb69(&mut self) -> u8 {
-        trace!("BIT 5,C");
-
         let val = self.registers.c();
         let zf = val & (1 << 5);
         self.registers.set_zero_flag(zf == 0);
@@ -5324,8 +4755,6 @@ impl Cpu {
 
     /// BIT 5,D
     fn op_cb6a(&mut self) -> u8 {
-        trace!("BIT 5,D");
-
         let val = self.registers.d();
         let zf = val & (1 << 5);
         self.registers.set_zero_flag(zf == 0);
@@ -5337,8 +4766,6 @@ impl Cpu {
 
     /// BIT 5,E
     fn op_cb6b(&mut self) -> u8 {
-        trace!("BIT 5,E");
-
         let val = self.registers.e();
         let zf = val & (1 << 5);
         self.registers.set_zero_flag(zf == 0);
@@ -5350,8 +4777,6 @@ impl Cpu {
 
     /// BIT 5,H
     fn op_cb6c(&mut self) -> u8 {
-        trace!("BIT 5,H");
-
         let val = self.registers.h();
         let zf = val & (1 << 5);
         self.registers.set_zero_flag(zf == 0);
@@ -5363,8 +4788,6 @@ impl Cpu {
 
     /// BIT 5,L
     fn op_cb6d(&mut self) -> u8 {
-        trace!("BIT 5,L");
-
         let val = self.registers.l();
         let zf = val & (1 << 5);
         self.registers.set_zero_flag(zf == 0);
@@ -5376,22 +4799,18 @@ impl Cpu {
 
     /// BIT 5,(HL)
     fn op_cb6e(&mut self) -> u8 {
-        trace!("BIT 5,(HL)");
-
         let hl = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(hl);
+        let val = self.read(hl);
         let zf = val & (1 << 5);
         self.registers.set_zero_flag(zf == 0);
         self.registers.set_negative_flag(false);
         self.registers.set_half_carry_flag(true);
 
-        16
+        12
     }
 
     /// BIT 5,A
     fn op_cb6f(&mut self) -> u8 {
-        trace!("BIT 5,A");
-
         let val = self.registers.a();
         let zf = val & (1 << 5);
         self.registers.set_zero_flag(zf == 0);
@@ -5403,8 +4822,6 @@ impl Cpu {
 
     /// BIT 6,B
     fn op_cb70(&mut self) -> u8 {
-        trace!("BIT 6,B");
-
         let val = self.registers.b();
         let zf = val & (1 << 6);
         self.registers.set_zero_flag(zf == 0);
@@ -5416,8 +4833,6 @@ impl Cpu {
 
     /// BIT 6,C
     fn op_cb71(&mut self) -> u8 {
-        trace!("BIT 6,C");
-
         let val = self.registers.c();
         let zf = val & (1 << 6);
         self.registers.set_zero_flag(zf == 0);
@@ -5429,8 +4844,6 @@ impl Cpu {
 
     /// BIT 6,D
     fn op_cb72(&mut self) -> u8 {
-        trace!("BIT 6,D");
-
         let val = self.registers.d();
         let zf = val & (1 << 6);
         self.registers.set_zero_flag(zf == 0);
@@ -5442,8 +4855,6 @@ impl Cpu {
 
     /// BIT 6,E
     fn op_cb73(&mut self) -> u8 {
-        trace!("BIT 6,E");
-
         let val = self.registers.e();
         let zf = val & (1 << 6);
         self.registers.set_zero_flag(zf == 0);
@@ -5455,8 +4866,6 @@ impl Cpu {
 
     /// BIT 6,H
     fn op_cb74(&mut self) -> u8 {
-        trace!("BIT 6,H");
-
         let val = self.registers.h();
         let zf = val & (1 << 6);
         self.registers.set_zero_flag(zf == 0);
@@ -5468,8 +4877,6 @@ impl Cpu {
 
     /// BIT 6,L
     fn op_cb75(&mut self) -> u8 {
-        trace!("BIT 6,L");
-
         let val = self.registers.l();
         let zf = val & (1 << 6);
         self.registers.set_zero_flag(zf == 0);
@@ -5481,22 +4888,18 @@ impl Cpu {
 
     /// BIT 6,(HL)
     fn op_cb76(&mut self) -> u8 {
-        trace!("BIT 6,(HL)");
-
         let hl = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(hl);
+        let val = self.read(hl);
         let zf = val & (1 << 6);
         self.registers.set_zero_flag(zf == 0);
         self.registers.set_negative_flag(false);
         self.registers.set_half_carry_flag(true);
 
-        16
+        12
     }
 
     /// BIT 6,A
     fn op_cb77(&mut self) -> u8 {
-        trace!("BIT 6,A");
-
         let val = self.registers.a();
         let zf = val & (1 << 6);
         self.registers.set_zero_flag(zf == 0);
@@ -5508,8 +4911,6 @@ impl Cpu {
 
     /// BIT 7,B
     fn op_cb78(&mut self) -> u8 {
-        trace!("BIT 7,B");
-
         let val = self.registers.b();
         let zf = val & (1 << 7);
         self.registers.set_zero_flag(zf == 0);
@@ -5521,8 +4922,6 @@ impl Cpu {
 
     /// BIT 7,C
     fn op_cb79(&mut self) -> u8 {
-        trace!("BIT 7,C");
-
         let val = self.registers.c();
         let zf = val & (1 << 7);
         self.registers.set_zero_flag(zf == 0);
@@ -5534,8 +4933,6 @@ impl Cpu {
 
     /// BIT 7,D
     fn op_cb7a(&mut self) -> u8 {
-        trace!("BIT 7,D");
-
         let val = self.registers.d();
         let zf = val & (1 << 7);
         self.registers.set_zero_flag(zf == 0);
@@ -5547,8 +4944,6 @@ impl Cpu {
 
     /// BIT 7,E
     fn op_cb7b(&mut self) -> u8 {
-        trace!("BIT 7,E");
-
         let val = self.registers.e();
         let zf = val & (1 << 7);
         self.registers.set_zero_flag(zf == 0);
@@ -5560,8 +4955,6 @@ impl Cpu {
 
     /// BIT 7,H
     fn op_cb7c(&mut self) -> u8 {
-        trace!("BIT 7,H");
-
         let val = self.registers.h();
         let zf = val & (1 << 7);
         self.registers.set_zero_flag(zf == 0);
@@ -5573,8 +4966,6 @@ impl Cpu {
 
     /// BIT 7,L
     fn op_cb7d(&mut self) -> u8 {
-        trace!("BIT 7,L");
-
         let val = self.registers.l();
         let zf = val & (1 << 7);
         self.registers.set_zero_flag(zf == 0);
@@ -5586,22 +4977,18 @@ impl Cpu {
 
     /// BIT 7,(HL)
     fn op_cb7e(&mut self) -> u8 {
-        trace!("BIT 7,(HL)");
-
         let hl = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(hl);
+        let val = self.read(hl);
         let zf = val & (1 << 7);
         self.registers.set_zero_flag(zf == 0);
         self.registers.set_negative_flag(false);
         self.registers.set_half_carry_flag(true);
 
-        16
+        12
     }
 
     /// BIT 7,A
     fn op_cb7f(&mut self) -> u8 {
-        trace!("BIT 7,A");
-
         let val = self.registers.a();
         let zf = val & (1 << 7);
         self.registers.set_zero_flag(zf == 0);
@@ -5613,8 +5000,6 @@ impl Cpu {
 
     /// RES 0,B
     fn op_cb80(&mut self) -> u8 {
-        trace!("RES 0,B");
-
         let val = self.registers.b();
         let res = val & !(1 << 0);
         self.registers.set_b(res);
@@ -5624,8 +5009,6 @@ impl Cpu {
 
     /// RES 0,C
     fn op_cb81(&mut self) -> u8 {
-        trace!("RES 0,C");
-
         let val = self.registers.c();
         let res = val & !(1 << 0);
         self.registers.set_c(res);
@@ -5635,8 +5018,6 @@ impl Cpu {
 
     /// RES 0,D
     fn op_cb82(&mut self) -> u8 {
-        trace!("RES 0,D");
-
         let val = self.registers.d();
         let res = val & !(1 << 0);
         self.registers.set_d(res);
@@ -5646,8 +5027,6 @@ impl Cpu {
 
     /// RES 0,E
     fn op_cb83(&mut self) -> u8 {
-        trace!("RES 0,E");
-
         let val = self.registers.e();
         let res = val & !(1 << 0);
         self.registers.set_e(res);
@@ -5657,8 +5036,6 @@ impl Cpu {
 
     /// RES 0,H
     fn op_cb84(&mut self) -> u8 {
-        trace!("RES 0,H");
-
         let val = self.registers.h();
         let res = val & !(1 << 0);
         self.registers.set_h(res);
@@ -5668,8 +5045,6 @@ impl Cpu {
 
     /// RES 0,L
     fn op_cb85(&mut self) -> u8 {
-        trace!("RES 0,L");
-
         let val = self.registers.l();
         let res = val & !(1 << 0);
         self.registers.set_l(res);
@@ -5679,20 +5054,16 @@ impl Cpu {
 
     /// RES 0,(HL)
     fn op_cb86(&mut self) -> u8 {
-        trace!("RES 0,(HL)");
-
         let hl = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(hl);
+        let val = self.read(hl);
         let res = val & !(1 << 0);
-        self.mmu.borrow_mut().write_byte(hl, res);
+        self.write(hl, res);
 
         16
     }
 
     /// RES 0,A
     fn op_cb87(&mut self) -> u8 {
-        trace!("RES 0,A");
-
         let val = self.registers.a();
         let res = val & !(1 << 0);
         self.registers.set_a(res);
@@ -5702,8 +5073,6 @@ impl Cpu {
 
     /// RES 1,B
     fn op_cb88(&mut self) -> u8 {
-        trace!("RES 1,B");
-
         let val = self.registers.b();
         let res = val & !(1 << 1);
         self.registers.set_b(res);
@@ -5713,8 +5082,6 @@ impl Cpu {
 
     /// RES 1,C
     fn op_cb89(&mut self) -> u8 {
-        trace!("RES 1,C");
-
         let val = self.registers.c();
         let res = val & !(1 << 1);
         self.registers.set_c(res);
@@ -5724,8 +5091,6 @@ impl Cpu {
 
     /// RES 1,D
     fn op_cb8a(&mut self) -> u8 {
-        trace!("RES 1,D");
-
         let val = self.registers.d();
         let res = val & !(1 << 1);
         self.registers.set_d(res);
@@ -5735,8 +5100,6 @@ impl Cpu {
 
     /// RES 1,E
     fn op_cb8b(&mut self) -> u8 {
-        trace!("RES 1,E");
-
         let val = self.registers.e();
         let res = val & !(1 << 1);
         self.registers.set_e(res);
@@ -5746,8 +5109,6 @@ impl Cpu {
 
     /// RES 1,H
     fn op_cb8c(&mut self) -> u8 {
-        trace!("RES 1,H");
-
         let val = self.registers.h();
         let res = val & !(1 << 1);
         self.registers.set_h(res);
@@ -5757,8 +5118,6 @@ impl Cpu {
 
     /// RES 1,L
     fn op_cb8d(&mut self) -> u8 {
-        trace!("RES 1,L");
-
         let val = self.registers.l();
         let res = val & !(1 << 1);
         self.registers.set_l(res);
@@ -5768,20 +5127,16 @@ impl Cpu {
 
     /// RES 1,(HL)
     fn op_cb8e(&mut self) -> u8 {
-        trace!("RES 1,(HL)");
-
         let hl = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(hl);
+        let val = self.read(hl);
         let res = val & !(1 << 1);
-        self.mmu.borrow_mut().write_byte(hl, res);
+        self.write(hl, res);
 
         16
     }
 
     /// RES 1,A
     fn op_cb8f(&mut self) -> u8 {
-        trace!("RES 1,A");
-
         let val = self.registers.a();
         let res = val & !(1 << 1);
         self.registers.set_a(res);
@@ -5791,8 +5146,6 @@ impl Cpu {
 
     /// RES 2,B
     fn op_cb90(&mut self) -> u8 {
-        trace!("RES 2,B");
-
         let val = self.registers.b();
         let res = val & !(1 << 2);
         self.registers.set_b(res);
@@ -5802,8 +5155,6 @@ impl Cpu {
 
     /// RES 2,C
     fn op_cb91(&mut self) -> u8 {
-        trace!("RES 2,C");
-
         let val = self.registers.c();
         let res = val & !(1 << 2);
         self.registers.set_c(res);
@@ -5813,8 +5164,6 @@ impl Cpu {
 
     /// RES 2,D
     fn op_cb92(&mut self) -> u8 {
-        trace!("RES 2,D");
-
         let val = self.registers.d();
         let res = val & !(1 << 2);
         self.registers.set_d(res);
@@ -5824,8 +5173,6 @@ impl Cpu {
 
     /// RES 2,E
     fn op_cb93(&mut self) -> u8 {
-        trace!("RES 2,E");
-
         let val = self.registers.e();
         let res = val & !(1 << 2);
         self.registers.set_e(res);
@@ -5835,8 +5182,6 @@ impl Cpu {
 
     /// RES 2,H
     fn op_cb94(&mut self) -> u8 {
-        trace!("RES 2,H");
-
         let val = self.registers.h();
         let res = val & !(1 << 2);
         self.registers.set_h(res);
@@ -5846,8 +5191,6 @@ impl Cpu {
 
     /// RES 2,L
     fn op_cb95(&mut self) -> u8 {
-        trace!("RES 2,L");
-
         let val = self.registers.l();
         let res = val & !(1 << 2);
         self.registers.set_l(res);
@@ -5857,20 +5200,16 @@ impl Cpu {
 
     /// RES 2,(HL)
     fn op_cb96(&mut self) -> u8 {
-        trace!("RES 2,(HL)");
-
         let hl = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(hl);
+        let val = self.read(hl);
         let res = val & !(1 << 2);
-        self.mmu.borrow_mut().write_byte(hl, res);
+        self.write(hl, res);
 
         16
     }
 
     /// RES 2,A
     fn op_cb97(&mut self) -> u8 {
-        trace!("RES 2,A");
-
         let val = self.registers.a();
         let res = val & !(1 << 2);
         self.registers.set_a(res);
@@ -5880,8 +5219,6 @@ impl Cpu {
 
     /// RES 3,B
     fn op_cb98(&mut self) -> u8 {
-        trace!("RES 3,B");
-
         let val = self.registers.b();
         let res = val & !(1 << 3);
         self.registers.set_b(res);
@@ -5891,8 +5228,6 @@ impl Cpu {
 
     /// RES 3,C
     fn op_cb99(&mut self) -> u8 {
-        trace!("RES 3,C");
-
         let val = self.registers.c();
         let res = val & !(1 << 3);
         self.registers.set_c(res);
@@ -5902,8 +5237,6 @@ impl Cpu {
 
     /// RES 3,D
     fn op_cb9a(&mut self) -> u8 {
-        trace!("RES 3,D");
-
         let val = self.registers.d();
         let res = val & !(1 << 3);
         self.registers.set_d(res);
@@ -5913,8 +5246,6 @@ impl Cpu {
 
     /// RES 3,E
     fn op_cb9b(&mut self) -> u8 {
-        trace!("RES 3,E");
-
         let val = self.registers.e();
         let res = val & !(1 << 3);
         self.registers.set_e(res);
@@ -5924,8 +5255,6 @@ impl Cpu {
 
     /// RES 3,H
     fn op_cb9c(&mut self) -> u8 {
-        trace!("RES 3,H");
-
         let val = self.registers.h();
         let res = val & !(1 << 3);
         self.registers.set_h(res);
@@ -5935,8 +5264,6 @@ impl Cpu {
 
     /// RES 3,L
     fn op_cb9d(&mut self) -> u8 {
-        trace!("RES 3,L");
-
         let val = self.registers.l();
         let res = val & !(1 << 3);
         self.registers.set_l(res);
@@ -5946,20 +5273,16 @@ impl Cpu {
 
     /// RES 3,(HL)
     fn op_cb9e(&mut self) -> u8 {
-        trace!("RES 3,(HL)");
-
         let hl = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(hl);
+        let val = self.read(hl);
         let res = val & !(1 << 3);
-        self.mmu.borrow_mut().write_byte(hl, res);
+        self.write(hl, res);
 
         16
     }
 
     /// RES 3,A
     fn op_cb9f(&mut self) -> u8 {
-        trace!("RES 3,A");
-
         let val = self.registers.a();
         let res = val & !(1 << 3);
         self.registers.set_a(res);
@@ -5969,8 +5292,6 @@ impl Cpu {
 
     /// RES 4,B
     fn op_cba0(&mut self) -> u8 {
-        trace!("RES 4,B");
-
         let val = self.registers.b();
         let res = val & !(1 << 4);
         self.registers.set_b(res);
@@ -5980,8 +5301,6 @@ impl Cpu {
 
     /// RES 4,C
     fn op_cba1(&mut self) -> u8 {
-        trace!("RES 4,C");
-
         let val = self.registers.c();
         let res = val & !(1 << 4);
         self.registers.set_c(res);
@@ -5991,8 +5310,6 @@ impl Cpu {
 
     /// RES 4,D
     fn op_cba2(&mut self) -> u8 {
-        trace!("RES 4,D");
-
         let val = self.registers.d();
         let res = val & !(1 << 4);
         self.registers.set_d(res);
@@ -6002,8 +5319,6 @@ impl Cpu {
 
     /// RES 4,E
     fn op_cba3(&mut self) -> u8 {
-        trace!("RES 4,E");
-
         let val = self.registers.e();
         let res = val & !(1 << 4);
         self.registers.set_e(res);
@@ -6013,8 +5328,6 @@ impl Cpu {
 
     /// RES 4,H
     fn op_cba4(&mut self) -> u8 {
-        trace!("RES 4,H");
-
         let val = self.registers.h();
         let res = val & !(1 << 4);
         self.registers.set_h(res);
@@ -6024,8 +5337,6 @@ impl Cpu {
 
     /// RES 4,L
     fn op_cba5(&mut self) -> u8 {
-        trace!("RES 4,L");
-
         let val = self.registers.l();
         let res = val & !(1 << 4);
         self.registers.set_l(res);
@@ -6035,20 +5346,16 @@ impl Cpu {
 
     /// RES 4,(HL)
     fn op_cba6(&mut self) -> u8 {
-        trace!("RES 4,(HL)");
-
         let hl = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(hl);
+        let val = self.read(hl);
         let res = val & !(1 << 4);
-        self.mmu.borrow_mut().write_byte(hl, res);
+        self.write(hl, res);
 
         16
     }
 
     /// RES 4,A
     fn op_cba7(&mut self) -> u8 {
-        trace!("RES 4,A");
-
         let val = self.registers.a();
         let res = val & !(1 << 4);
         self.registers.set_a(res);
@@ -6058,8 +5365,6 @@ impl Cpu {
 
     /// RES 5,B
     fn op_cba8(&mut self) -> u8 {
-        trace!("RES 5,B");
-
         let val = self.registers.b();
         let res = val & !(1 << 5);
         self.registers.set_b(res);
@@ -6069,8 +5374,6 @@ impl Cpu {
 
     /// RES 5,C
     fn op_cba9(&mut self) -> u8 {
-        trace!("RES 5,C");
-
         let val = self.registers.c();
         let res = val & !(1 << 5);
         self.registers.set_c(res);
@@ -6080,8 +5383,6 @@ impl Cpu {
 
     /// RES 5,D
     fn op_cbaa(&mut self) -> u8 {
-        trace!("RES 5,D");
-
         let val = self.registers.d();
         let res = val & !(1 << 5);
         self.registers.set_d(res);
@@ -6091,8 +5392,6 @@ impl Cpu {
 
     /// RES 5,E
     fn op_cbab(&mut self) -> u8 {
-        trace!("RES 5,E");
-
         let val = self.registers.e();
         let res = val & !(1 << 5);
         self.registers.set_e(res);
@@ -6102,8 +5401,6 @@ impl Cpu {
 
     /// RES 5,H
     fn op_cbac(&mut self) -> u8 {
-        trace!("RES 5,H");
-
         let val = self.registers.h();
         let res = val & !(1 << 5);
         self.registers.set_h(res);
@@ -6113,8 +5410,6 @@ impl Cpu {
 
     /// RES 5,L
     fn op_cbad(&mut self) -> u8 {
-        trace!("RES 5,L");
-
         let val = self.registers.l();
         let res = val & !(1 << 5);
         self.registers.set_l(res);
@@ -6124,20 +5419,16 @@ impl Cpu {
 
     /// RES 5,(HL)
     fn op_cbae(&mut self) -> u8 {
-        trace!("RES 5,(HL)");
-
         let hl = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(hl);
+        let val = self.read(hl);
         let res = val & !(1 << 5);
-        self.mmu.borrow_mut().write_byte(hl, res);
+        self.write(hl, res);
 
         16
     }
 
     /// RES 5,A
     fn op_cbaf(&mut self) -> u8 {
-        trace!("RES 5,A");
-
         let val = self.registers.a();
         let res = val & !(1 << 5);
         self.registers.set_a(res);
@@ -6147,8 +5438,6 @@ impl Cpu {
 
     /// RES 6,B
     fn op_cbb0(&mut self) -> u8 {
-        trace!("RES 6,B");
-
         let val = self.registers.b();
         let res = val & !(1 << 6);
         self.registers.set_b(res);
@@ -6158,8 +5447,6 @@ impl Cpu {
 
     /// RES 6,C
     fn op_cbb1(&mut self) -> u8 {
-        trace!("RES 6,C");
-
         let val = self.registers.c();
         let res = val & !(1 << 6);
         self.registers.set_c(res);
@@ -6169,8 +5456,6 @@ impl Cpu {
 
     /// RES 6,D
     fn op_cbb2(&mut self) -> u8 {
-        trace!("RES 6,D");
-
         let val = self.registers.d();
         let res = val & !(1 << 6);
         self.registers.set_d(res);
@@ -6180,8 +5465,6 @@ impl Cpu {
 
     /// RES 6,E
     fn op_cbb3(&mut self) -> u8 {
-        trace!("RES 6,E");
-
         let val = self.registers.e();
         let res = val & !(1 << 6);
         self.registers.set_e(res);
@@ -6191,8 +5474,6 @@ impl Cpu {
 
     /// RES 6,H
     fn op_cbb4(&mut self) -> u8 {
-        trace!("RES 6,H");
-
         let val = self.registers.h();
         let res = val & !(1 << 6);
         self.registers.set_h(res);
@@ -6202,8 +5483,6 @@ impl Cpu {
 
     /// RES 6,L
     fn op_cbb5(&mut self) -> u8 {
-        trace!("RES 6,L");
-
         let val = self.registers.l();
         let res = val & !(1 << 6);
         self.registers.set_l(res);
@@ -6213,20 +5492,16 @@ impl Cpu {
 
     /// RES 6,(HL)
     fn op_cbb6(&mut self) -> u8 {
-        trace!("RES 6,(HL)");
-
         let hl = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(hl);
+        let val = self.read(hl);
         let res = val & !(1 << 6);
-        self.mmu.borrow_mut().write_byte(hl, res);
+        self.write(hl, res);
 
         16
     }
 
     /// RES 6,A
     fn op_cbb7(&mut self) -> u8 {
-        trace!("RES 6,A");
-
         let val = self.registers.a();
         let res = val & !(1 << 6);
         self.registers.set_a(res);
@@ -6236,8 +5511,6 @@ impl Cpu {
 
     /// RES 7,B
     fn op_cbb8(&mut self) -> u8 {
-        trace!("RES 7,B");
-
         let val = self.registers.b();
         let res = val & !(1 << 7);
         self.registers.set_b(res);
@@ -6247,8 +5520,6 @@ impl Cpu {
 
     /// RES 7,C
     fn op_cbb9(&mut self) -> u8 {
-        trace!("RES 7,C");
-
         let val = self.registers.c();
         let res = val & !(1 << 7);
         self.registers.set_c(res);
@@ -6258,8 +5529,6 @@ impl Cpu {
 
     /// RES 7,D
     fn op_cbba(&mut self) -> u8 {
-        trace!("RES 7,D");
-
         let val = self.registers.d();
         let res = val & !(1 << 7);
         self.registers.set_d(res);
@@ -6269,8 +5538,6 @@ impl Cpu {
 
     /// RES 7,E
     fn op_cbbb(&mut self) -> u8 {
-        trace!("RES 7,E");
-
         let val = self.registers.e();
         let res = val & !(1 << 7);
         self.registers.set_e(res);
@@ -6280,8 +5547,6 @@ impl Cpu {
 
     /// RES 7,H
     fn op_cbbc(&mut self) -> u8 {
-        trace!("RES 7,H");
-
         let val = self.registers.h();
         let res = val & !(1 << 7);
         self.registers.set_h(res);
@@ -6291,8 +5556,6 @@ impl Cpu {
 
     /// RES 7,L
     fn op_cbbd(&mut self) -> u8 {
-        trace!("RES 7,L");
-
         let val = self.registers.l();
         let res = val & !(1 << 7);
         self.registers.set_l(res);
@@ -6302,20 +5565,16 @@ impl Cpu {
 
     /// RES 7,(HL)
     fn op_cbbe(&mut self) -> u8 {
-        trace!("RES 7,(HL)");
-
         let hl = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(hl);
+        let val = self.read(hl);
         let res = val & !(1 << 7);
-        self.mmu.borrow_mut().write_byte(hl, res);
+        self.write(hl, res);
 
         16
     }
 
     /// RES 7,A
     fn op_cbbf(&mut self) -> u8 {
-        trace!("RES 7,A");
-
         let val = self.registers.a();
         let res = val & !(1 << 7);
         self.registers.set_a(res);
@@ -6325,8 +5584,6 @@ impl Cpu {
 
     /// SET 0,B
     fn op_cbc0(&mut self) -> u8 {
-        trace!("SET 0,B");
-
         let val = self.registers.b();
         let res = val | (1 << 0);
         self.registers.set_b(res);
@@ -6336,8 +5593,6 @@ impl Cpu {
 
     /// SET 0,C
     fn op_cbc1(&mut self) -> u8 {
-        trace!("SET 0,C");
-
         let val = self.registers.c();
         let res = val | (1 << 0);
         self.registers.set_c(res);
@@ -6347,8 +5602,6 @@ impl Cpu {
 
     /// SET 0,D
     fn op_cbc2(&mut self) -> u8 {
-        trace!("SET 0,D");
-
         let val = self.registers.d();
         let res = val | (1 << 0);
         self.registers.set_d(res);
@@ -6358,8 +5611,6 @@ impl Cpu {
 
     /// SET 0,E
     fn op_cbc3(&mut self) -> u8 {
-        trace!("SET 0,E");
-
         let val = self.registers.e();
         let res = val | (1 << 0);
         self.registers.set_e(res);
@@ -6369,8 +5620,6 @@ impl Cpu {
 
     /// SET 0,H
     fn op_cbc4(&mut self) -> u8 {
-        trace!("SET 0,H");
-
         let val = self.registers.h();
         let res = val | (1 << 0);
         self.registers.set_h(res);
@@ -6380,8 +5629,6 @@ impl Cpu {
 
     /// SET 0,L
     fn op_cbc5(&mut self) -> u8 {
-        trace!("SET 0,L");
-
         let val = self.registers.l();
         let res = val | (1 << 0);
         self.registers.set_l(res);
@@ -6391,20 +5638,16 @@ impl Cpu {
 
     /// SET 0,(HL)
     fn op_cbc6(&mut self) -> u8 {
-        trace!("SET 0,(HL)");
-
         let hl = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(hl);
+        let val = self.read(hl);
         let res = val | (1 << 0);
-        self.mmu.borrow_mut().write_byte(hl, res);
+        self.write(hl, res);
 
         16
     }
 
     /// SET 0,A
     fn op_cbc7(&mut self) -> u8 {
-        trace!("SET 0,A");
-
         let val = self.registers.a();
         let res = val | (1 << 0);
         self.registers.set_a(res);
@@ -6414,8 +5657,6 @@ impl Cpu {
 
     /// SET 1,B
     fn op_cbc8(&mut self) -> u8 {
-        trace!("SET 1,B");
-
         let val = self.registers.b();
         let res = val | (1 << 1);
         self.registers.set_b(res);
@@ -6425,8 +5666,6 @@ impl Cpu {
 
     /// SET 1,C
     fn op_cbc9(&mut self) -> u8 {
-        trace!("SET 1,C");
-
         let val = self.registers.c();
         let res = val | (1 << 1);
         self.registers.set_c(res);
@@ -6436,8 +5675,6 @@ impl Cpu {
 
     /// SET 1,D
     fn op_cbca(&mut self) -> u8 {
-        trace!("SET 1,D");
-
         let val = self.registers.d();
         let res = val | (1 << 1);
         self.registers.set_d(res);
@@ -6447,8 +5684,6 @@ impl Cpu {
 
     /// SET 1,E
     fn op_cbcb(&mut self) -> u8 {
-        trace!("SET 1,E");
-
         let val = self.registers.e();
         let res = val | (1 << 1);
         self.registers.set_e(res);
@@ -6458,8 +5693,6 @@ impl Cpu {
 
     /// SET 1,H
     fn op_cbcc(&mut self) -> u8 {
-        trace!("SET 1,H");
-
         let val = self.registers.h();
         let res = val | (1 << 1);
         self.registers.set_h(res);
@@ -6469,8 +5702,6 @@ impl Cpu {
 
     /// SET 1,L
     fn op_cbcd(&mut self) -> u8 {
-        trace!("SET 1,L");
-
         let val = self.registers.l();
         let res = val | (1 << 1);
         self.registers.set_l(res);
@@ -6480,20 +5711,16 @@ impl Cpu {
 
     /// SET 1,(HL)
     fn op_cbce(&mut self) -> u8 {
-        trace!("SET 1,(HL)");
-
         let hl = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(hl);
+        let val = self.read(hl);
         let res = val | (1 << 1);
-        self.mmu.borrow_mut().write_byte(hl, res);
+        self.write(hl, res);
 
         16
     }
 
     /// SET 1,A
     fn op_cbcf(&mut self) -> u8 {
-        trace!("SET 1,A");
-
         let val = self.registers.a();
         let res = val | (1 << 1);
         self.registers.set_a(res);
@@ -6503,8 +5730,6 @@ impl Cpu {
 
     /// SET 2,B
     fn op_cbd0(&mut self) -> u8 {
-        trace!("SET 2,B");
-
         let val = self.registers.b();
         let res = val | (1 << 2);
         self.registers.set_b(res);
@@ -6514,8 +5739,6 @@ impl Cpu {
 
     /// SET 2,C
     fn op_cbd1(&mut self) -> u8 {
-        trace!("SET 2,C");
-
         let val = self.registers.c();
         let res = val | (1 << 2);
         self.registers.set_c(res);
@@ -6525,8 +5748,6 @@ impl Cpu {
 
     /// SET 2,D
     fn op_cbd2(&mut self) -> u8 {
-        trace!("SET 2,D");
-
         let val = self.registers.d();
         let res = val | (1 << 2);
         self.registers.set_d(res);
@@ -6536,8 +5757,6 @@ impl Cpu {
 
     /// SET 2,E
     fn op_cbd3(&mut self) -> u8 {
-        trace!("SET 2,E");
-
         let val = self.registers.e();
         let res = val | (1 << 2);
         self.registers.set_e(res);
@@ -6547,8 +5766,6 @@ impl Cpu {
 
     /// SET 2,H
     fn op_cbd4(&mut self) -> u8 {
-        trace!("SET 2,H");
-
         let val = self.registers.h();
         let res = val | (1 << 2);
         self.registers.set_h(res);
@@ -6558,8 +5775,6 @@ impl Cpu {
 
     /// SET 2,L
     fn op_cbd5(&mut self) -> u8 {
-        trace!("SET 2,L");
-
         let val = self.registers.l();
         let res = val | (1 << 2);
         self.registers.set_l(res);
@@ -6569,20 +5784,16 @@ impl Cpu {
 
     /// SET 2,(HL)
     fn op_cbd6(&mut self) -> u8 {
-        trace!("SET 2,(HL)");
-
         let hl = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(hl);
+        let val = self.read(hl);
         let res = val | (1 << 2);
-        self.mmu.borrow_mut().write_byte(hl, res);
+        self.write(hl, res);
 
         16
     }
 
     /// SET 2,A
     fn op_cbd7(&mut self) -> u8 {
-        trace!("SET 2,A");
-
         let val = self.registers.a();
         let res = val | (1 << 2);
         self.registers.set_a(res);
@@ -6592,8 +5803,6 @@ impl Cpu {
 
     /// SET 3,B
     fn op_cbd8(&mut self) -> u8 {
-        trace!("SET 3,B");
-
         let val = self.registers.b();
         let res = val | (1 << 3);
         self.registers.set_b(res);
@@ -6603,8 +5812,6 @@ impl Cpu {
 
     /// SET 3,C
     fn op_cbd9(&mut self) -> u8 {
-        trace!("SET 3,C");
-
         let val = self.registers.c();
         let res = val | (1 << 3);
         self.registers.set_c(res);
@@ -6614,8 +5821,6 @@ impl Cpu {
 
     /// SET 3,D
     fn op_cbda(&mut self) -> u8 {
-        trace!("SET 3,D");
-
         let val = self.registers.d();
         let res = val | (1 << 3);
         self.registers.set_d(res);
@@ -6625,8 +5830,6 @@ impl Cpu {
 
     /// SET 3,E
     fn op_cbdb(&mut self) -> u8 {
-        trace!("SET 3,E");
-
         let val = self.registers.e();
         let res = val | (1 << 3);
         self.registers.set_e(res);
@@ -6636,8 +5839,6 @@ impl Cpu {
 
     /// SET 3,H
     fn op_cbdc(&mut self) -> u8 {
-        trace!("SET 3,H");
-
         let val = self.registers.h();
         let res = val | (1 << 3);
         self.registers.set_h(res);
@@ -6647,8 +5848,6 @@ impl Cpu {
 
     /// SET 3,L
     fn op_cbdd(&mut self) -> u8 {
-        trace!("SET 3,L");
-
         let val = self.registers.l();
         let res = val | (1 << 3);
         self.registers.set_l(res);
@@ -6658,20 +5857,16 @@ impl Cpu {
 
     /// SET 3,(HL)
     fn op_cbde(&mut self) -> u8 {
-        trace!("SET 3,(HL)");
-
         let hl = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(hl);
+        let val = self.read(hl);
         let res = val | (1 << 3);
-        self.mmu.borrow_mut().write_byte(hl, res);
+        self.write(hl, res);
 
         16
     }
 
     /// SET 3,A
     fn op_cbdf(&mut self) -> u8 {
-        trace!("SET 3,A");
-
         let val = self.registers.a();
         let res = val | (1 << 3);
         self.registers.set_a(res);
@@ -6681,8 +5876,6 @@ impl Cpu {
 
     /// SET 4,B
     fn op_cbe0(&mut self) -> u8 {
-        trace!("SET 4,B");
-
         let val = self.registers.b();
         let res = val | (1 << 4);
         self.registers.set_b(res);
@@ -6692,8 +5885,6 @@ impl Cpu {
 
     /// SET 4,C
     fn op_cbe1(&mut self) -> u8 {
-        trace!("SET 4,C");
-
         let val = self.registers.c();
         let res = val | (1 << 4);
         self.registers.set_c(res);
@@ -6703,8 +5894,6 @@ impl Cpu {
 
     /// SET 4,D
     fn op_cbe2(&mut self) -> u8 {
-        trace!("SET 4,D");
-
         let val = self.registers.d();
         let res = val | (1 << 4);
         self.registers.set_d(res);
@@ -6714,8 +5903,6 @@ impl Cpu {
 
     /// SET 4,E
     fn op_cbe3(&mut self) -> u8 {
-        trace!("SET 4,E");
-
         let val = self.registers.e();
         let res = val | (1 << 4);
         self.registers.set_e(res);
@@ -6725,8 +5912,6 @@ impl Cpu {
 
     /// SET 4,H
     fn op_cbe4(&mut self) -> u8 {
-        trace!("SET 4,H");
-
         let val = self.registers.h();
         let res = val | (1 << 4);
         self.registers.set_h(res);
@@ -6736,8 +5921,6 @@ impl Cpu {
 
     /// SET 4,L
     fn op_cbe5(&mut self) -> u8 {
-        trace!("SET 4,L");
-
         let val = self.registers.l();
         let res = val | (1 << 4);
         self.registers.set_l(res);
@@ -6747,20 +5930,16 @@ impl Cpu {
 
     /// SET 4,(HL)
     fn op_cbe6(&mut self) -> u8 {
-        trace!("SET 4,(HL)");
-
         let hl = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(hl);
+        let val = self.read(hl);
         let res = val | (1 << 4);
-        self.mmu.borrow_mut().write_byte(hl, res);
+        self.write(hl, res);
 
         16
     }
 
     /// SET 4,A
     fn op_cbe7(&mut self) -> u8 {
-        trace!("SET 4,A");
-
         let val = self.registers.a();
         let res = val | (1 << 4);
         self.registers.set_a(res);
@@ -6770,8 +5949,6 @@ impl Cpu {
 
     /// SET 5,B
     fn op_cbe8(&mut self) -> u8 {
-        trace!("SET 5,B");
-
         let val = self.registers.b();
         let res = val | (1 << 5);
         self.registers.set_b(res);
@@ -6781,8 +5958,6 @@ impl Cpu {
 
     /// SET 5,C
     fn op_cbe9(&mut self) -> u8 {
-        trace!("SET 5,C");
-
         let val = self.registers.c();
         let res = val | (1 << 5);
         self.registers.set_c(res);
@@ -6792,8 +5967,6 @@ impl Cpu {
 
     /// SET 5,D
     fn op_cbea(&mut self) -> u8 {
-        trace!("SET 5,D");
-
         let val = self.registers.d();
         let res = val | (1 << 5);
         self.registers.set_d(res);
@@ -6803,8 +5976,6 @@ impl Cpu {
 
     /// SET 5,E
     fn op_cbeb(&mut self) -> u8 {
-        trace!("SET 5,E");
-
         let val = self.registers.e();
         let res = val | (1 << 5);
         self.registers.set_e(res);
@@ -6814,8 +5985,6 @@ impl Cpu {
 
     /// SET 5,H
     fn op_cbec(&mut self) -> u8 {
-        trace!("SET 5,H");
-
         let val = self.registers.h();
         let res = val | (1 << 5);
         self.registers.set_h(res);
@@ -6825,8 +5994,6 @@ impl Cpu {
 
     /// SET 5,L
     fn op_cbed(&mut self) -> u8 {
-        trace!("SET 5,L");
-
         let val = self.registers.l();
         let res = val | (1 << 5);
         self.registers.set_l(res);
@@ -6836,20 +6003,16 @@ impl Cpu {
 
     /// SET 5,(HL)
     fn op_cbee(&mut self) -> u8 {
-        trace!("SET 5,(HL)");
-
         let hl = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(hl);
+        let val = self.read(hl);
         let res = val | (1 << 5);
-        self.mmu.borrow_mut().write_byte(hl, res);
+        self.write(hl, res);
 
         16
     }
 
     /// SET 5,A
     fn op_cbef(&mut self) -> u8 {
-        trace!("SET 5,A");
-
         let val = self.registers.a();
         let res = val | (1 << 5);
         self.registers.set_a(res);
@@ -6859,8 +6022,6 @@ impl Cpu {
 
     /// SET 6,B
     fn op_cbf0(&mut self) -> u8 {
-        trace!("SET 6,B");
-
         let val = self.registers.b();
         let res = val | (1 << 6);
         self.registers.set_b(res);
@@ -6870,8 +6031,6 @@ impl Cpu {
 
     /// SET 6,C
     fn op_cbf1(&mut self) -> u8 {
-        trace!("SET 6,C");
-
         let val = self.registers.c();
         let res = val | (1 << 6);
         self.registers.set_c(res);
@@ -6881,8 +6040,6 @@ impl Cpu {
 
     /// SET 6,D
     fn op_cbf2(&mut self) -> u8 {
-        trace!("SET 6,D");
-
         let val = self.registers.d();
         let res = val | (1 << 6);
         self.registers.set_d(res);
@@ -6892,8 +6049,6 @@ impl Cpu {
 
     /// SET 6,E
     fn op_cbf3(&mut self) -> u8 {
-        trace!("SET 6,E");
-
         let val = self.registers.e();
         let res = val | (1 << 6);
         self.registers.set_e(res);
@@ -6903,8 +6058,6 @@ impl Cpu {
 
     /// SET 6,H
     fn op_cbf4(&mut self) -> u8 {
-        trace!("SET 6,H");
-
         let val = self.registers.h();
         let res = val | (1 << 6);
         self.registers.set_h(res);
@@ -6914,8 +6067,6 @@ impl Cpu {
 
     /// SET 6,L
     fn op_cbf5(&mut self) -> u8 {
-        trace!("SET 6,L");
-
         let val = self.registers.l();
         let res = val | (1 << 6);
         self.registers.set_l(res);
@@ -6925,20 +6076,16 @@ impl Cpu {
 
     /// SET 6,(HL)
     fn op_cbf6(&mut self) -> u8 {
-        trace!("SET 6,(HL)");
-
         let hl = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(hl);
+        let val = self.read(hl);
         let res = val | (1 << 6);
-        self.mmu.borrow_mut().write_byte(hl, res);
+        self.write(hl, res);
 
         16
     }
 
     /// SET 6,A
     fn op_cbf7(&mut self) -> u8 {
-        trace!("SET 6,A");
-
         let val = self.registers.a();
         let res = val | (1 << 6);
         self.registers.set_a(res);
@@ -6948,8 +6095,6 @@ impl Cpu {
 
     /// SET 7,B
     fn op_cbf8(&mut self) -> u8 {
-        trace!("SET 7,B");
-
         let val = self.registers.b();
         let res = val | (1 << 7);
         self.registers.set_b(res);
@@ -6959,8 +6104,6 @@ impl Cpu {
 
     /// SET 7,C
     fn op_cbf9(&mut self) -> u8 {
-        trace!("SET 7,C");
-
         let val = self.registers.c();
         let res = val | (1 << 7);
         self.registers.set_c(res);
@@ -6970,8 +6113,6 @@ impl Cpu {
 
     /// SET 7,D
     fn op_cbfa(&mut self) -> u8 {
-        trace!("SET 7,D");
-
         let val = self.registers.d();
         let res = val | (1 << 7);
         self.registers.set_d(res);
@@ -6981,8 +6122,6 @@ impl Cpu {
 
     /// SET 7,E
     fn op_cbfb(&mut self) -> u8 {
-        trace!("SET 7,E");
-
         let val = self.registers.e();
         let res = val | (1 << 7);
         self.registers.set_e(res);
@@ -6992,8 +6131,6 @@ impl Cpu {
 
     /// SET 7,H
     fn op_cbfc(&mut self) -> u8 {
-        trace!("SET 7,H");
-
         let val = self.registers.h();
         let res = val | (1 << 7);
         self.registers.set_h(res);
@@ -7003,8 +6140,6 @@ impl Cpu {
 
     /// SET 7,L
     fn op_cbfd(&mut self) -> u8 {
-        trace!("SET 7,L");
-
         let val = self.registers.l();
         let res = val | (1 << 7);
         self.registers.set_l(res);
@@ -7014,20 +6149,16 @@ impl Cpu {
 
     /// SET 7,(HL)
     fn op_cbfe(&mut self) -> u8 {
-        trace!("SET 7,(HL)");
-
         let hl = self.registers.hl();
-        let val = self.mmu.borrow().read_byte(hl);
+        let val = self.read(hl);
         let res = val | (1 << 7);
-        self.mmu.borrow_mut().write_byte(hl, res);
+        self.write(hl, res);
 
         16
     }
 
     /// SET 7,A
     fn op_cbff(&mut self) -> u8 {
-        trace!("SET 7,A");
-
         let val = self.registers.a();
         let res = val | (1 << 7);
         self.registers.set_a(res);
@@ -7036,39 +6167,139 @@ impl Cpu {
     }
 }
 
+impl<B: Bus + Snapshot> Snapshot for Cpu<B> {
+    fn snapshot(&mut self, state: &mut State) {
+        self.registers.snapshot(state);
+        state.bool(&mut self.ime);
+        state.bool(&mut self.ime_pending);
+        state.u64(&mut self.cycles);
+        self.bus.snapshot(state);
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use alloc::{vec, vec::Vec};
+    #[cfg(feature = "std")]
+    use std::{
+        format,
+        path::{Path, PathBuf},
+        string::String,
+    };
 
-    use std::cell::RefCell;
-    use std::rc::Rc;
+    #[cfg(feature = "std")]
+    use serde_json::Value;
 
-    use crate::cartridge::Cartridge;
-    use crate::irq::Irq;
-    use crate::joypad::JoyPad;
-    use crate::mmu::Mmu;
-    use crate::ppu::Ppu;
-    use crate::sound::Apu;
+    use crate::bus::Bus;
+    #[cfg(feature = "std")]
+    use crate::debugger::CpuRegisters;
 
     use super::Cpu;
 
+    // Opcodes which lock up the CPU and have no test vectors
+    const ILLEGAL_OPCODES: [u8; 11] = [
+        0xd3, 0xdb, 0xdd, 0xe3, 0xe4, 0xeb, 0xec, 0xed, 0xf4, 0xfc, 0xfd,
+    ];
+
+    /// 64 KB of plain RAM which records every access
+    struct FlatBus {
+        ram: Vec<u8>,
+        // address, value and whether it was a write
+        accesses: Vec<(u16, u8, bool)>,
+        // the M-cycle of every access, counting from 1
+        access_cycles: Vec<usize>,
+        ticks: usize,
+    }
+
+    impl FlatBus {
+        fn new() -> Self {
+            Self {
+                ram: vec![0; 0x10000],
+                accesses: Vec::new(),
+                access_cycles: Vec::new(),
+                ticks: 0,
+            }
+        }
+    }
+
+    impl Bus for FlatBus {
+        fn read(&mut self, addr: u16) -> u8 {
+            let value = self.ram[addr as usize];
+            self.accesses.push((addr, value, false));
+            self.access_cycles.push(self.ticks);
+            value
+        }
+
+        fn write(&mut self, addr: u16, value: u8) {
+            self.ram[addr as usize] = value;
+            self.accesses.push((addr, value, true));
+            self.access_cycles.push(self.ticks);
+        }
+
+        fn tick(&mut self) {
+            self.ticks += 1;
+        }
+
+        fn peek(&self, addr: u16) -> u8 {
+            self.ram[addr as usize]
+        }
+    }
+
+    // The disassembler has its own table of lengths and cycles, which has to agree with
+    // what the CPU does
+    #[test]
+    fn disassembly_matches_execution() {
+        let opcodes = (0..=0xffu16)
+            .filter(|&opcode| !ILLEGAL_OPCODES.contains(&(opcode as u8)) && opcode != 0xcb)
+            .map(|opcode| [opcode as u8, 0x12])
+            .chain((0..=0xffu16).map(|opcode| [0xcb, opcode as u8]));
+
+        for bytes in opcodes {
+            // the flags decide whether conditional instructions are taken
+            for flags in [0x0000, 0x00f0] {
+                let mut bus = FlatBus::new();
+                bus.ram[0x0200..0x0203].copy_from_slice(&[bytes[0], bytes[1], 0x34]);
+                let instruction = crate::disasm::disassemble(0x0200, |addr| bus.ram[addr as usize]);
+
+                let mut cpu = Cpu::new(bus);
+                cpu.registers.set_pc(0x0200);
+                cpu.registers.set_sp(0xd000);
+                cpu.registers.set_af(flags);
+                let cycles = cpu.step();
+                let pc = cpu.registers.pc();
+                // more memory accesses than M-cycles would run the bus ahead
+                assert_eq!(cpu.bus.ticks * 4, cycles as usize, "{}", instruction.text);
+
+                let taken = instruction.taken_cycles == Some(cycles);
+                assert!(
+                    cycles == instruction.cycles || taken,
+                    "{:02x?} {}: {} cycles",
+                    bytes,
+                    instruction.text,
+                    cycles
+                );
+                let jumps = taken || instruction.taken_cycles.is_none();
+                match instruction.target {
+                    Some(target) if jumps => assert_eq!(pc, target, "{}", instruction.text),
+                    // returns and JP HL go wherever the stack or HL points
+                    _ if jumps
+                        && matches!(bytes[0], 0xc0 | 0xc8 | 0xc9 | 0xd0 | 0xd8 | 0xd9 | 0xe9) => {}
+                    _ => assert_eq!(
+                        pc,
+                        0x0200 + instruction.length as u16,
+                        "{}",
+                        instruction.text
+                    ),
+                }
+            }
+        }
+    }
+
     #[test]
     fn push_pop() {
         let _ = env_logger::builder().is_test(true).try_init();
 
-        let mock_game = [0u8; 0];
-        let irq = Rc::new(RefCell::new(Irq::new()));
-        let ppu = Rc::new(RefCell::new(Ppu::new(Rc::clone(&irq))));
-        let joypad = Rc::new(RefCell::new(JoyPad::new(Rc::clone(&irq))));
-        let cartridge = Cartridge::no_boot(&mock_game);
-        let apu = Rc::new(RefCell::new(Apu::new()));
-        let mmu = Rc::new(RefCell::new(Mmu::new(
-            apu,
-            Rc::clone(&irq),
-            ppu,
-            joypad,
-            cartridge,
-        )));
-        let mut cpu = Cpu::new(irq, mmu);
+        let mut cpu = Cpu::new(FlatBus::new());
         cpu.registers.set_sp(0xfffe);
 
         let values = vec![0, 1, 2, 3, 4, 5];
@@ -7085,4 +6316,188 @@ mod tests {
 
         assert_eq!(values, popped_values);
     }
+
+    #[test]
+    fn access_cycles() {
+        // CALL $1234
+        let mut bus = FlatBus::new();
+        bus.ram[0x0100..0x0103].copy_from_slice(&[0xcd, 0x34, 0x12]);
+        let mut cpu = Cpu::new(bus);
+        cpu.registers.set_pc(0x0100);
+        cpu.registers.set_sp(0xfffe);
+
+        assert_eq!(cpu.step(), 24);
+        let bus = cpu.bus();
+        assert_eq!(bus.ticks, 6);
+        assert_eq!(
+            bus.accesses,
+            [
+                (0x0100, 0xcd, false),
+                (0x0101, 0x34, false),
+                (0x0102, 0x12, false),
+                (0xfffd, 0x01, true),
+                (0xfffc, 0x03, true)
+            ]
+        );
+        // the return address is pushed after an internal M-cycle
+        assert_eq!(bus.access_cycles, [1, 2, 3, 5, 6]);
+    }
+
+    #[cfg(feature = "std")]
+    fn sm83_test_directory() -> PathBuf {
+        match std::env::var_os("SM83_TESTS") {
+            Some(dir) => PathBuf::from(dir),
+            None => Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/sm83/smoke"),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn registers(state: &Value) -> CpuRegisters {
+        let byte = |name: &str| state[name].as_u64().unwrap() as u8;
+        let word = |name: &str| state[name].as_u64().unwrap() as u16;
+        CpuRegisters {
+            a: byte("a"),
+            f: byte("f"),
+            b: byte("b"),
+            c: byte("c"),
+            d: byte("d"),
+            e: byte("e"),
+            h: byte("h"),
+            l: byte("l"),
+            sp: word("sp"),
+            pc: word("pc"),
+            ime: state["ime"].as_u64() == Some(1),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn ram(state: &Value) -> Vec<(u16, u8)> {
+        state["ram"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| {
+                let addr = entry[0].as_u64().unwrap() as u16;
+                (addr, entry[1].as_u64().unwrap() as u8)
+            })
+            .collect()
+    }
+
+    // Runs a single test vector and describes the differences to the expected state
+    #[cfg(feature = "std")]
+    fn run_vector(vector: &Value) -> Option<String> {
+        let initial = &vector["initial"];
+        let expected = &vector["final"];
+
+        let mut bus = FlatBus::new();
+        for (addr, value) in ram(initial) {
+            bus.ram[addr as usize] = value;
+        }
+        let mut cpu = Cpu::new(bus);
+        cpu.set_registers(registers(initial));
+
+        let cycles = cpu.step() as usize;
+
+        let mut errors = Vec::new();
+        let actual_registers = cpu.registers();
+        let expected_registers = registers(expected);
+        if actual_registers != expected_registers {
+            errors.push(format!(
+                "registers {:x?}, expected {:x?}",
+                actual_registers, expected_registers
+            ));
+        }
+        // set after EI, which enables interrupts only after the next instruction
+        let ime_pending = expected["ei"].as_u64() == Some(1);
+        if cpu.ime_pending != ime_pending {
+            errors.push(format!(
+                "EI pending is {}, expected {}",
+                cpu.ime_pending, ime_pending
+            ));
+        }
+
+        let bus = cpu.bus();
+        for (addr, value) in ram(expected) {
+            if bus.ram[addr as usize] != value {
+                let actual = bus.ram[addr as usize];
+                errors.push(format!(
+                    "{:04x} is {:02x}, expected {:02x}",
+                    addr, actual, value
+                ));
+            }
+        }
+
+        let expected_cycles = vector["cycles"].as_array().unwrap();
+        if cycles != expected_cycles.len() * 4 {
+            let expected = expected_cycles.len() * 4;
+            errors.push(format!("took {} cycles, expected {}", cycles, expected));
+        }
+
+        // every access with the M-cycle it happens in, which is its index in the cycles
+        // counting from 1. Internal M-cycles are null or neither read nor write.
+        let mut expected_accesses = Vec::new();
+        for (index, cycle) in expected_cycles.iter().enumerate() {
+            let kind = cycle[2].as_str().unwrap_or("---");
+            if kind.contains('r') || kind.contains('w') {
+                let addr = cycle[0].as_u64().unwrap() as u16;
+                let value = cycle[1].as_u64().unwrap() as u8;
+                expected_accesses.push((addr, value, kind.contains('w'), index + 1));
+            }
+        }
+        let accesses: Vec<(u16, u8, bool, usize)> = bus
+            .accesses
+            .iter()
+            .zip(&bus.access_cycles)
+            .map(|(&(addr, value, write), &cycle)| (addr, value, write, cycle))
+            .collect();
+        if accesses != expected_accesses {
+            errors.push(format!(
+                "accesses {:x?}, expected {:x?}",
+                accesses, expected_accesses
+            ));
+        }
+
+        if errors.is_empty() {
+            None
+        } else {
+            Some(errors.join("; "))
+        }
+    }
+
+    /// Runs the SM83 single step tests from `$SM83_TESTS`. Without it, only the hand-made
+    /// smoke vectors in `tests/sm83/smoke` run, which cover a few opcodes of every group.
+    /// Full coverage needs the upstream vectors, see `tests/sm83/README.md`.
+    #[cfg(feature = "std")]
+    #[test]
+    fn sm83_single_step() {
+        let directory = sm83_test_directory();
+        let mut files: Vec<PathBuf> = match std::fs::read_dir(&directory) {
+            Ok(entries) => entries.map(|e| e.unwrap().path()).collect(),
+            Err(e) => panic!("no SM83 tests at {}: {}", directory.display(), e),
+        };
+        files.sort();
+
+        let mut failures = Vec::new();
+        for file in files {
+            // files are named after the opcode, such as "d3" or "cb 06"
+            let stem = file.file_stem().unwrap().to_string_lossy();
+            let illegal =
+                u8::from_str_radix(&stem, 16).is_ok_and(|opcode| ILLEGAL_OPCODES.contains(&opcode));
+            if file.extension().and_then(|e| e.to_str()) != Some("json") || illegal {
+                continue;
+            }
+            let vectors: Value = serde_json::from_slice(&std::fs::read(&file).unwrap()).unwrap();
+            // the first failing vector of an opcode is enough to debug it
+            let failure = vectors.as_array().unwrap().iter().find_map(|vector| {
+                let error = run_vector(vector)?;
+                Some(format!("{}: {}", vector["name"].as_str().unwrap(), error))
+            });
+            failures.extend(failure);
+        }
+
+        for failure in &failures {
+            println!("{}", failure);
+        }
+        assert!(failures.is_empty(), "{} opcodes failed", failures.len());
+    }
 }
@@ -0,0 +1,268 @@
+use crate::registers::Registers;
+
+/// Kind of memory access a watchpoint reacts to
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    /// Fetching the first byte of an instruction
+    Execute,
+}
+
+/// Stops execution on accesses to the addresses `start..=end`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub start: u16,
+    pub end: u16,
+    pub access: Access,
+}
+
+impl Watchpoint {
+    pub(crate) fn matches(&self, addr: u16, access: Access) -> bool {
+        self.access == access && (self.start..=self.end).contains(&addr)
+    }
+}
+
+/// CPU register which a breakpoint condition compares
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Register {
+    A,
+    F,
+    B,
+    C,
+    D,
+    E,
+    H,
+    L,
+    AF,
+    BC,
+    DE,
+    HL,
+    SP,
+    PC,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    Greater,
+}
+
+/// Compares a register against a value
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Condition {
+    pub register: Register,
+    pub comparison: Comparison,
+    pub value: u16,
+}
+
+impl Condition {
+    fn holds(&self, registers: &CpuRegisters) -> bool {
+        let value = registers.get(self.register);
+        match self.comparison {
+            Comparison::Equal => value == self.value,
+            Comparison::NotEqual => value != self.value,
+            Comparison::Less => value < self.value,
+            Comparison::Greater => value > self.value,
+        }
+    }
+}
+
+/// Stops execution before the instruction at `address`, if the condition holds
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub address: u16,
+    pub condition: Option<Condition>,
+}
+
+impl Breakpoint {
+    pub fn new(address: u16) -> Self {
+        Self {
+            address,
+            condition: None,
+        }
+    }
+
+    pub fn with_condition(address: u16, condition: Condition) -> Self {
+        Self {
+            address,
+            condition: Some(condition),
+        }
+    }
+
+    pub(crate) fn hit(&self, registers: &CpuRegisters) -> bool {
+        if self.address != registers.pc {
+            return false;
+        }
+        match self.condition {
+            Some(condition) => condition.holds(registers),
+            None => true,
+        }
+    }
+}
+
+/// Why the debugger handed control back
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    /// A single instruction was executed
+    Step,
+    /// The next instruction is at a breakpoint
+    Breakpoint(u16),
+    /// The last instruction accessed a watched address, or the next one is at a watched
+    /// address for `Access::Execute`
+    Watchpoint {
+        address: u16,
+        access: Access,
+        value: u8,
+    },
+    /// An interrupt was dispatched, the next instruction is the first of its handler
+    Interrupt(u8),
+    /// The next instruction is `LD B,B`, which test roms use as breakpoint
+    SoftwareBreakpoint(u16),
+    /// The cycle budget is used up
+    CycleLimit,
+}
+
+/// Copy of the CPU registers
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CpuRegisters {
+    pub a: u8,
+    pub f: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+    pub sp: u16,
+    pub pc: u16,
+    /// Interrupt master enable flag
+    pub ime: bool,
+}
+
+impl CpuRegisters {
+    pub(crate) fn new(registers: &Registers, ime: bool) -> Self {
+        let [a, f] = registers.af().to_be_bytes();
+        Self {
+            a,
+            f,
+            b: registers.b(),
+            c: registers.c(),
+            d: registers.d(),
+            e: registers.e(),
+            h: registers.h(),
+            l: registers.l(),
+            sp: registers.sp(),
+            pc: registers.pc(),
+            ime,
+        }
+    }
+
+    pub fn get(&self, register: Register) -> u16 {
+        let pair = |high: u8, low: u8| u16::from_be_bytes([high, low]);
+        match register {
+            Register::A => self.a as u16,
+            Register::F => self.f as u16,
+            Register::B => self.b as u16,
+            Register::C => self.c as u16,
+            Register::D => self.d as u16,
+            Register::E => self.e as u16,
+            Register::H => self.h as u16,
+            Register::L => self.l as u16,
+            Register::AF => pair(self.a, self.f),
+            Register::BC => pair(self.b, self.c),
+            Register::DE => pair(self.d, self.e),
+            Register::HL => pair(self.h, self.l),
+            Register::SP => self.sp,
+            Register::PC => self.pc,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, vec::Vec};
+
+    use super::{Access, Breakpoint, Comparison, Condition, Register, StopReason, Watchpoint};
+    use crate::board::{Board, Config};
+
+    // counts B up and stores it to 0xc000 forever
+    fn counter_game() -> Vec<u8> {
+        let mut game = vec![0u8; 0x8000];
+        game[0x0100..0x0108].copy_from_slice(&[
+            0x04, // INC B
+            0x78, // LD A,B
+            0xea, 0x00, 0xc0, // LD (0xc000),A
+            0xc3, 0x00, 0x01, // JP 0x0100
+        ]);
+        game
+    }
+
+    #[test]
+    fn breakpoints() {
+        let mut board = Board::no_boot(&counter_game(), Config::default());
+        assert_eq!(board.step_instruction(), StopReason::Step);
+        assert_eq!(board.registers().pc, 0x0101);
+
+        board.add_breakpoint(Breakpoint::with_condition(
+            0x0105,
+            Condition {
+                register: Register::B,
+                comparison: Comparison::Equal,
+                value: 3,
+            },
+        ));
+        assert_eq!(
+            board.run_until_break(10_000),
+            StopReason::Breakpoint(0x0105)
+        );
+        assert_eq!(board.registers().b, 3);
+
+        board.clear_breakpoints();
+        assert_eq!(board.run_until_break(100), StopReason::CycleLimit);
+    }
+
+    #[test]
+    fn watchpoints() {
+        let mut board = Board::no_boot(&counter_game(), Config::default());
+        board.add_watchpoint(Watchpoint {
+            start: 0xc000,
+            end: 0xc0ff,
+            access: Access::Write,
+        });
+        let b = board.registers().b;
+        assert_eq!(
+            board.run_until_break(10_000),
+            StopReason::Watchpoint {
+                address: 0xc000,
+                access: Access::Write,
+                value: b.wrapping_add(1),
+            }
+        );
+        assert_eq!(board.registers().pc, 0x0105);
+        assert_eq!(board.peek(0xc000), b.wrapping_add(1));
+    }
+
+    #[test]
+    fn watchpoints_ignore_fetches_and_stale_hits() {
+        let mut board = Board::no_boot(&counter_game(), Config::default());
+        board.add_watchpoint(Watchpoint {
+            start: 0x0100,
+            end: 0x0107,
+            access: Access::Read,
+        });
+        assert_eq!(board.run_until_break(1000), StopReason::CycleLimit);
+
+        // a hit while running without the debugger isn't reported later
+        board.add_watchpoint(Watchpoint {
+            start: 0xc000,
+            end: 0xc000,
+            access: Access::Write,
+        });
+        board.run_to_next_frame();
+        board.clear_watchpoints();
+        assert_eq!(board.step_instruction(), StopReason::Step);
+    }
+}
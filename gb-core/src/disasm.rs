@@ -0,0 +1,290 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+};
+use core::fmt;
+use core::str::FromStr;
+
+/// An instruction decoded from memory
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Instruction {
+    /// Mnemonic with operands, e.g. `LD A,(HL+)` or `JR NZ,$-5`
+    pub text: String,
+    /// Size in bytes, including the prefix and operands
+    pub length: u8,
+    /// Clock cycles, for conditional instructions if the condition doesn't hold
+    pub cycles: u8,
+    /// Clock cycles of conditional instructions if the condition holds
+    pub taken_cycles: Option<u8>,
+    /// Destination of jumps, calls and restarts
+    pub target: Option<u16>,
+}
+
+/// Address together with the bank mapped at it, written as `BB:AAAA` like in RGBDS
+/// symbol files
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BankAddress {
+    pub bank: u16,
+    pub address: u16,
+}
+
+impl fmt::Display for BankAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:02x}:{:04x}", self.bank, self.address)
+    }
+}
+
+impl FromStr for BankAddress {
+    type Err = String;
+
+    /// Parses `BB:AAAA`, or `AAAA` for bank 0, in hex
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |hex: &str| {
+            u16::from_str_radix(hex.trim_start_matches('$'), 16)
+                .map_err(|_| format!("Invalid address {}", s))
+        };
+        match s.split_once(':') {
+            Some((bank, address)) => Ok(Self {
+                bank: parse(bank)?,
+                address: parse(address)?,
+            }),
+            None => Ok(Self {
+                bank: 0,
+                address: parse(s)?,
+            }),
+        }
+    }
+}
+
+const REGISTERS: [&str; 8] = ["B", "C", "D", "E", "H", "L", "(HL)", "A"];
+const REGISTER_PAIRS: [&str; 4] = ["BC", "DE", "HL", "SP"];
+const STACK_PAIRS: [&str; 4] = ["BC", "DE", "HL", "AF"];
+const CONDITIONS: [&str; 4] = ["NZ", "Z", "NC", "C"];
+const ALU: [&str; 8] = [
+    "ADD A,", "ADC A,", "SUB ", "SBC A,", "AND ", "XOR ", "OR ", "CP ",
+];
+const ROTATIONS: [&str; 8] = ["RLC", "RRC", "RL", "RR", "SLA", "SRA", "SWAP", "SRL"];
+const ACCUMULATOR_OPS: [&str; 8] = ["RLCA", "RRCA", "RLA", "RRA", "DAA", "CPL", "SCF", "CCF"];
+
+/// Decodes the instruction at `address`, reading its bytes through `read`
+pub fn disassemble(address: u16, read: impl Fn(u16) -> u8) -> Instruction {
+    let opcode = read(address);
+    let d8 = read(address.wrapping_add(1));
+    let d16 = u16::from_le_bytes([d8, read(address.wrapping_add(2))]);
+    let e = d8 as i8;
+
+    // the opcode is split into the fields xxyyyzzz, and y into ppq
+    let x = opcode >> 6;
+    let y = ((opcode >> 3) & 7) as usize;
+    let z = (opcode & 7) as usize;
+    let p = y >> 1;
+    let q = y & 1;
+
+    let mut instruction = Instruction {
+        text: String::new(),
+        length: 1,
+        cycles: 4,
+        taken_cycles: None,
+        target: None,
+    };
+    let mut set = |text: String, length: u8, cycles: u8| {
+        instruction.text = text;
+        instruction.length = length;
+        instruction.cycles = cycles;
+    };
+    // 8 bit operands cost an additional memory access if they are (HL)
+    let register_cycles = |r: usize, cycles: u8| if r == 6 { cycles + 4 } else { cycles };
+
+    match (x, z) {
+        (0, 0) => match y {
+            0 => set("NOP".to_string(), 1, 4),
+            1 => set(format!("LD (${:04x}),SP", d16), 3, 20),
+            2 => set("STOP 0".to_string(), 2, 4),
+            3 => set(format!("JR {}", relative(e)), 2, 12),
+            _ => set(format!("JR {},{}", CONDITIONS[y - 4], relative(e)), 2, 8),
+        },
+        (0, 1) if q == 0 => set(format!("LD {},${:04x}", REGISTER_PAIRS[p], d16), 3, 12),
+        (0, 1) => set(format!("ADD HL,{}", REGISTER_PAIRS[p]), 1, 8),
+        (0, 2) => {
+            let pointer = ["(BC)", "(DE)", "(HL+)", "(HL-)"][p];
+            let text = if q == 0 {
+                format!("LD {},A", pointer)
+            } else {
+                format!("LD A,{}", pointer)
+            };
+            set(text, 1, 8)
+        }
+        (0, 3) => {
+            let op = if q == 0 { "INC" } else { "DEC" };
+            set(format!("{} {}", op, REGISTER_PAIRS[p]), 1, 8)
+        }
+        (0, 4) | (0, 5) => {
+            let op = if z == 4 { "INC" } else { "DEC" };
+            // (HL) is read and written back
+            let cycles = if y == 6 { 12 } else { 4 };
+            set(format!("{} {}", op, REGISTERS[y]), 1, cycles)
+        }
+        (0, 6) => set(
+            format!("LD {},${:02x}", REGISTERS[y], d8),
+            2,
+            register_cycles(y, 8),
+        ),
+        (0, 7) => set(ACCUMULATOR_OPS[y].to_string(), 1, 4),
+        (1, 6) if y == 6 => set("HALT".to_string(), 1, 4),
+        (1, _) => {
+            let text = format!("LD {},{}", REGISTERS[y], REGISTERS[z]);
+            set(text, 1, register_cycles(y, register_cycles(z, 4)))
+        }
+        (2, _) => set(
+            format!("{}{}", ALU[y], REGISTERS[z]),
+            1,
+            register_cycles(z, 4),
+        ),
+        (3, 0) => match y {
+            0..=3 => set(format!("RET {}", CONDITIONS[y]), 1, 8),
+            4 => set(format!("LDH (${:02x}),A", d8), 2, 12),
+            5 => set(format!("ADD SP,{}", e), 2, 16),
+            6 => set(format!("LDH A,(${:02x})", d8), 2, 12),
+            _ => set(format!("LD HL,SP{:+}", e), 2, 12),
+        },
+        (3, 1) if q == 0 => set(format!("POP {}", STACK_PAIRS[p]), 1, 12),
+        (3, 1) => match p {
+            0 => set("RET".to_string(), 1, 16),
+            1 => set("RETI".to_string(), 1, 16),
+            2 => set("JP (HL)".to_string(), 1, 4),
+            _ => set("LD SP,HL".to_string(), 1, 8),
+        },
+        (3, 2) => match y {
+            0..=3 => set(format!("JP {},${:04x}", CONDITIONS[y], d16), 3, 12),
+            4 => set("LD (C),A".to_string(), 1, 8),
+            5 => set(format!("LD (${:04x}),A", d16), 3, 16),
+            6 => set("LD A,(C)".to_string(), 1, 8),
+            _ => set(format!("LD A,(${:04x})", d16), 3, 16),
+        },
+        (3, 3) => match y {
+            0 => set(format!("JP ${:04x}", d16), 3, 16),
+            1 => {
+                let (text, cycles) = disassemble_cb(d8);
+                set(text, 2, cycles)
+            }
+            6 => set("DI".to_string(), 1, 4),
+            7 => set("EI".to_string(), 1, 4),
+            _ => set(format!("DB ${:02x}", opcode), 1, 4),
+        },
+        (3, 4) if y < 4 => set(format!("CALL {},${:04x}", CONDITIONS[y], d16), 3, 12),
+        (3, 5) if q == 0 => set(format!("PUSH {}", STACK_PAIRS[p]), 1, 16),
+        (3, 5) if p == 0 => set(format!("CALL ${:04x}", d16), 3, 24),
+        (3, 6) => set(format!("{}${:02x}", ALU[y], d8), 2, 8),
+        (3, 7) => set(format!("RST {:02X}H", y * 8), 1, 16),
+        // opcodes which lock up the CPU
+        _ => set(format!("DB ${:02x}", opcode), 1, 4),
+    }
+
+    let next = address.wrapping_add(instruction.length as u16);
+    match opcode {
+        0x18 | 0x20 | 0x28 | 0x30 | 0x38 => {
+            instruction.target = Some(next.wrapping_add(e as u16));
+        }
+        0xc2 | 0xc3 | 0xca | 0xd2 | 0xda | 0xc4 | 0xcc | 0xcd | 0xd4 | 0xdc => {
+            instruction.target = Some(d16);
+        }
+        0xc7 | 0xcf | 0xd7 | 0xdf | 0xe7 | 0xef | 0xf7 | 0xff => {
+            instruction.target = Some(y as u16 * 8);
+        }
+        _ => (),
+    }
+    instruction.taken_cycles = match opcode {
+        0x20 | 0x28 | 0x30 | 0x38 => Some(12),
+        0xc0 | 0xc8 | 0xd0 | 0xd8 => Some(20),
+        0xc2 | 0xca | 0xd2 | 0xda => Some(16),
+        0xc4 | 0xcc | 0xd4 | 0xdc => Some(24),
+        _ => None,
+    };
+    instruction
+}
+
+// Decodes the opcode following the CB prefix into its text and cycles
+fn disassemble_cb(opcode: u8) -> (String, u8) {
+    let x = opcode >> 6;
+    let y = ((opcode >> 3) & 7) as usize;
+    let z = (opcode & 7) as usize;
+    let register = REGISTERS[z];
+    let text = match x {
+        0 => format!("{} {}", ROTATIONS[y], register),
+        1 => format!("BIT {},{}", y, register),
+        2 => format!("RES {},{}", y, register),
+        _ => format!("SET {},{}", y, register),
+    };
+    let cycles = match (x, z) {
+        (1, 6) => 12,
+        (_, 6) => 16,
+        _ => 8,
+    };
+    (text, cycles)
+}
+
+// Relative jumps are written relative to the start of the instruction, as in RGBDS
+fn relative(offset: i8) -> String {
+    let distance = offset as i16 + 2;
+    if distance < 0 {
+        format!("$-{}", -distance)
+    } else {
+        format!("$+{}", distance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::{String, ToString};
+
+    use super::{disassemble, BankAddress};
+
+    fn decode(bytes: &[u8]) -> (String, u8, u8) {
+        let instruction = disassemble(0x0150, |addr| {
+            bytes.get(addr as usize - 0x0150).copied().unwrap_or(0)
+        });
+        (instruction.text, instruction.length, instruction.cycles)
+    }
+
+    #[test]
+    fn instructions() {
+        assert_eq!(decode(&[0x2a]), ("LD A,(HL+)".to_string(), 1, 8));
+        assert_eq!(decode(&[0x20, 0xf9]), ("JR NZ,$-5".to_string(), 2, 8));
+        assert_eq!(decode(&[0x36, 0x12]), ("LD (HL),$12".to_string(), 2, 12));
+        assert_eq!(decode(&[0x34]), ("INC (HL)".to_string(), 1, 12));
+        assert_eq!(
+            decode(&[0xcd, 0x34, 0x12]),
+            ("CALL $1234".to_string(), 3, 24)
+        );
+        assert_eq!(decode(&[0xe0, 0x40]), ("LDH ($40),A".to_string(), 2, 12));
+        assert_eq!(decode(&[0xf8, 0xfe]), ("LD HL,SP-2".to_string(), 2, 12));
+        assert_eq!(decode(&[0xcb, 0x7e]), ("BIT 7,(HL)".to_string(), 2, 12));
+        assert_eq!(decode(&[0xcb, 0x37]), ("SWAP A".to_string(), 2, 8));
+        assert_eq!(decode(&[0xff]), ("RST 38H".to_string(), 1, 16));
+        assert_eq!(decode(&[0xd3]), ("DB $d3".to_string(), 1, 4));
+
+        let jump = disassemble(0x0150, |addr| [0x20, 0xf9, 0x00][addr as usize - 0x0150]);
+        assert_eq!(jump.target, Some(0x014b));
+        assert_eq!(jump.taken_cycles, Some(12));
+    }
+
+    #[test]
+    fn bank_addresses() {
+        let address: BankAddress = "01:4000".parse().unwrap();
+        assert_eq!(
+            address,
+            BankAddress {
+                bank: 1,
+                address: 0x4000
+            }
+        );
+        assert_eq!(address.to_string(), "01:4000");
+        assert_eq!(
+            "$0150".parse(),
+            Ok(BankAddress {
+                bank: 0,
+                address: 0x0150
+            })
+        );
+    }
+}
@@ -0,0 +1,319 @@
+use alloc::{string::String, vec, vec::Vec};
+use core::fmt;
+
+use crate::board::{Board, Config};
+
+const HEADER_SIZE: usize = 0x70;
+
+// Address at which execution of the player starts, as in a regular cartridge
+const DRIVER_ADDR: usize = 0x100;
+
+/// Error while parsing a GBS file
+#[derive(Debug, PartialEq, Eq)]
+pub enum GbsError {
+    /// The file doesn't start with a GBS header
+    InvalidHeader,
+    /// Only version 1 of the format exists
+    UnsupportedVersion(u8),
+    /// The data has to be loaded between 0x400 and 0x7fff
+    InvalidLoadAddress(u16),
+    /// The first song, counted from 1, is not one of the songs of the file
+    InvalidFirstSong { first: u8, count: u8 },
+}
+
+impl fmt::Display for GbsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GbsError::InvalidHeader => write!(f, "not a GBS file"),
+            GbsError::UnsupportedVersion(version) => {
+                write!(f, "unsupported GBS version {}", version)
+            }
+            GbsError::InvalidLoadAddress(addr) => write!(f, "invalid load address {:04x}", addr),
+            GbsError::InvalidFirstSong { first, count } => {
+                write!(f, "first song {} of only {} songs", first, count)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for GbsError {}
+
+/// Game Boy Sound file, a rip of the music code and data of a game
+pub struct Gbs {
+    song_count: u8,
+    first_song: u8,
+    load_addr: u16,
+    init_addr: u16,
+    play_addr: u16,
+    stack_pointer: u16,
+    timer_modulo: u8,
+    timer_control: u8,
+    title: String,
+    author: String,
+    copyright: String,
+    data: Vec<u8>,
+}
+
+impl Gbs {
+    pub fn parse(data: &[u8]) -> Result<Self, GbsError> {
+        if data.len() < HEADER_SIZE || &data[0..3] != b"GBS" {
+            return Err(GbsError::InvalidHeader);
+        }
+        if data[3] != 1 {
+            return Err(GbsError::UnsupportedVersion(data[3]));
+        }
+
+        let word = |offset: usize| u16::from_le_bytes([data[offset], data[offset + 1]]);
+        let load_addr = word(0x06);
+        if !(0x400..0x8000).contains(&load_addr) {
+            return Err(GbsError::InvalidLoadAddress(load_addr));
+        }
+
+        let (count, first) = (data[0x04], data[0x05]);
+        // some rips count the first song from 0
+        if count == 0 || first > count {
+            return Err(GbsError::InvalidFirstSong { first, count });
+        }
+
+        Ok(Self {
+            song_count: count,
+            first_song: first.max(1) - 1,
+            load_addr,
+            init_addr: word(0x08),
+            play_addr: word(0x0a),
+            stack_pointer: word(0x0c),
+            timer_modulo: data[0x0e],
+            timer_control: data[0x0f],
+            title: header_string(&data[0x10..0x30]),
+            author: header_string(&data[0x30..0x50]),
+            copyright: header_string(&data[0x50..0x70]),
+            data: data[HEADER_SIZE..].to_vec(),
+        })
+    }
+
+    pub fn song_count(&self) -> u8 {
+        self.song_count
+    }
+
+    /// The song to start with, counted from 0
+    pub fn first_song(&self) -> u8 {
+        self.first_song
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn author(&self) -> &str {
+        &self.author
+    }
+
+    pub fn copyright(&self) -> &str {
+        &self.copyright
+    }
+
+    /// Whether the play routine is called by the timer instead of on every VBlank
+    pub(crate) fn uses_timer(&self) -> bool {
+        (self.timer_control & (1 << 2)) != 0
+    }
+
+    pub(crate) fn timer_modulo(&self) -> u8 {
+        self.timer_modulo
+    }
+
+    pub(crate) fn timer_control(&self) -> u8 {
+        self.timer_control
+    }
+
+    /// Builds a cartridge image with the data at its load address and a small driver,
+    /// which initializes the given song and then calls the play routine on every
+    /// VBlank or timer interrupt.
+    pub(crate) fn rom(&self, song: u8) -> Vec<u8> {
+        let load_addr = self.load_addr as usize;
+        let mut rom = vec![0u8; load_addr];
+        rom.extend_from_slice(&self.data);
+
+        // RST vectors are relocated to the load address
+        for rst in (0x00..0x40).step_by(8) {
+            let [low, high] = (self.load_addr + rst as u16).to_le_bytes();
+            rom[rst..rst + 3].copy_from_slice(&[0xc3, low, high]); // JP a16
+        }
+
+        // VBlank and timer interrupt handlers
+        let [play_low, play_high] = self.play_addr.to_le_bytes();
+        #[rustfmt::skip]
+        let handler = [
+            0xcd, play_low, play_high, // CALL play
+            0xd9,                      // RETI
+        ];
+        for vector in [0x40, 0x50] {
+            rom[vector..vector + handler.len()].copy_from_slice(&handler);
+        }
+
+        let [sp_low, sp_high] = self.stack_pointer.to_le_bytes();
+        let [init_low, init_high] = self.init_addr.to_le_bytes();
+        #[rustfmt::skip]
+        let driver = [
+            0x31, sp_low, sp_high,     // LD SP,d16
+            0x3e, song,                // LD A,d8
+            0xcd, init_low, init_high, // CALL init
+            0xfb,                      // EI
+            0x76,                      // HALT
+            0x18, 0xfd,                // JR HALT
+        ];
+        rom[DRIVER_ADDR..DRIVER_ADDR + driver.len()].copy_from_slice(&driver);
+
+        rom
+    }
+}
+
+fn header_string(data: &[u8]) -> String {
+    let end = data.iter().position(|&c| c == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).into_owned()
+}
+
+/// Plays the songs of a GBS file on a board without a game
+pub struct GbsPlayer {
+    gbs: Gbs,
+    config: Config,
+    song: u8,
+    board: Board,
+}
+
+impl GbsPlayer {
+    pub fn new(data: &[u8], config: Config) -> Result<Self, GbsError> {
+        let gbs = Gbs::parse(data)?;
+        let song = gbs.first_song();
+        let board = Board::gbs(&gbs, song, config);
+        Ok(Self {
+            gbs,
+            config,
+            song,
+            board,
+        })
+    }
+
+    pub fn gbs(&self) -> &Gbs {
+        &self.gbs
+    }
+
+    /// The song which is currently playing, counted from 0
+    pub fn song(&self) -> u8 {
+        self.song
+    }
+
+    /// Restarts the board with the given song. Muted and soloed channels, the audio rate
+    /// adjustment and channel taps carry over. A running VGM log is restarted for the new
+    /// song, and the finished log of the previous song is returned.
+    pub fn play_song(&mut self, song: u8) -> Option<Vec<u8>> {
+        self.song = song % self.gbs.song_count();
+        let mut board = Board::gbs(&self.gbs, self.song, self.config);
+        board.copy_audio_settings(&self.board);
+
+        let vgm = self.board.stop_vgm_log();
+        if vgm.is_some() {
+            board.start_vgm_log();
+        }
+        self.board = board;
+        vgm
+    }
+
+    pub fn next_song(&mut self) -> Option<Vec<u8>> {
+        self.play_song(self.song.wrapping_add(1))
+    }
+
+    pub fn previous_song(&mut self) -> Option<Vec<u8>> {
+        let song = match self.song {
+            0 => self.gbs.song_count() - 1,
+            song => song - 1,
+        };
+        self.play_song(song)
+    }
+
+    pub fn board(&mut self) -> &mut Board {
+        &mut self.board
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, vec::Vec};
+
+    use super::{Gbs, GbsError, GbsPlayer};
+    use crate::board::{Board, Config};
+    use crate::sound::Channel;
+
+    fn header(load_addr: u16) -> Vec<u8> {
+        let mut data = vec![0u8; 0x70];
+        data[0..4].copy_from_slice(b"GBS\x01");
+        data[0x04] = 3;
+        data[0x05] = 2;
+        data[0x06..0x08].copy_from_slice(&load_addr.to_le_bytes());
+        data[0x0a..0x0c].copy_from_slice(&0x0408u16.to_le_bytes());
+        data[0x10..0x15].copy_from_slice(b"Title");
+        data.extend_from_slice(&[0xaa, 0xbb]);
+        data
+    }
+
+    #[test]
+    fn parse_and_build_rom() {
+        let gbs = Gbs::parse(&header(0x0400)).unwrap();
+        assert_eq!(gbs.song_count(), 3);
+        assert_eq!(gbs.first_song(), 1);
+        assert_eq!(gbs.title(), "Title");
+
+        let rom = gbs.rom(1);
+        assert_eq!(rom[0x400..], [0xaa, 0xbb]);
+        assert_eq!(rom[0x08..0x0b], [0xc3, 0x08, 0x04]);
+        assert_eq!(rom[0x40..0x44], [0xcd, 0x08, 0x04, 0xd9]);
+        assert_eq!(rom[0x104], 1);
+    }
+
+    #[test]
+    fn reject_invalid_files() {
+        assert_eq!(Gbs::parse(b"GBS").err(), Some(GbsError::InvalidHeader));
+        assert_eq!(
+            Gbs::parse(&header(0x0100)).err(),
+            Some(GbsError::InvalidLoadAddress(0x0100))
+        );
+        let mut data = header(0x0400);
+        data[0x05] = 4;
+        assert_eq!(
+            Gbs::parse(&data).err(),
+            Some(GbsError::InvalidFirstSong { first: 4, count: 3 })
+        );
+    }
+
+    // samples of ten frames
+    fn audio_len(board: &mut Board) -> usize {
+        board.audio();
+        for _ in 0..10 {
+            board.run_to_next_frame();
+        }
+        board.audio().len()
+    }
+
+    #[test]
+    fn song_change_keeps_settings() {
+        // init and play return right away
+        let mut data = header(0x0400);
+        data[0x08..0x0c].copy_from_slice(&[0x00, 0x04, 0x00, 0x04]);
+        data[0x0c..0x0e].copy_from_slice(&0xdffeu16.to_le_bytes());
+        data[0x70] = 0xc9; // RET
+        let mut player = GbsPlayer::new(&data, Config::default()).unwrap();
+        let nominal = audio_len(player.board());
+        player.board().adjust_audio_rate(2.0);
+        player.board().set_channel_taps(true);
+        player.board().start_vgm_log();
+
+        assert!(player.next_song().is_some());
+        assert_eq!(player.song(), 2);
+        let board = player.board();
+        assert!(audio_len(board) > nominal);
+        assert!(!board.channel_audio(Channel::Wave).is_empty());
+        // the new song is logged on its own
+        assert!(player.previous_song().is_some());
+        assert!(player.board().stop_vgm_log().is_some());
+    }
+}
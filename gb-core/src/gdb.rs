@@ -0,0 +1,536 @@
+use std::io::{self, ErrorKind, Read, Write};
+
+use log::{debug, warn};
+
+use crate::board::Board;
+use crate::debugger::{Access, Breakpoint, CpuRegisters, StopReason, Watchpoint};
+
+// Cycles to run per poll while the target is running, one frame
+const POLL_CYCLES: usize = 70224;
+
+// Largest packet we accept and send, as told to gdb
+const PACKET_SIZE: usize = 0x1000;
+
+// GDB numbers the registers in this order, each 16 bits wide
+const REGISTER_COUNT: usize = 6;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <architecture>sm83</architecture>
+  <feature name="org.gnu.gdb.sm83.cpu">
+    <reg name="af" bitsize="16" type="int"/>
+    <reg name="bc" bitsize="16" type="int"/>
+    <reg name="de" bitsize="16" type="int"/>
+    <reg name="hl" bitsize="16" type="data_ptr"/>
+    <reg name="sp" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+  </feature>
+</target>
+"#;
+
+// Signals of the stop replies
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+
+/// Server for the GDB remote serial protocol, which lets gdb control a board over a
+/// non-blocking stream, such as a local TCP connection
+pub struct GdbStub<S> {
+    stream: S,
+    // received bytes which don't form a complete packet yet
+    input: Vec<u8>,
+    running: bool,
+    connected: bool,
+    // ranges of the access watchpoints, whose hits gdb expects as awatch
+    access_watchpoints: Vec<(u16, u16)>,
+}
+
+impl<S: Read + Write> GdbStub<S> {
+    /// Takes a stream whose reads fail with `WouldBlock` if no data is available
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream,
+            input: Vec::new(),
+            running: false,
+            connected: true,
+            access_watchpoints: Vec::new(),
+        }
+    }
+
+    pub fn connected(&self) -> bool {
+        self.connected
+    }
+
+    /// Handles the received commands and, while gdb lets the target run, runs the board
+    /// for a frame or until it stops. Meant to be called in place of `run_to_next_frame`.
+    pub fn poll(&mut self, board: &mut Board) -> io::Result<()> {
+        self.receive()?;
+        while self.connected {
+            if let Some(interrupt) = self.input.iter().position(|&b| b == 0x03) {
+                // gdb interrupts with a single byte outside of packets
+                self.input.remove(interrupt);
+                if self.running {
+                    self.running = false;
+                    self.send(&format!("S{:02x}", SIGINT))?;
+                }
+                continue;
+            }
+            match self.next_packet()? {
+                Some(packet) => self.handle(board, &packet)?,
+                None => break,
+            }
+        }
+
+        if self.running {
+            let reason = board.run_until_break(POLL_CYCLES);
+            if reason != StopReason::CycleLimit {
+                self.running = false;
+                let reply = self.stop_reply(reason);
+                self.send(&reply)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn receive(&mut self) -> io::Result<()> {
+        let mut buffer = [0u8; 1024];
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => {
+                    self.connected = false;
+                    return Ok(());
+                }
+                Ok(n) => self.input.extend_from_slice(&buffer[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e) if e.kind() == ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    // Takes the next complete packet out of the input and acknowledges it
+    fn next_packet(&mut self) -> io::Result<Option<String>> {
+        let start = match self.input.iter().position(|&b| b == b'$') {
+            Some(start) => start,
+            None => {
+                // acknowledgements of our packets
+                self.input.clear();
+                return Ok(None);
+            }
+        };
+        let end = match self.input[start..].iter().position(|&b| b == b'#') {
+            Some(end) if start + end + 2 < self.input.len() => start + end,
+            _ => return Ok(None),
+        };
+
+        let packet: Vec<u8> = self.input.drain(..end + 3).collect();
+        let data = &packet[start + 1..end];
+        let checksum = std::str::from_utf8(&packet[end + 1..])
+            .ok()
+            .and_then(|c| u8::from_str_radix(c, 16).ok());
+        if checksum != Some(checksum_of(data)) {
+            warn!("gdb packet with bad checksum");
+            self.stream.write_all(b"-")?;
+            return Ok(None);
+        }
+        self.stream.write_all(b"+")?;
+        Ok(Some(String::from_utf8_lossy(data).into_owned()))
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        debug!("gdb <- {}", data);
+        let packet = format!("${}#{:02x}", data, checksum_of(data.as_bytes()));
+        self.stream.write_all(packet.as_bytes())?;
+        self.stream.flush()
+    }
+
+    fn handle(&mut self, board: &mut Board, packet: &str) -> io::Result<()> {
+        debug!("gdb -> {}", packet);
+        let mut chars = packet.chars();
+        let (command, args) = match chars.next() {
+            Some(command) => (command, chars.as_str()),
+            None => return self.send(""),
+        };
+        let reply = match command {
+            '?' => format!("S{:02x}", SIGTRAP),
+            'g' => {
+                let registers = register_values(&board.registers());
+                registers.iter().map(|&r| hex_word(r)).collect()
+            }
+            'G' => match parse_hex_bytes(args) {
+                Some(bytes) if bytes.len() == REGISTER_COUNT * 2 => {
+                    let mut registers = board.registers();
+                    for (index, value) in bytes.chunks(2).enumerate() {
+                        let value = u16::from_le_bytes([value[0], value[1]]);
+                        set_register(&mut registers, index, value);
+                    }
+                    board.set_registers(registers);
+                    "OK".to_string()
+                }
+                _ => "E01".to_string(),
+            },
+            'p' => match usize::from_str_radix(args, 16) {
+                Ok(index) if index < REGISTER_COUNT => {
+                    hex_word(register_values(&board.registers())[index])
+                }
+                _ => "E01".to_string(),
+            },
+            'P' => match parse_register_write(args) {
+                Some((index, value)) if index < REGISTER_COUNT => {
+                    let mut registers = board.registers();
+                    set_register(&mut registers, index, value);
+                    board.set_registers(registers);
+                    "OK".to_string()
+                }
+                _ => "E01".to_string(),
+            },
+            // replies with fewer bytes if they don't fit into a packet with its $ and
+            // checksum, as gdb allows
+            'm' => match parse_range(args) {
+                Some((addr, len)) => (0..len.min((PACKET_SIZE as u16 - 4) / 2))
+                    .map(|offset| format!("{:02x}", board.peek(addr.wrapping_add(offset))))
+                    .collect(),
+                None => "E01".to_string(),
+            },
+            // writes to the ROM would switch banks instead, so they are refused
+            'M' => match args.split_once(':') {
+                Some((range, data)) => match (parse_range(range), parse_hex_bytes(data)) {
+                    (Some((addr, len)), Some(bytes))
+                        if bytes.len() == len as usize && !writes_rom(addr, len) =>
+                    {
+                        for (offset, &byte) in bytes.iter().enumerate() {
+                            board.poke(addr.wrapping_add(offset as u16), byte);
+                        }
+                        "OK".to_string()
+                    }
+                    _ => "E01".to_string(),
+                },
+                None => "E01".to_string(),
+            },
+            'c' => {
+                self.running = true;
+                return Ok(());
+            }
+            's' => {
+                let reason = board.step_instruction();
+                self.stop_reply(reason)
+            }
+            'Z' | 'z' => match parse_point(args) {
+                Some((kind, addr, len)) => {
+                    let insert = command == 'Z';
+                    if self.update_point(board, insert, kind, addr, len) {
+                        "OK".to_string()
+                    } else {
+                        String::new()
+                    }
+                }
+                None => "E01".to_string(),
+            },
+            'q' if args.starts_with("Supported") => {
+                format!("PacketSize={:x};qXfer:features:read+", PACKET_SIZE)
+            }
+            'q' if args.starts_with("Xfer:features:read:target.xml:") => {
+                let range = &args["Xfer:features:read:target.xml:".len()..];
+                match parse_range(range) {
+                    Some((offset, len)) => {
+                        let offset = (offset as usize).min(TARGET_XML.len());
+                        let end = (offset + len as usize).min(TARGET_XML.len());
+                        let more = if end < TARGET_XML.len() { "m" } else { "l" };
+                        format!("{}{}", more, &TARGET_XML[offset..end])
+                    }
+                    None => "E01".to_string(),
+                }
+            }
+            'q' if args == "Attached" => "1".to_string(),
+            'D' => {
+                self.send("OK")?;
+                self.connected = false;
+                return Ok(());
+            }
+            'k' => {
+                self.connected = false;
+                return Ok(());
+            }
+            _ => String::new(),
+        };
+        self.send(&reply)
+    }
+
+    // Inserts or removes a breakpoint or watchpoint, returns false for unsupported kinds
+    fn update_point(
+        &mut self,
+        board: &mut Board,
+        insert: bool,
+        kind: u8,
+        addr: u16,
+        len: u16,
+    ) -> bool {
+        let end = addr.wrapping_add(len.max(1) - 1);
+        let accesses: &[Access] = match kind {
+            // software and hardware breakpoints behave the same
+            0 | 1 => {
+                if insert {
+                    board.add_breakpoint(Breakpoint::new(addr));
+                } else {
+                    board.remove_breakpoint(addr);
+                }
+                return true;
+            }
+            2 => &[Access::Write],
+            3 => &[Access::Read],
+            4 => {
+                if insert {
+                    self.access_watchpoints.push((addr, end));
+                } else if let Some(i) = self
+                    .access_watchpoints
+                    .iter()
+                    .position(|&w| w == (addr, end))
+                {
+                    self.access_watchpoints.remove(i);
+                }
+                &[Access::Read, Access::Write]
+            }
+            _ => return false,
+        };
+        for &access in accesses {
+            let watchpoint = Watchpoint {
+                start: addr,
+                end,
+                access,
+            };
+            if insert {
+                board.add_watchpoint(watchpoint);
+            } else {
+                board.remove_watchpoint(watchpoint);
+            }
+        }
+        true
+    }
+
+    fn stop_reply(&self, reason: StopReason) -> String {
+        match reason {
+            StopReason::Watchpoint {
+                address, access, ..
+            } => {
+                let watched = |&(start, end): &(u16, u16)| (start..=end).contains(&address);
+                let kind = match access {
+                    Access::Execute => return format!("S{:02x}", SIGTRAP),
+                    _ if self.access_watchpoints.iter().any(watched) => "awatch",
+                    Access::Read => "rwatch",
+                    Access::Write => "watch",
+                };
+                format!("T{:02x}{}:{:x};", SIGTRAP, kind, address)
+            }
+            _ => format!("S{:02x}", SIGTRAP),
+        }
+    }
+}
+
+fn register_values(registers: &CpuRegisters) -> [u16; REGISTER_COUNT] {
+    let pair = |high: u8, low: u8| u16::from_be_bytes([high, low]);
+    [
+        pair(registers.a, registers.f),
+        pair(registers.b, registers.c),
+        pair(registers.d, registers.e),
+        pair(registers.h, registers.l),
+        registers.sp,
+        registers.pc,
+    ]
+}
+
+fn set_register(registers: &mut CpuRegisters, index: usize, value: u16) {
+    let [high, low] = value.to_be_bytes();
+    match index {
+        0 => (registers.a, registers.f) = (high, low),
+        1 => (registers.b, registers.c) = (high, low),
+        2 => (registers.d, registers.e) = (high, low),
+        3 => (registers.h, registers.l) = (high, low),
+        4 => registers.sp = value,
+        5 => registers.pc = value,
+        _ => unreachable!(),
+    }
+}
+
+// Registers are sent in target byte order, which is little endian
+fn hex_word(value: u16) -> String {
+    let [low, high] = value.to_le_bytes();
+    format!("{:02x}{:02x}", low, high)
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, &b| sum.wrapping_add(b))
+}
+
+fn parse_hex_bytes(data: &str) -> Option<Vec<u8>> {
+    data.as_bytes()
+        .chunks(2)
+        .map(|pair| match pair {
+            [_, _] => u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok(),
+            _ => None,
+        })
+        .collect()
+}
+
+// whether a range of memory overlaps the cartridge ROM at 0x0000-0x7fff, also after
+// wrapping around
+fn writes_rom(addr: u16, len: u16) -> bool {
+    len > 0 && (addr < 0x8000 || addr as u32 + len as u32 > 0x10000)
+}
+
+// "addr,length"
+fn parse_range(args: &str) -> Option<(u16, u16)> {
+    let (addr, len) = args.split_once(',')?;
+    Some((
+        u16::from_str_radix(addr, 16).ok()?,
+        u16::from_str_radix(len, 16).ok()?,
+    ))
+}
+
+// "register=value", with the value in target byte order
+fn parse_register_write(args: &str) -> Option<(usize, u16)> {
+    let (index, value) = args.split_once('=')?;
+    let bytes = parse_hex_bytes(value)?;
+    if bytes.len() != 2 {
+        return None;
+    }
+    Some((
+        usize::from_str_radix(index, 16).ok()?,
+        u16::from_le_bytes([bytes[0], bytes[1]]),
+    ))
+}
+
+// "kind,addr,length"
+fn parse_point(args: &str) -> Option<(u8, u16, u16)> {
+    let (kind, range) = args.split_once(',')?;
+    let (addr, len) = parse_range(range.split(';').next()?)?;
+    Some((kind.parse().ok()?, addr, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, ErrorKind, Read, Write};
+
+    use super::{checksum_of, GdbStub};
+    use crate::board::{Board, Config};
+
+    // Replays gdb's packets and collects the replies, without blocking
+    struct MockStream {
+        input: Vec<u8>,
+        output: Vec<u8>,
+    }
+
+    impl Read for MockStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.input.is_empty() {
+                return Err(ErrorKind::WouldBlock.into());
+            }
+            let n = buf.len().min(self.input.len());
+            buf[..n].copy_from_slice(&self.input[..n]);
+            self.input.drain(..n);
+            Ok(n)
+        }
+    }
+
+    impl Write for MockStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn packet(data: &str) -> Vec<u8> {
+        format!("${}#{:02x}", data, checksum_of(data.as_bytes())).into_bytes()
+    }
+
+    // Runs the packets against the board and returns the replies
+    fn replies(board: &mut Board, input: Vec<u8>) -> Vec<String> {
+        let mut stub = GdbStub::new(MockStream {
+            input,
+            output: Vec::new(),
+        });
+        stub.poll(board).unwrap();
+        String::from_utf8(stub.stream.output.clone())
+            .unwrap()
+            .split('$')
+            .skip(1)
+            .map(|reply| reply.split('#').next().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn registers_memory_and_breakpoints() {
+        let mut game = vec![0u8; 0x8000];
+        game[0x0100..0x0104].copy_from_slice(&[0x00, 0x00, 0x18, 0xfc]); // NOP, NOP, JR -4
+        let mut board = Board::no_boot(&game, Config::default());
+
+        let mut input = Vec::new();
+        for command in ["p5", "m100,4", "Mc000,2:abcd", "mc000,2", "Z0,102,1", "c"] {
+            input.extend(packet(command));
+        }
+        let mut stub = GdbStub::new(MockStream {
+            input,
+            output: Vec::new(),
+        });
+        stub.poll(&mut board).unwrap();
+
+        let output = String::from_utf8(stub.stream.output.clone()).unwrap();
+        let replies: Vec<&str> = output
+            .split('$')
+            .skip(1)
+            .map(|reply| reply.split('#').next().unwrap())
+            .collect();
+        assert_eq!(replies, ["0001", "000018fc", "OK", "abcd", "OK", "S05"]);
+        assert_eq!(board.registers().pc, 0x0102);
+        assert!(stub.connected());
+    }
+
+    #[test]
+    fn access_watchpoints_and_packet_size() {
+        let mut game = vec![0u8; 0x8000];
+        game[0x0100..0x0106].copy_from_slice(&[
+            0xea, 0x00, 0xc0, // LD (0xc000),A
+            0xc3, 0x00, 0x01, // JP 0x0100
+        ]);
+        let mut board = Board::no_boot(&game, Config::default());
+
+        let mut input = Vec::new();
+        for command in ["m0,2000", "Z4,c000,1", "c"] {
+            input.extend(packet(command));
+        }
+        let replies = replies(&mut board, input);
+        assert_eq!(replies[0].len(), super::PACKET_SIZE - 4);
+        assert_eq!(replies[1..], ["OK", "T05awatch:c000;"]);
+    }
+
+    #[test]
+    fn malformed_packets() {
+        let mut board = Board::no_boot(&vec![0u8; 0x8000], Config::default());
+        // empty, and starting with a byte which isn't valid UTF-8
+        let mut input = b"$#00$\xffx#".to_vec();
+        input.extend(format!("{:02x}", checksum_of(b"\xffx")).into_bytes());
+        input.extend(packet("?"));
+        assert_eq!(replies(&mut board, input), ["", "", "S05"]);
+    }
+
+    #[test]
+    fn io_registers_and_rom_writes() {
+        let mut board = Board::no_boot(&vec![0u8; 0x8000], Config::default());
+        let mut input = Vec::new();
+        for command in [
+            "mff00,80",
+            "Mff03,1:00",
+            "M2000,1:05",
+            "Mffff,2:0000",
+            "m4000,1",
+        ] {
+            input.extend(packet(command));
+        }
+        let replies = replies(&mut board, input);
+        assert_eq!(replies[0].len(), 0x100);
+        assert_eq!(replies[1..], ["OK", "E01", "E01", "00"]);
+    }
+}
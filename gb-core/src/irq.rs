@@ -1,3 +1,5 @@
+use crate::state::{Snapshot, State};
+
 pub(crate) struct Irq {
     interrupt_flag: u8,
 }
@@ -27,7 +29,17 @@ impl Irq {
         self.interrupt_flag |= 1 << 2;
     }
 
+    pub fn serial_interrupt(&mut self) {
+        self.interrupt_flag |= 1 << 3;
+    }
+
     pub fn joypad_interrupt(&mut self) {
         self.interrupt_flag |= 1 << 4;
     }
 }
+
+impl Snapshot for Irq {
+    fn snapshot(&mut self, state: &mut State) {
+        state.u8(&mut self.interrupt_flag);
+    }
+}
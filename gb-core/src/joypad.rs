@@ -1,7 +1,9 @@
-use std::cell::RefCell;
-use std::rc::Rc;
-
 use crate::irq::Irq;
+use crate::sgb::Sgb;
+use crate::state::{Snapshot, State};
+
+// The SGB supports up to four joypads
+pub const MAX_PLAYERS: usize = 4;
 
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub enum Button {
@@ -16,60 +18,95 @@ pub enum Button {
 }
 
 pub(crate) struct JoyPad {
-    irq: Rc<RefCell<Irq>>,
-    direction_buttons: u8,
-    action_buttons: u8,
+    direction_buttons: [u8; MAX_PLAYERS],
+    action_buttons: [u8; MAX_PLAYERS],
     selection_flag: u8,
+    // receives command packets through the selection lines
+    sgb: Option<Sgb>,
 }
 
 impl JoyPad {
-    pub fn new(irq: Rc<RefCell<Irq>>) -> Self {
+    pub fn new(sgb: Option<Sgb>) -> Self {
         Self {
-            irq,
-            direction_buttons: 0xf,
-            action_buttons: 0xf,
+            direction_buttons: [0xf; MAX_PLAYERS],
+            action_buttons: [0xf; MAX_PLAYERS],
             selection_flag: 0,
+            sgb,
         }
     }
 
-    pub fn button_pressed(&mut self, button: Button) {
+    pub fn sgb(&self) -> Option<&Sgb> {
+        self.sgb.as_ref()
+    }
+
+    pub fn sgb_mut(&mut self) -> Option<&mut Sgb> {
+        self.sgb.as_mut()
+    }
+
+    pub fn button_pressed(&mut self, player: usize, button: Button, irq: &mut Irq) {
+        let direction_buttons = &mut self.direction_buttons[player];
+        let action_buttons = &mut self.action_buttons[player];
         match button {
-            Button::Right => self.direction_buttons = !(1 << 0),
-            Button::Left => self.direction_buttons = !(1 << 1),
-            Button::Up => self.direction_buttons = !(1 << 2),
-            Button::Down => self.direction_buttons = !(1 << 3),
-            Button::A => self.action_buttons = !(1 << 0),
-            Button::B => self.action_buttons = !(1 << 1),
-            Button::Select => self.action_buttons = !(1 << 2),
-            Button::Start => self.action_buttons = !(1 << 3),
+            Button::Right => *direction_buttons &= !(1 << 0),
+            Button::Left => *direction_buttons &= !(1 << 1),
+            Button::Up => *direction_buttons &= !(1 << 2),
+            Button::Down => *direction_buttons &= !(1 << 3),
+            Button::A => *action_buttons &= !(1 << 0),
+            Button::B => *action_buttons &= !(1 << 1),
+            Button::Select => *action_buttons &= !(1 << 2),
+            Button::Start => *action_buttons &= !(1 << 3),
         }
-        self.irq.borrow_mut().joypad_interrupt();
+        irq.joypad_interrupt();
     }
 
-    pub fn button_released(&mut self, button: Button) {
+    pub fn button_released(&mut self, player: usize, button: Button) {
+        let direction_buttons = &mut self.direction_buttons[player];
+        let action_buttons = &mut self.action_buttons[player];
         match button {
-            Button::Right => self.direction_buttons |= 1 << 0,
-            Button::Left => self.direction_buttons |= 1 << 1,
-            Button::Up => self.direction_buttons |= 1 << 2,
-            Button::Down => self.direction_buttons |= 1 << 3,
-            Button::A => self.action_buttons |= 1 << 0,
-            Button::B => self.action_buttons |= 1 << 1,
-            Button::Select => self.action_buttons |= 1 << 2,
-            Button::Start => self.action_buttons |= 1 << 3,
+            Button::Right => *direction_buttons |= 1 << 0,
+            Button::Left => *direction_buttons |= 1 << 1,
+            Button::Up => *direction_buttons |= 1 << 2,
+            Button::Down => *direction_buttons |= 1 << 3,
+            Button::A => *action_buttons |= 1 << 0,
+            Button::B => *action_buttons |= 1 << 1,
+            Button::Select => *action_buttons |= 1 << 2,
+            Button::Start => *action_buttons |= 1 << 3,
         }
     }
 
     pub fn write_byte(&mut self, value: u8) {
+        if let Some(sgb) = &mut self.sgb {
+            sgb.write_joypad(value);
+        }
         self.selection_flag = value;
     }
 
     pub fn read_byte(&self) -> u8 {
+        let (player, multiplayer) = match &self.sgb {
+            Some(sgb) => (sgb.player(), sgb.multiplayer()),
+            None => (0, false),
+        };
+
         if (self.selection_flag & 0x20) == 0 {
-            self.action_buttons
+            self.action_buttons[player]
         } else if (self.selection_flag & 0x10) == 0 {
-            self.direction_buttons
+            self.direction_buttons[player]
+        } else if multiplayer {
+            // with both lines deselected, the SGB reports the current joypad
+            0xf - player as u8
         } else {
             0xf
         }
     }
 }
+
+impl Snapshot for JoyPad {
+    fn snapshot(&mut self, state: &mut State) {
+        state.bytes(&mut self.direction_buttons);
+        state.bytes(&mut self.action_buttons);
+        state.u8(&mut self.selection_flag);
+        if let Some(sgb) = self.sgb.as_mut() {
+            sgb.snapshot(state);
+        }
+    }
+}
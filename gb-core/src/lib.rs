@@ -1,18 +1,50 @@
-#![feature(exclusive_range_pattern)]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
 
 mod alu;
 mod board;
+mod bus;
 mod cartridge;
 mod cpu;
+mod debugger;
+mod disasm;
+mod gbs;
+#[cfg(feature = "std")]
+mod gdb;
 mod irq;
 mod joypad;
 mod mmu;
+mod model;
+mod palette;
 mod ppu;
 mod registers;
+mod rewind;
+mod sgb;
 mod sound;
+mod state;
+mod testrom;
 mod timer;
+mod trace;
 
-pub use board::Board;
+pub use board::{Board, Config, HBlankHook, InterruptHook, VBlankHook};
+pub use bus::Bus;
+pub use cpu::Cpu;
+pub use debugger::{
+    Access, Breakpoint, Comparison, Condition, CpuRegisters, Register, StopReason, Watchpoint,
+};
+pub use disasm::{disassemble, BankAddress, Instruction};
+pub use gbs::{Gbs, GbsError, GbsPlayer};
+#[cfg(feature = "std")]
+pub use gdb::GdbStub;
 pub use joypad::Button;
-pub use ppu::{Color, HEIGHT, WIDTH};
-pub use sound::SAMPLE_RATE as AUDIO_SAMPLE_RATE;
+pub use model::Model;
+pub use palette::{CompatPalette, DmgPalette};
+pub use ppu::{Color, PixelFormat, HEIGHT, WIDTH};
+pub use sgb::{SGB_HEIGHT, SGB_WIDTH};
+pub use sound::AudioStatus;
+pub use sound::Channel as AudioChannel;
+#[cfg(feature = "png")]
+pub use testrom::{find_test_roms, run_test_rom_file, TestSuite};
+pub use testrom::{run_test_rom, TestOutcome, TestRom};
+pub use trace::{TraceFormat, TraceSink};
@@ -1,131 +1,442 @@
-use std::cell::RefCell;
-use std::rc::Rc;
+use alloc::{boxed::Box, vec::Vec};
+use core::cell::Cell;
 
-use log::error;
+use log::debug;
 
+use crate::bus::Bus;
 use crate::cartridge::Cartridge;
+use crate::debugger::{Access, StopReason, Watchpoint};
 use crate::irq::Irq;
-use crate::joypad::JoyPad;
+use crate::joypad::{Button, JoyPad};
 use crate::ppu::Ppu;
 use crate::sound::Apu;
+use crate::state::{Snapshot, State};
 use crate::timer::Timer;
 
-const WRAM_SIZE: usize = 0x2000;
+// the CGB has 8 banks of work ram, the DMG only 2
+const WRAM_SIZE: usize = 0x8000;
+const WRAM_BANK_SIZE: usize = 0x1000;
 const ECHO_RAM_SIZE: usize = 0x1e00;
 const HRAM_SIZE: usize = 0xfe;
 const SERIAL_RAM: usize = 0x2;
 
+// A serial transfer with the internal clock shifts out one bit every 512 CPU cycles, or
+// every 16 with the fast clock of the CGB
+const SERIAL_BIT_CYCLES: usize = 512;
+const SERIAL_FAST_BIT_CYCLES: usize = 16;
+
+// VRAM DMA copies blocks of 16 bytes, each halting the CPU for 32 cycles of the normal clock
+const VRAM_DMA_BLOCK_SIZE: u16 = 0x10;
+const VRAM_DMA_BLOCK_CYCLES: usize = 32;
+
 pub(crate) struct Mmu {
     timer: Timer,
-    apu: Rc<RefCell<Apu>>,
-    irq: Rc<RefCell<Irq>>,
-    ppu: Rc<RefCell<Ppu>>,
-    joypad: Rc<RefCell<JoyPad>>,
+    apu: Apu,
+    irq: Irq,
+    ppu: Ppu,
+    joypad: JoyPad,
     cartridge: Cartridge,
-    wram: [u8; WRAM_SIZE],
+    // whether the CGB features are enabled
+    cgb: bool,
+    // boxed like the screen of the PPU
+    wram: Box<[u8; WRAM_SIZE]>,
+    svbk: u8, // selects the work ram bank at 0xd000-0xdfff
     echo_ram: [u8; ECHO_RAM_SIZE],
     hram: [u8; HRAM_SIZE],
     serial_ram: [u8; SERIAL_RAM],
+    // CPU cycles until the transfer of a byte over the link cable ends, 0 if none runs
+    serial_cycles: usize,
+    // bytes sent over the link cable
+    serial_output: Vec<u8>,
     interrupt_enable: u8,
+    // CGB speed switch
+    double_speed: bool,
+    speed_switch_armed: bool,
+    // CGB VRAM DMA
+    hdma_source: u16,
+    hdma_destination: u16,
+    hdma_blocks: u8, // remaining blocks minus one
+    hblank_dma_active: bool,
+    // CPU cycles for which the CPU is halted by VRAM DMA
+    dma_stall: usize,
+    // ticks of the normal clock since they were last taken
+    elapsed_ticks: usize,
+    // PPU events since they were last taken
+    vblank_started: bool,
+    hblank_lines: Vec<u8>,
+    watchpoints: Vec<Watchpoint>,
+    // the first watchpoint hit since it was last taken
+    watch_hit: Cell<Option<StopReason>>,
 }
 
 impl Mmu {
-    pub fn new(
-        apu: Rc<RefCell<Apu>>,
-        irq: Rc<RefCell<Irq>>,
-        ppu: Rc<RefCell<Ppu>>,
-        joypad: Rc<RefCell<JoyPad>>,
-        cartridge: Cartridge,
-    ) -> Self {
+    pub fn new(apu: Apu, ppu: Ppu, joypad: JoyPad, cartridge: Cartridge, cgb: bool) -> Self {
         Self {
-            timer: Timer::new(Rc::clone(&irq)),
+            timer: Timer::new(),
             apu,
-            irq,
+            irq: Irq::new(),
             ppu,
             joypad,
             cartridge,
-            wram: [0; WRAM_SIZE],
+            cgb,
+            wram: Box::new([0; WRAM_SIZE]),
+            svbk: 0,
             echo_ram: [0; ECHO_RAM_SIZE],
             hram: [0; HRAM_SIZE],
             serial_ram: [0; SERIAL_RAM],
+            serial_cycles: 0,
+            serial_output: Vec::new(),
             interrupt_enable: 0,
+            double_speed: false,
+            speed_switch_armed: false,
+            hdma_source: 0,
+            hdma_destination: 0,
+            hdma_blocks: 0x7f,
+            hblank_dma_active: false,
+            dma_stall: 0,
+            elapsed_ticks: 0,
+            vblank_started: false,
+            hblank_lines: Vec::new(),
+            watchpoints: Vec::new(),
+            watch_hit: Cell::new(None),
+        }
+    }
+
+    /// Advances the other components by the given CPU cycles, plus the cycles for which
+    /// VRAM DMA halted the CPU. In double speed mode, the PPU and APU only see half of
+    /// them, so the returned cycles of the normal clock are the ones which count towards
+    /// a frame.
+    fn step(&mut self, steps: u8) -> usize {
+        let mut ticks = self.step_components(steps) as usize;
+        while self.dma_stall > 0 {
+            let stall = self.dma_stall.min(4);
+            self.dma_stall -= stall;
+            ticks += self.step_components(stall as u8) as usize;
+        }
+        ticks
+    }
+
+    /// Takes the ticks of the normal clock which elapsed through `Bus::tick`
+    pub fn take_elapsed_ticks(&mut self) -> usize {
+        core::mem::take(&mut self.elapsed_ticks)
+    }
+
+    /// Returns whether VBlank started since the last call
+    pub fn take_vblank_started(&mut self) -> bool {
+        core::mem::take(&mut self.vblank_started)
+    }
+
+    /// Moves the lines whose HBlank started since the last call into `lines`
+    pub fn take_hblank_lines(&mut self, lines: &mut Vec<u8>) {
+        lines.append(&mut self.hblank_lines);
+    }
+
+    fn step_components(&mut self, steps: u8) -> u8 {
+        let ticks = if self.double_speed { steps / 2 } else { steps };
+
+        self.ppu.step(ticks, &mut self.irq);
+        let hblank_started = self.ppu.take_hblank_started();
+        if hblank_started {
+            self.hblank_lines.push(self.ppu.ly());
+        }
+        self.vblank_started |= self.ppu.take_vblank_started();
+
+        self.apu.step(ticks);
+        self.timer.step(steps, &mut self.irq);
+        if self.serial_cycles > 0 {
+            self.serial_cycles = self.serial_cycles.saturating_sub(steps as usize);
+            if self.serial_cycles == 0 {
+                self.finish_serial_transfer();
+            }
+        }
+
+        if hblank_started && self.hblank_dma_active {
+            self.copy_vram_block();
+            if self.hdma_blocks == 0 {
+                self.hblank_dma_active = false;
+            }
+            self.hdma_blocks = self.hdma_blocks.wrapping_sub(1) & 0x7f;
+        }
+
+        ticks
+    }
+
+    pub fn apu(&self) -> &Apu {
+        &self.apu
+    }
+
+    pub fn apu_mut(&mut self) -> &mut Apu {
+        &mut self.apu
+    }
+
+    pub fn ppu(&self) -> &Ppu {
+        &self.ppu
+    }
+
+    pub fn ppu_mut(&mut self) -> &mut Ppu {
+        &mut self.ppu
+    }
+
+    pub fn joypad(&self) -> &JoyPad {
+        &self.joypad
+    }
+
+    pub fn button_pressed(&mut self, player: usize, button: Button) {
+        self.joypad.button_pressed(player, button, &mut self.irq);
+    }
+
+    pub fn button_released(&mut self, player: usize, button: Button) {
+        self.joypad.button_released(player, button);
+    }
+
+    /// Lets the SGB take the finished frame, and a VRAM transfer if one is pending
+    pub fn end_frame(&mut self) {
+        if let Some(sgb) = self.joypad.sgb_mut() {
+            if sgb.transfer_pending() {
+                sgb.transfer(self.ppu.shades());
+            }
+            sgb.end_frame(self.ppu.shades());
+        }
+    }
+
+    pub fn set_divider(&mut self, value: u16) {
+        self.timer.set_divider(value);
+    }
+
+    /// Switches between normal and double speed, if the switch was prepared through KEY1
+    fn switch_speed(&mut self) {
+        if self.speed_switch_armed {
+            self.double_speed = !self.double_speed;
+            self.speed_switch_armed = false;
         }
     }
 
-    pub fn step(&mut self, steps: u8) {
-        let mut ppu = self.ppu.borrow_mut();
-        ppu.step(steps);
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
 
-        let mut apu = self.apu.borrow_mut();
-        apu.step(steps);
+    pub fn remove_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.retain(|&w| w != watchpoint);
+    }
 
-        self.timer.step(steps);
+    pub fn clear_watchpoints(&mut self) {
+        self.watchpoints.clear();
     }
 
-    pub fn interrupt_enable(&self) -> u8 {
-        self.interrupt_enable
+    /// Records a hit if the access is watched
+    pub fn watch(&self, addr: u16, access: Access, value: u8) {
+        if self.watchpoints.is_empty() || self.watch_hit.get().is_some() {
+            return;
+        }
+        if self.watchpoints.iter().any(|w| w.matches(addr, access)) {
+            self.watch_hit.set(Some(StopReason::Watchpoint {
+                address: addr,
+                access,
+                value,
+            }));
+        }
+    }
+
+    pub fn take_watch_hit(&self) -> Option<StopReason> {
+        self.watch_hit.take()
+    }
+
+    /// The bank mapped at the address, 0 for unbanked memory
+    pub fn bank(&self, addr: u16) -> u16 {
+        let bank = match addr {
+            0x4000..=0x7fff => self.cartridge.rom_bank(),
+            0x8000..=0x9fff => self.ppu.vram_bank(),
+            0xd000..=0xdfff => self.wram_offset(addr) / WRAM_BANK_SIZE,
+            _ => 0,
+        };
+        bank as u16
     }
 
     pub fn read_byte(&self, addr: u16) -> u8 {
-        match addr {
+        let value = self.fetch_byte(addr);
+        self.watch(addr, Access::Read, value);
+        value
+    }
+
+    /// Reads a byte of an instruction, which doesn't trigger watchpoints. Unmapped
+    /// addresses read 0xff.
+    pub fn fetch_byte(&self, addr: u16) -> u8 {
+        match self.mapped_byte(addr) {
+            Some(value) => value,
+            None => {
+                debug!("Read from unmapped addr {:04x}", addr);
+                0xff
+            }
+        }
+    }
+
+    /// Reads a byte without triggering watchpoints. Unmapped addresses read 0xff.
+    pub fn peek(&self, addr: u16) -> u8 {
+        self.mapped_byte(addr).unwrap_or(0xff)
+    }
+
+    fn mapped_byte(&self, addr: u16) -> Option<u8> {
+        let value = match addr {
             0x0000..=0x7fff => self.cartridge.read_byte(addr),
-            0x8000..=0x9fff => self.ppu.borrow().read_byte(addr),
-            0xc000..=0xdfff => self.wram[addr as usize - 0xc000],
+            0x8000..=0x9fff => self.ppu.read_byte(addr),
+            0xa000..=0xbfff => self.cartridge.read_byte(addr),
+            0xc000..=0xcfff => self.wram[addr as usize - 0xc000],
+            0xd000..=0xdfff => self.wram[self.wram_offset(addr)],
             0xe000..=0xfdff => self.echo_ram[addr as usize - 0xe000],
-            0xfe00..=0xfe9f => self.ppu.borrow().read_byte(addr),
-            0xff00 => self.joypad.borrow().read_byte(),
+            0xfe00..=0xfe9f => self.ppu.read_byte(addr),
+            0xff00 => self.joypad.read_byte(),
             0xff01..=0xff02 => self.serial_ram[addr as usize - 0xff01],
             0xff04..=0xff07 => self.timer.read_byte(addr),
-            0xff0f => self.irq.borrow().interrupt_flag(),
-            0xff10..=0xff26 | 0xff30..=0xff3f => self.apu.borrow().read_byte(addr),
-            0xff40..=0xff45 | 0xff47..=0xff4b => self.ppu.borrow().read_byte(addr),
-            0xff50 => self.cartridge.read_byte(addr),
+            0xff0f => self.irq.interrupt_flag(),
+            0xff10..=0xff14 | 0xff16..=0xff1e | 0xff20..=0xff26 | 0xff30..=0xff3f => {
+                self.apu.read_byte(addr)
+            }
+            0xff40..=0xff45 | 0xff47..=0xff4b => self.ppu.read_byte(addr),
+            0xff4d | 0xff4f | 0xff51..=0xff55 | 0xff68..=0xff6b | 0xff70 if !self.cgb => 0xff, // CGB only
+            0xff4d => {
+                let speed = (self.double_speed as u8) << 7;
+                speed | 0x7e | self.speed_switch_armed as u8
+            }
+            0xff4f | 0xff68..=0xff6b => self.ppu.read_byte(addr),
+            0xff51..=0xff54 => 0xff, // write only
+            0xff55 => {
+                let inactive = (!self.hblank_dma_active as u8) << 7;
+                inactive | self.hdma_blocks
+            }
+            0xff4c | 0xff6c => 0xff, // used by the CGB boot rom only
+            0xff50 => 0xff,          // write only
+            0xff70 => self.svbk | 0xf8,
             0xff80..=0xfffe => self.hram[addr as usize - 0xff80],
             0xffff => self.interrupt_enable,
-            _ => {
-                error!("Unimplemented read byte from addr {:04x}", addr);
-                unimplemented!();
-            }
-        }
+            _ => return None,
+        };
+        Some(value)
     }
 
     pub fn write_byte(&mut self, addr: u16, value: u8) {
+        self.watch(addr, Access::Write, value);
+        self.poke(addr, value);
+    }
+
+    /// Writes a byte without triggering watchpoints
+    pub fn poke(&mut self, addr: u16, value: u8) {
         match addr {
             0x0000..=0x7fff => self.cartridge.write_byte(addr, value),
-            0x8000..=0x9fff => self.ppu.borrow_mut().write_byte(addr, value),
-            0xc000..=0xdfff => self.wram[addr as usize - 0xc000] = value,
+            0x8000..=0x9fff => self.ppu.write_byte(addr, value),
+            0xa000..=0xbfff => self.cartridge.write_byte(addr, value),
+            0xc000..=0xcfff => self.wram[addr as usize - 0xc000] = value,
+            0xd000..=0xdfff => self.wram[self.wram_offset(addr)] = value,
             0xe000..=0xfdff => self.echo_ram[addr as usize - 0xe000] = value,
-            0xfe00..=0xfe9f => self.ppu.borrow_mut().write_byte(addr, value),
+            0xfe00..=0xfe9f => self.ppu.write_byte(addr, value),
             0xfea0..=0xfeff => (), // not usable
-            0xff00 => self.joypad.borrow_mut().write_byte(value),
-            0xff01..=0xff02 => self.serial_ram[addr as usize - 0xff01] = value,
+            0xff00 => self.joypad.write_byte(value),
+            0xff01 => self.serial_ram[0] = value,
+            0xff02 => self.serial_transfer(value),
             0xff04..=0xff07 => self.timer.write_byte(addr, value),
-            0xff0f => self.irq.borrow_mut().set_interrupt_flag(value),
-            0xff10..=0xff26 | 0xff30..=0xff3f => self.apu.borrow_mut().write_byte(addr, value),
-            0xff40..=0xff45 | 0xff47..=0xff4b => self.ppu.borrow_mut().write_byte(addr, value),
+            0xff0f => self.irq.set_interrupt_flag(value),
+            0xff10..=0xff14 | 0xff16..=0xff1e | 0xff20..=0xff26 | 0xff30..=0xff3f => {
+                self.apu.write_byte(addr, value)
+            }
+            0xff40..=0xff45 | 0xff47..=0xff4b => self.ppu.write_byte(addr, value),
             0xff46 => self.dma_transfer(value),
+            0xff4d | 0xff4f | 0xff51..=0xff55 | 0xff68..=0xff6b | 0xff70 if !self.cgb => (), // CGB only
+            0xff4d => self.speed_switch_armed = (value & 1) != 0,
+            0xff4f | 0xff68..=0xff6b => self.ppu.write_byte(addr, value),
+            0xff51 => self.hdma_source = (self.hdma_source & 0x00ff) | ((value as u16) << 8),
+            0xff52 => self.hdma_source = (self.hdma_source & 0xff00) | (value & 0xf0) as u16,
+            0xff53 => {
+                let high = ((value & 0x1f) as u16) << 8;
+                self.hdma_destination = (self.hdma_destination & 0x00ff) | high;
+            }
+            0xff54 => {
+                let low = (value & 0xf0) as u16;
+                self.hdma_destination = (self.hdma_destination & 0xff00) | low;
+            }
+            0xff55 => self.start_vram_dma(value),
+            // the CGB boot rom selects the DMG compatibility mode through KEY0 and OPRI,
+            // which is decided when the board is created instead
+            0xff4c | 0xff6c => (),
             0xff50 => self.cartridge.write_byte(addr, value),
+            0xff70 => self.svbk = value & 0x07,
             0xff7f => (), // not usable
             0xff80..=0xfffe => self.hram[addr as usize - 0xff80] = value,
             0xffff => self.interrupt_enable = value,
-            _ => {
-                error!("Unimplemented write byte to addr {:04x}", addr);
-                unimplemented!();
-            }
+            // unused I/O registers ignore writes
+            _ => debug!("Ignored write to unmapped addr {:04x}", addr),
         }
     }
 
-    pub fn read_word(&self, addr: u16) -> u16 {
-        let low = self.read_byte(addr) as u16;
-        let high = self.read_byte(addr + 1) as u16;
-        (high << 8) | low
+    pub fn take_serial_output(&mut self) -> Vec<u8> {
+        core::mem::take(&mut self.serial_output)
+    }
+
+    // Starts sending a byte with the internal clock. With the external clock, the
+    // transfer waits for a partner which never comes.
+    fn serial_transfer(&mut self, value: u8) {
+        self.serial_ram[1] = value;
+        self.serial_cycles = if (value & 0x81) == 0x81 {
+            let fast = self.cgb && (value & 0x02) != 0;
+            8 * if fast {
+                SERIAL_FAST_BIT_CYCLES
+            } else {
+                SERIAL_BIT_CYCLES
+            }
+        } else {
+            0
+        };
+    }
+
+    // Without a partner, 0xff is received for the byte which was sent
+    fn finish_serial_transfer(&mut self) {
+        self.serial_output.push(self.serial_ram[0]);
+        self.serial_ram[0] = 0xff;
+        self.serial_ram[1] &= 0x7f;
+        self.irq.serial_interrupt();
+    }
+
+    /// Offset into the work ram for the switchable bank at 0xd000-0xdfff. Bank 0 can't be
+    /// selected and the DMG always uses bank 1.
+    fn wram_offset(&self, addr: u16) -> usize {
+        let bank = (self.svbk as usize).max(1);
+        bank * WRAM_BANK_SIZE + addr as usize - 0xd000
+    }
+
+    /// Starts a general purpose VRAM DMA, which copies everything at once, or a HBlank
+    /// DMA, which copies one block per HBlank. Writing bit 7 as 0 while a HBlank DMA is
+    /// running cancels it instead.
+    fn start_vram_dma(&mut self, value: u8) {
+        let hblank = (value & 0x80) != 0;
+        if self.hblank_dma_active && !hblank {
+            self.hblank_dma_active = false;
+            return;
+        }
+
+        self.hdma_blocks = value & 0x7f;
+        if hblank {
+            self.hblank_dma_active = true;
+            return;
+        }
+
+        for _ in 0..=self.hdma_blocks {
+            self.copy_vram_block();
+        }
+        self.hdma_blocks = 0x7f;
     }
 
-    pub fn write_word(&mut self, addr: u16, value: u16) {
-        let high = (value >> 8) as u8;
-        let low = (value & 0xFF) as u8;
-        self.write_byte(addr, low);
-        self.write_byte(addr + 1, high);
+    /// Copies the next block of the VRAM DMA into the current VRAM bank and halts the CPU
+    /// for the duration
+    fn copy_vram_block(&mut self) {
+        for offset in 0..VRAM_DMA_BLOCK_SIZE {
+            let byte = self.peek(self.hdma_source.wrapping_add(offset));
+            let destination = 0x8000 | (self.hdma_destination.wrapping_add(offset) & 0x1fff);
+            self.ppu.write_byte(destination, byte);
+        }
+        self.hdma_source = self.hdma_source.wrapping_add(VRAM_DMA_BLOCK_SIZE);
+        self.hdma_destination = (self.hdma_destination + VRAM_DMA_BLOCK_SIZE) & 0x1fff;
+
+        // the block takes the same time in double speed, which are twice the CPU cycles
+        let speed = if self.double_speed { 2 } else { 1 };
+        self.dma_stall += VRAM_DMA_BLOCK_CYCLES * speed;
     }
 
     fn dma_transfer(&mut self, value: u8) {
@@ -133,8 +444,226 @@ impl Mmu {
         for offset in 0u16..=0x9f {
             let source = high_byte | offset;
             let destination = 0xfe00 | offset;
-            let byte = self.read_byte(source);
-            self.write_byte(destination, byte);
+            let byte = self.peek(source);
+            self.poke(destination, byte);
+        }
+    }
+}
+
+impl Bus for Mmu {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.read_byte(addr)
+    }
+
+    fn fetch(&mut self, addr: u16) -> u8 {
+        self.fetch_byte(addr)
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        self.write_byte(addr, value);
+    }
+
+    fn tick(&mut self) {
+        self.elapsed_ticks += self.step(4);
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        Mmu::peek(self, addr)
+    }
+
+    fn pending_interrupts(&self) -> u8 {
+        self.interrupt_enable & self.irq.interrupt_flag()
+    }
+
+    fn acknowledge_interrupt(&mut self, bit: u8) {
+        let interrupt_flag = self.irq.interrupt_flag() & !(1 << bit);
+        self.irq.set_interrupt_flag(interrupt_flag);
+    }
+
+    fn stop(&mut self) {
+        self.switch_speed();
+    }
+}
+
+/// Bookkeeping of the board, like watchpoints and the serial output, is not part of the
+/// machine state
+impl Snapshot for Mmu {
+    fn snapshot(&mut self, state: &mut State) {
+        self.timer.snapshot(state);
+        self.apu.snapshot(state);
+        self.irq.snapshot(state);
+        self.ppu.snapshot(state);
+        self.joypad.snapshot(state);
+        self.cartridge.snapshot(state);
+        state.bytes(self.wram.as_mut_slice());
+        state.u8(&mut self.svbk);
+        state.bytes(&mut self.echo_ram);
+        state.bytes(&mut self.hram);
+        state.bytes(&mut self.serial_ram);
+        state.usize(&mut self.serial_cycles);
+        state.u8(&mut self.interrupt_enable);
+        state.bool(&mut self.double_speed);
+        state.bool(&mut self.speed_switch_armed);
+        state.u16(&mut self.hdma_source);
+        state.u16(&mut self.hdma_destination);
+        state.u8(&mut self.hdma_blocks);
+        state.bool(&mut self.hblank_dma_active);
+        state.usize(&mut self.dma_stall);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::Mmu;
+    use crate::bus::Bus;
+    use crate::cartridge::Cartridge;
+    use crate::debugger::{Access, Watchpoint};
+    use crate::joypad::JoyPad;
+    use crate::ppu::Ppu;
+    use crate::sound::{Apu, DEFAULT_SAMPLE_RATE};
+
+    fn cgb_mmu() -> Mmu {
+        let apu = Apu::new(DEFAULT_SAMPLE_RATE);
+        let cartridge = Cartridge::no_boot(&vec![0; 0x8000]);
+        Mmu::new(apu, Ppu::new(true), JoyPad::new(None), cartridge, true)
+    }
+
+    #[test]
+    fn unmapped_addresses() {
+        let mut mmu = cgb_mmu();
+        for addr in [0xfea0, 0xff03, 0xff15, 0xff27, 0xff50, 0xff7f] {
+            assert_eq!(mmu.read_byte(addr), 0xff);
+            mmu.write_byte(addr, 0);
+        }
+    }
+
+    #[test]
+    fn oam_dma_ignores_watchpoints() {
+        let mut mmu = cgb_mmu();
+        mmu.add_watchpoint(Watchpoint {
+            start: 0xc000,
+            end: 0xc09f,
+            access: Access::Read,
+        });
+        mmu.add_watchpoint(Watchpoint {
+            start: 0xfe00,
+            end: 0xfe9f,
+            access: Access::Write,
+        });
+        mmu.write_byte(0xff46, 0xc0);
+        assert_eq!(mmu.take_watch_hit(), None);
+    }
+
+    #[test]
+    fn serial_transfer_takes_eight_bits() {
+        let mut mmu = cgb_mmu();
+        mmu.write_byte(0xff01, 0x41);
+        mmu.write_byte(0xff02, 0x81);
+        // 8 bits of 512 cycles, 4 cycles per tick
+        for _ in 0..1023 {
+            mmu.tick();
+        }
+        assert!(mmu.take_serial_output().is_empty());
+        assert_eq!(mmu.read_byte(0xff02), 0x81);
+
+        mmu.tick();
+        assert_eq!(mmu.take_serial_output(), [0x41]);
+        assert_eq!(mmu.read_byte(0xff01), 0xff);
+        assert_eq!(mmu.read_byte(0xff02), 0x01);
+        assert_eq!(mmu.irq.interrupt_flag() & 0x08, 0x08);
+    }
+
+    // fills 0xc000.. with a pattern and points the VRAM DMA from there to 0x8100
+    fn prepare_vram_dma(mmu: &mut Mmu) {
+        for i in 0..0x40 {
+            mmu.write_byte(0xc000 + i, i as u8 + 1);
+        }
+        for (addr, value) in [
+            (0xff51, 0xc0),
+            (0xff52, 0x00),
+            (0xff53, 0x01),
+            (0xff54, 0x00),
+        ] {
+            mmu.write_byte(addr, value);
+        }
+    }
+
+    #[test]
+    fn speed_switch() {
+        let mut mmu = cgb_mmu();
+        mmu.stop();
+        assert_eq!(mmu.read_byte(0xff4d), 0x7e);
+        mmu.write_byte(0xff4d, 1);
+        assert_eq!(mmu.read_byte(0xff4d), 0x7f);
+        mmu.stop();
+        assert_eq!(mmu.read_byte(0xff4d), 0xfe);
+    }
+
+    #[test]
+    fn vram_and_wram_banks() {
+        let mut mmu = cgb_mmu();
+        mmu.write_byte(0xff4f, 1);
+        mmu.write_byte(0x8000, 0xaa);
+        mmu.write_byte(0xff4f, 0);
+        mmu.write_byte(0x8000, 0x55);
+        assert_eq!(mmu.read_byte(0x8000), 0x55);
+        mmu.write_byte(0xff4f, 1);
+        assert_eq!(mmu.read_byte(0x8000), 0xaa);
+        assert_eq!(mmu.read_byte(0xff4f), 0xff);
+
+        for bank in 1..8 {
+            mmu.write_byte(0xff70, bank);
+            mmu.write_byte(0xd000, bank * 0x11);
+        }
+        // bank 0 selects bank 1
+        mmu.write_byte(0xff70, 0);
+        assert_eq!(mmu.read_byte(0xd000), 0x11);
+        assert_eq!(mmu.read_byte(0xff70), 0xf8);
+        mmu.write_byte(0xff70, 5);
+        assert_eq!(mmu.read_byte(0xd000), 0x55);
+        assert_eq!(mmu.read_byte(0xc000), 0);
+    }
+
+    #[test]
+    fn general_purpose_vram_dma() {
+        let mut mmu = cgb_mmu();
+        prepare_vram_dma(&mut mmu);
+        // two blocks
+        mmu.write_byte(0xff55, 0x01);
+
+        for i in 0..0x20 {
+            assert_eq!(mmu.read_byte(0x8100 + i), i as u8 + 1);
+        }
+        assert_eq!(mmu.read_byte(0x8120), 0);
+        assert_eq!(mmu.read_byte(0xff55), 0xff);
+        assert!(mmu.dma_stall > 0);
+    }
+
+    #[test]
+    fn hblank_vram_dma_and_cancel() {
+        let mut mmu = cgb_mmu();
+        mmu.write_byte(0xff40, 0x80);
+        prepare_vram_dma(&mut mmu);
+        // three blocks, one per HBlank
+        mmu.write_byte(0xff55, 0x82);
+        assert_eq!(mmu.read_byte(0xff55), 0x02);
+
+        while mmu.read_byte(0xff55) == 0x02 {
+            mmu.tick();
+        }
+        assert_eq!(mmu.read_byte(0xff55), 0x01);
+        assert_eq!(mmu.read_byte(0x810f), 0x10);
+        assert_eq!(mmu.read_byte(0x8110), 0);
+
+        // cancelling keeps the remaining length and sets bit 7
+        mmu.write_byte(0xff55, 0x00);
+        assert_eq!(mmu.read_byte(0xff55), 0x81);
+        for _ in 0..1000 {
+            mmu.tick();
         }
+        assert_eq!(mmu.read_byte(0xff55), 0x81);
+        assert_eq!(mmu.read_byte(0x8110), 0);
     }
 }
@@ -0,0 +1,134 @@
+/// Game Boy model, which decides the available hardware and the state after booting
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Model {
+    /// Early Game Boy with a different boot rom
+    Dmg0,
+    /// The original Game Boy
+    Dmg,
+    /// Game Boy Pocket and Light
+    Mgb,
+    /// Super Game Boy, which colors the screen and draws a border around it
+    Sgb,
+    /// Super Game Boy 2
+    Sgb2,
+    /// Early Game Boy Color
+    Cgb0,
+    /// Game Boy Color
+    Cgb,
+    /// Game Boy Advance, running Game Boy games
+    Agb,
+}
+
+impl Model {
+    pub fn is_sgb(self) -> bool {
+        matches!(self, Model::Sgb | Model::Sgb2)
+    }
+
+    pub fn is_cgb(self) -> bool {
+        matches!(self, Model::Cgb0 | Model::Cgb | Model::Agb)
+    }
+
+    /// Size of the boot rom. The CGB boot roms are mapped to 0x0000-0x00ff and
+    /// 0x0200-0x08ff, which leaves the cartridge header visible.
+    pub fn boot_rom_size(self) -> usize {
+        if self.is_cgb() {
+            0x900
+        } else {
+            0x100
+        }
+    }
+
+    /// The internal counter behind DIV when the boot rom ends, as the boot_div tests of
+    /// mooneye expect it. The CGB boot roms run longer for monochrome games, as they look
+    /// up their palette.
+    pub(crate) fn boot_divider(self, cgb_mode: bool) -> u16 {
+        match self {
+            Model::Dmg0 => 0x1800,
+            Model::Dmg | Model::Mgb => 0xabcc,
+            Model::Sgb | Model::Sgb2 => 0xd85c,
+            Model::Cgb0 | Model::Cgb | Model::Agb if cgb_mode => 0x1ea0,
+            Model::Cgb0 | Model::Cgb | Model::Agb => 0x267c,
+        }
+    }
+
+    /// The I/O registers which the boot rom leaves behind, in the order to write them
+    pub(crate) fn boot_io(self) -> &'static [(u16, u8)] {
+        match self {
+            Model::Dmg0 | Model::Dmg | Model::Mgb => &DMG_IO,
+            Model::Sgb | Model::Sgb2 => &SGB_IO,
+            Model::Cgb0 | Model::Cgb | Model::Agb => &CGB_IO,
+        }
+    }
+}
+
+// The DMG boot rom plays its sound on pulse channel 1, which is still running afterwards
+const DMG_IO: [(u16, u8); 20] = [
+    (0xff02, 0x7e),
+    (0xff10, 0x80),
+    (0xff11, 0xbf),
+    (0xff12, 0xf3),
+    (0xff14, 0xbf),
+    (0xff16, 0x3f),
+    (0xff19, 0xbf),
+    (0xff1a, 0x7f),
+    (0xff1b, 0xff),
+    (0xff1c, 0x9f),
+    (0xff1e, 0xff),
+    (0xff20, 0xff),
+    (0xff23, 0xbf),
+    (0xff24, 0x77),
+    (0xff25, 0xf3),
+    (0xff26, 0xf1),
+    (0xff40, 0x91),
+    (0xff47, 0xfc),
+    (0xff48, 0xff),
+    (0xff49, 0xff),
+];
+
+// The SGB plays no sound on boot, so no channel is triggered
+const SGB_IO: [(u16, u8); 20] = [
+    (0xff02, 0x7e),
+    (0xff10, 0x80),
+    (0xff11, 0xbf),
+    (0xff12, 0xf3),
+    (0xff14, 0x3f),
+    (0xff16, 0x3f),
+    (0xff19, 0xbf),
+    (0xff1a, 0x7f),
+    (0xff1b, 0xff),
+    (0xff1c, 0x9f),
+    (0xff1e, 0xff),
+    (0xff20, 0xff),
+    (0xff23, 0xbf),
+    (0xff24, 0x77),
+    (0xff25, 0xf3),
+    (0xff26, 0xf0),
+    (0xff40, 0x91),
+    (0xff47, 0xfc),
+    (0xff48, 0xff),
+    (0xff49, 0xff),
+];
+
+// Like the DMG, but the serial clock is left on the internal one
+const CGB_IO: [(u16, u8); 20] = [
+    (0xff02, 0x7f),
+    (0xff10, 0x80),
+    (0xff11, 0xbf),
+    (0xff12, 0xf3),
+    (0xff14, 0xbf),
+    (0xff16, 0x3f),
+    (0xff19, 0xbf),
+    (0xff1a, 0x7f),
+    (0xff1b, 0xff),
+    (0xff1c, 0x9f),
+    (0xff1e, 0xff),
+    (0xff20, 0xff),
+    (0xff23, 0xbf),
+    (0xff24, 0x77),
+    (0xff25, 0xf3),
+    (0xff26, 0xf1),
+    (0xff40, 0x91),
+    (0xff47, 0xfc),
+    (0xff48, 0xff),
+    (0xff49, 0xff),
+];
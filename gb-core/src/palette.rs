@@ -0,0 +1,402 @@
+use alloc::vec::Vec;
+
+use crate::ppu::Color;
+
+/// Colors of the four shades of the background and the two object palettes, which
+/// monochrome games select through BGP, OBP0 and OBP1
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DmgPalette {
+    pub bg: [Color; 4],
+    pub obj0: [Color; 4],
+    pub obj1: [Color; 4],
+}
+
+impl DmgPalette {
+    /// Evenly spaced grey shades
+    pub const GREY: DmgPalette = Self::uniform(colors([0xffffff, 0xaaaaaa, 0x555555, 0x000000]));
+    /// The green tint of the original Game Boy
+    pub const PEA_SOUP: DmgPalette =
+        Self::uniform(colors([0x9bbc0f, 0x8bac0f, 0x306230, 0x0f380f]));
+    /// The screen of the Game Boy Pocket
+    pub const POCKET: DmgPalette = Self::uniform(colors([0xc4cfa1, 0x8b956d, 0x4d533c, 0x1f1f1f]));
+    /// The backlit screen of the Game Boy Light
+    pub const LIGHT: DmgPalette = Self::uniform(colors([0x00b581, 0x009a71, 0x00694a, 0x004f3b]));
+
+    /// Uses the same colors for background and objects
+    pub const fn uniform(colors: [Color; 4]) -> Self {
+        Self {
+            bg: colors,
+            obj0: colors,
+            obj1: colors,
+        }
+    }
+}
+
+impl Default for DmgPalette {
+    fn default() -> Self {
+        Self::GREY
+    }
+}
+
+/// Palettes which the CGB boot rom lets the player choose for monochrome games, named
+/// after the button combination held during the boot logo
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CompatPalette {
+    Up,
+    UpA,
+    UpB,
+    Left,
+    LeftA,
+    LeftB,
+    Down,
+    DownA,
+    DownB,
+    Right,
+    RightA,
+    RightB,
+}
+
+impl CompatPalette {
+    pub const ALL: [CompatPalette; 12] = [
+        CompatPalette::Up,
+        CompatPalette::UpA,
+        CompatPalette::UpB,
+        CompatPalette::Left,
+        CompatPalette::LeftA,
+        CompatPalette::LeftB,
+        CompatPalette::Down,
+        CompatPalette::DownA,
+        CompatPalette::DownB,
+        CompatPalette::Right,
+        CompatPalette::RightA,
+        CompatPalette::RightB,
+    ];
+
+    pub fn palette(self) -> DmgPalette {
+        let combination = match self {
+            CompatPalette::Up => 5,
+            CompatPalette::UpA => 43,
+            CompatPalette::UpB => 28,
+            CompatPalette::Left => 48,
+            CompatPalette::LeftA => 40,
+            CompatPalette::LeftB => 7,
+            CompatPalette::Down => 8,
+            CompatPalette::DownA => 3,
+            CompatPalette::DownB => 49,
+            CompatPalette::Right => 1,
+            CompatPalette::RightA => 0,
+            CompatPalette::RightB => 6,
+        };
+        combination_palette(combination)
+    }
+}
+
+// The 15 bit colors in the boot rom, four per palette
+const BOOT_COLORS: [u16; 120] = [
+    0x7fff, 0x32bf, 0x00d0, 0x0000, // 0
+    0x639f, 0x4279, 0x15b0, 0x04cb, // 1
+    0x7fff, 0x6e31, 0x454a, 0x0000, // 2
+    0x7fff, 0x1bef, 0x0200, 0x0000, // 3
+    0x7fff, 0x421f, 0x1cf2, 0x0000, // 4
+    0x7fff, 0x5294, 0x294a, 0x0000, // 5
+    0x7fff, 0x03ff, 0x012f, 0x0000, // 6
+    0x7fff, 0x03ef, 0x01d6, 0x0000, // 7
+    0x7fff, 0x42b5, 0x3dc8, 0x0000, // 8
+    0x7e74, 0x03ff, 0x0180, 0x0000, // 9
+    0x67ff, 0x77ac, 0x1a13, 0x2d6b, // 10
+    0x7ed6, 0x4bff, 0x2175, 0x0000, // 11
+    0x53ff, 0x4a5f, 0x7e52, 0x0000, // 12
+    0x4fff, 0x7ed2, 0x3a4c, 0x1ce0, // 13
+    0x03ed, 0x7fff, 0x255f, 0x0000, // 14
+    0x036a, 0x021f, 0x03ff, 0x7fff, // 15
+    0x7fff, 0x01df, 0x0112, 0x0000, // 16
+    0x231f, 0x035f, 0x00f2, 0x0009, // 17
+    0x7fff, 0x03ea, 0x011f, 0x0000, // 18
+    0x299f, 0x001a, 0x000c, 0x0000, // 19
+    0x7fff, 0x027f, 0x001f, 0x0000, // 20
+    0x7fff, 0x03e0, 0x0206, 0x0120, // 21
+    0x7fff, 0x7eeb, 0x001f, 0x7c00, // 22
+    0x7fff, 0x3fff, 0x7e00, 0x001f, // 23
+    0x7fff, 0x03ff, 0x001f, 0x0000, // 24
+    0x03ff, 0x001f, 0x000c, 0x0000, // 25
+    0x7fff, 0x033f, 0x0193, 0x0000, // 26
+    0x0000, 0x4200, 0x037f, 0x7fff, // 27
+    0x7fff, 0x7e8c, 0x7c00, 0x0000, // 28
+    0x7fff, 0x1bef, 0x6180, 0x0000, // 29
+];
+
+// Combinations of an OBJ0, OBJ1 and BG palette, given as the index of their first color
+// in BOOT_COLORS. A few start in the middle of a palette, like the boot rom has them.
+const COMBINATIONS: [[u8; 3]; 51] = [
+    combination(4 * 4, 4 * 4, 29 * 4),         // 0, Right + A
+    combination(18 * 4, 18 * 4, 18 * 4),       // 1, Right
+    combination(20 * 4, 20 * 4, 20 * 4),       // 2
+    combination(24 * 4, 24 * 4, 24 * 4),       // 3, Down + A
+    combination(9 * 4, 9 * 4, 9 * 4),          // 4
+    combination(0, 0, 0),                      // 5, Up
+    combination(27 * 4, 27 * 4, 27 * 4),       // 6, Right + B
+    combination(5 * 4, 5 * 4, 5 * 4),          // 7, Left + B
+    combination(12 * 4, 12 * 4, 12 * 4),       // 8, Down
+    combination(26 * 4, 26 * 4, 26 * 4),       // 9
+    combination(16 * 4, 8 * 4, 8 * 4),         // 10
+    combination(4 * 4, 28 * 4, 28 * 4),        // 11
+    combination(4 * 4, 2 * 4, 2 * 4),          // 12
+    combination(3 * 4, 4 * 4, 4 * 4),          // 13
+    combination(4 * 4, 29 * 4, 29 * 4),        // 14
+    combination(28 * 4, 4 * 4, 28 * 4),        // 15
+    combination(2 * 4, 17 * 4, 2 * 4),         // 16
+    combination(16 * 4, 16 * 4, 8 * 4),        // 17
+    combination(4 * 4, 4 * 4, 7 * 4),          // 18
+    combination(4 * 4, 4 * 4, 18 * 4),         // 19
+    combination(4 * 4, 4 * 4, 20 * 4),         // 20
+    combination(19 * 4, 19 * 4, 9 * 4),        // 21
+    combination(4 * 4 - 1, 4 * 4 - 1, 11 * 4), // 22
+    combination(17 * 4, 17 * 4, 2 * 4),        // 23
+    combination(4 * 4, 4 * 4, 2 * 4),          // 24
+    combination(4 * 4, 4 * 4, 3 * 4),          // 25
+    combination(28 * 4, 28 * 4, 0),            // 26
+    combination(3 * 4, 3 * 4, 0),              // 27
+    combination(0, 0, 4),                      // 28, Up + B
+    combination(18 * 4, 22 * 4, 18 * 4),       // 29
+    combination(20 * 4, 22 * 4, 20 * 4),       // 30
+    combination(24 * 4, 22 * 4, 24 * 4),       // 31
+    combination(16 * 4, 22 * 4, 8 * 4),        // 32
+    combination(17 * 4, 4 * 4, 13 * 4),        // 33
+    combination(28 * 4 - 1, 0, 14 * 4),        // 34
+    combination(28 * 4 - 1, 4 * 4, 15 * 4),    // 35
+    combination(19 * 4, 23 * 4 - 1, 9 * 4),    // 36
+    combination(16 * 4, 28 * 4, 10 * 4),       // 37
+    combination(4 * 4, 23 * 4, 28 * 4),        // 38
+    combination(17 * 4, 22 * 4, 2 * 4),        // 39
+    combination(4 * 4, 0, 2 * 4),              // 40, Left + A
+    combination(4 * 4, 28 * 4, 3 * 4),         // 41
+    combination(28 * 4, 3 * 4, 0),             // 42
+    combination(3 * 4, 28 * 4, 4 * 4),         // 43, Up + A
+    combination(21 * 4, 28 * 4, 4 * 4),        // 44
+    combination(3 * 4, 28 * 4, 0),             // 45
+    combination(25 * 4, 3 * 4, 28 * 4),        // 46
+    combination(0, 28 * 4, 8 * 4),             // 47
+    combination(4 * 4, 3 * 4, 28 * 4),         // 48, Left
+    combination(28 * 4, 3 * 4, 6 * 4),         // 49, Down + B
+    combination(4 * 4, 28 * 4, 29 * 4),        // 50
+];
+
+const fn combination(obj0: u8, obj1: u8, bg: u8) -> [u8; 3] {
+    [obj0, obj1, bg]
+}
+
+fn combination_palette(index: usize) -> DmgPalette {
+    let [obj0, obj1, bg] = COMBINATIONS[index];
+    let palette = |first: u8| {
+        let first = first as usize;
+        let mut colors = [Color::rgb(0); 4];
+        for (color, &value) in colors.iter_mut().zip(&BOOT_COLORS[first..first + 4]) {
+            *color = rgb555(value);
+        }
+        colors
+    };
+    DmgPalette {
+        bg: palette(bg),
+        obj0: palette(obj0),
+        obj1: palette(obj1),
+    }
+}
+
+// Games of Nintendo are identified by the checksum of their title. The checksums from
+// TITLES_WITH_LETTER on are shared by several titles, so they only match if the fourth
+// letter of the title matches too.
+const TITLE_CHECKSUMS: [u8; 94] = [
+    0x00, 0x88, 0x16, 0x36, 0xd1, 0xdb, 0xf2, 0x3c, 0x8c, 0x92, 0x3d, 0x5c, 0x58, 0xc9, 0x3e, 0x70,
+    0x1d, 0x59, 0x69, 0x19, 0x35, 0xa8, 0x14, 0xaa, 0x75, 0x95, 0x99, 0x34, 0x6f, 0x15, 0xff, 0x97,
+    0x4b, 0x90, 0x17, 0x10, 0x39, 0xf7, 0xf6, 0xa2, 0x49, 0x4e, 0x43, 0x68, 0xe0, 0x8b, 0xf0, 0xce,
+    0x0c, 0x29, 0xe8, 0xb7, 0x86, 0x9a, 0x52, 0x01, 0x9d, 0x71, 0x9c, 0xbd, 0x5d, 0x6d, 0x67, 0x3f,
+    0x6b, // fourth letter from here on
+    0xb3, 0x46, 0x28, 0xa5, 0xc6, 0xd3, 0x27, 0x61, 0x18, 0x66, 0x6a, 0xbf, 0x0d, 0xf4, 0xb3, 0x46,
+    0x28, 0xa5, 0xc6, 0xd3, 0x27, 0x61, 0x18, 0x66, 0x6a, 0xbf, 0x0d, 0xf4, 0xb3,
+];
+
+const TITLES_WITH_LETTER: usize = 65;
+
+const FOURTH_LETTERS: &[u8; 29] = b"BEFAARBEKEK R-URAR INAILICE R";
+
+// Palette combination of every entry in TITLE_CHECKSUMS
+const TITLE_COMBINATIONS: [u8; 94] = [
+    0, 4, 5, 35, 34, 3, 31, 15, 10, 5, 19, 36, 7, 37, 30, 44, 21, 32, 31, 20, 5, 33, 13, 14, 5, 29,
+    5, 18, 9, 3, 2, 26, 25, 25, 41, 42, 26, 45, 42, 45, 36, 38, 26, 42, 30, 41, 34, 34, 5, 42, 6,
+    5, 33, 25, 42, 42, 40, 2, 16, 25, 42, 42, 5, 0, 39, // fourth letter from here on
+    36, 22, 25, 6, 32, 12, 36, 11, 39, 18, 39, 24, 31, 50, 17, 46, 6, 27, 0, 47, 41, 41, 0, 0, 19,
+    34, 23, 18, 29,
+];
+
+/// Picks the palette which the CGB boot rom assigns to a monochrome game, given the
+/// cartridge header at 0x0100-0x014f
+pub(crate) fn title_palette(header: &[u8]) -> DmgPalette {
+    let checksum = match title_checksum(header) {
+        Some(checksum) => checksum,
+        None => return CompatPalette::RightA.palette(),
+    };
+
+    let fourth_letter = header[0x37];
+    let entry = TITLE_CHECKSUMS.iter().enumerate().position(|(i, &sum)| {
+        sum == checksum
+            && (i < TITLES_WITH_LETTER || FOURTH_LETTERS[i - TITLES_WITH_LETTER] == fourth_letter)
+    });
+    // games which aren't listed get the first combination
+    combination_palette(entry.map_or(0, |i| TITLE_COMBINATIONS[i] as usize))
+}
+
+/// The sum of the title bytes, which the CGB boot rom only computes for games of Nintendo
+pub(crate) fn title_checksum(header: &[u8]) -> Option<u8> {
+    let old_licensee = header[0x4b];
+    let new_licensee = &header[0x44..0x46];
+    let nintendo = old_licensee == 0x01 || (old_licensee == 0x33 && new_licensee == b"01");
+    if !nintendo {
+        return None;
+    }
+    let title = &header[0x34..0x44];
+    Some(title.iter().fold(0u8, |sum, &c| sum.wrapping_add(c)))
+}
+
+// The LCD of the CGB shows colors with a higher gamma than regular displays
+const LCD_GAMMA: f64 = 2.5;
+const DISPLAY_GAMMA: f64 = 2.2;
+
+// Share of the red, green and blue input in every output channel, as the colors of the
+// LCD bleed into each other
+const CHANNEL_BLEED: [[f64; 3]; 3] = [
+    [26.0 / 32.0, 4.0 / 32.0, 2.0 / 32.0],
+    [0.0, 24.0 / 32.0, 8.0 / 32.0],
+    [6.0 / 32.0, 4.0 / 32.0, 22.0 / 32.0],
+];
+
+/// Converts every 15 bit CGB color, optionally to what the real LCD shows
+pub(crate) fn cgb_colors(color_correction: bool) -> Vec<Color> {
+    (0..0x8000)
+        .map(|value| {
+            if color_correction {
+                lcd_color(value)
+            } else {
+                rgb555(value)
+            }
+        })
+        .collect()
+}
+
+pub(crate) fn rgb555(value: u16) -> Color {
+    let scale = |c: u16| ((c << 3) | (c >> 2)) as u8;
+    Color {
+        r: scale(value & 0x1f),
+        g: scale((value >> 5) & 0x1f),
+        b: scale((value >> 10) & 0x1f),
+    }
+}
+
+fn lcd_color(value: u16) -> Color {
+    // libm, because floating point functions need std
+    let linear = |shift: u16| libm::pow(((value >> shift) & 0x1f) as f64 / 31.0, LCD_GAMMA);
+    let input = [linear(0), linear(5), linear(10)];
+    let output = |weights: [f64; 3]| {
+        let mixed: f64 = weights.iter().zip(input.iter()).map(|(w, c)| w * c).sum();
+        libm::round(libm::pow(mixed, 1.0 / DISPLAY_GAMMA) * 255.0) as u8
+    };
+    Color {
+        r: output(CHANNEL_BLEED[0]),
+        g: output(CHANNEL_BLEED[1]),
+        b: output(CHANNEL_BLEED[2]),
+    }
+}
+
+const fn colors(values: [u32; 4]) -> [Color; 4] {
+    [
+        Color::rgb(values[0]),
+        Color::rgb(values[1]),
+        Color::rgb(values[2]),
+        Color::rgb(values[3]),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, vec::Vec};
+
+    use super::{cgb_colors, rgb555, title_palette, CompatPalette, DmgPalette};
+    use crate::ppu::Color;
+
+    fn header(title: &[u8], licensee: u8) -> Vec<u8> {
+        let mut header = vec![0u8; 0x50];
+        header[0x34..0x34 + title.len()].copy_from_slice(title);
+        header[0x4b] = licensee;
+        header
+    }
+
+    fn boot(values: [u16; 4]) -> [Color; 4] {
+        values.map(rgb555)
+    }
+
+    #[test]
+    fn palette_by_title_checksum() {
+        let red = title_palette(&header(b"POKEMON RED", 0x01));
+        assert_eq!(red.bg, boot([0x7fff, 0x421f, 0x1cf2, 0x0000]));
+        assert_eq!(red.obj0, boot([0x7fff, 0x1bef, 0x0200, 0x0000]));
+        assert_eq!(red.obj1, red.bg);
+
+        // the sky of SUPER MARIOLAND, with objects in a palette that starts one color early
+        let mario = title_palette(&header(b"SUPER MARIOLAND", 0x01));
+        assert_eq!(mario.bg, boot([0x7ed6, 0x4bff, 0x2175, 0x0000]));
+        assert_eq!(mario.obj0, boot([0x0000, 0x7fff, 0x421f, 0x1cf2]));
+
+        let unknown = header(b"HOMEBREW GAME", 0x01);
+        assert_eq!(title_palette(&unknown), CompatPalette::RightA.palette());
+
+        let other_licensee = header(b"POKEMON RED", 0x08);
+        assert_eq!(
+            title_palette(&other_licensee),
+            CompatPalette::RightA.palette()
+        );
+    }
+
+    #[test]
+    fn fourth_letter_resolves_shared_checksums() {
+        // both titles add up to 0x61
+        let blue = title_palette(&header(b"POKEMON BLUE", 0x01));
+        let vegas = title_palette(&header(b"VEGAS STAKES", 0x01));
+        assert_eq!(blue.bg, boot([0x7fff, 0x7e8c, 0x7c00, 0x0000]));
+        assert_eq!(blue.obj0, boot([0x7fff, 0x421f, 0x1cf2, 0x0000]));
+        assert_eq!(vegas.bg, boot([0x7fff, 0x1bef, 0x0200, 0x0000]));
+        assert_eq!(vegas.obj1, blue.bg);
+
+        // the same checksum with a fourth letter which isn't listed
+        let mut other = header(b"POKEMON BLUE", 0x01);
+        other[0x37] = b'X';
+        other[0x38] = other[0x38].wrapping_add(b'E').wrapping_sub(b'X');
+        assert_eq!(title_palette(&other), CompatPalette::RightA.palette());
+    }
+
+    #[test]
+    fn button_palettes() {
+        let left = CompatPalette::Left.palette();
+        assert_eq!(left.bg, boot([0x7fff, 0x7e8c, 0x7c00, 0x0000]));
+        let up_b = CompatPalette::UpB.palette();
+        assert_eq!(up_b.bg, boot([0x639f, 0x4279, 0x15b0, 0x04cb]));
+        assert_eq!(up_b.obj0, boot([0x7fff, 0x32bf, 0x00d0, 0x0000]));
+        let right_b = CompatPalette::RightB.palette();
+        assert_eq!(
+            right_b,
+            DmgPalette::uniform(boot([0x0000, 0x4200, 0x037f, 0x7fff]))
+        );
+    }
+
+    #[test]
+    fn color_correction() {
+        let plain = cgb_colors(false);
+        assert_eq!(plain[0x7fff], Color::rgb(0xffffff));
+        assert_eq!(plain[0x001f], Color::rgb(0xff0000));
+
+        let corrected = cgb_colors(true);
+        assert_eq!(corrected[0x7fff], Color::rgb(0xffffff));
+        assert_eq!(corrected[0x0000], Color::rgb(0x000000));
+        // pure red bleeds into the blue channel and is dimmed
+        let red = corrected[0x001f];
+        assert!(red.r < 0xff && red.g == 0 && red.b > 0);
+    }
+}
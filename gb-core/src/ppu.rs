@@ -1,12 +1,16 @@
-use std::cell::RefCell;
-use std::rc::Rc;
+use alloc::{boxed::Box, vec::Vec};
 
 use log::error;
 
 use crate::irq::Irq;
+use crate::palette::{self, DmgPalette};
+use crate::state::{Snapshot, State};
 
 const VRAM_SIZE: usize = 0x4000;
+const VRAM_BANK_SIZE: usize = 0x2000;
 const OAM_SIZE: usize = 0xa0;
+// 8 palettes of 4 colors with 2 bytes each
+const PALETTE_RAM_SIZE: usize = 0x40;
 
 pub const HEIGHT: usize = 144;
 pub const WIDTH: usize = 160;
@@ -21,16 +25,78 @@ enum Mode {
     Transfer,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct Color {
     pub r: u8,
     pub g: u8,
     pub b: u8,
 }
 
+/// Byte layout of the pixels written by `Board::render_into`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// Red, green and blue byte
+    Rgb24,
+    /// Red, green, blue and an opaque alpha byte
+    Rgba8888,
+    /// Blue, green, red and an opaque alpha byte, which is `ARGB8888` of SDL on little
+    /// endian machines
+    Bgra8888,
+    /// 5 bits red, 6 bits green and 5 bits blue in a little endian `u16`
+    Rgb565,
+    /// One byte holding the 2-bit shade of monochrome games before they are colored,
+    /// from 0 for the lightest to 3 for the darkest. CGB games have no shades and show 0.
+    Shades,
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Rgb24 => 3,
+            PixelFormat::Rgba8888 | PixelFormat::Bgra8888 => 4,
+            PixelFormat::Rgb565 => 2,
+            PixelFormat::Shades => 1,
+        }
+    }
+
+    /// Writes rows of `width` pixels in this format into `out`, starting every row `pitch`
+    /// bytes after the previous one. `out` has to hold all of them.
+    pub(crate) fn write(
+        self,
+        pixels: impl Iterator<Item = (Color, u8)>,
+        width: usize,
+        out: &mut [u8],
+        pitch: usize,
+    ) {
+        let row_size = width * self.bytes_per_pixel();
+        let out = out
+            .chunks_mut(pitch)
+            .flat_map(|row| row[..row_size].chunks_exact_mut(self.bytes_per_pixel()));
+        for ((color, shade), out) in pixels.zip(out) {
+            match self {
+                PixelFormat::Rgb24 => out.copy_from_slice(&[color.r, color.g, color.b]),
+                PixelFormat::Rgba8888 => out.copy_from_slice(&[color.r, color.g, color.b, 0xff]),
+                PixelFormat::Bgra8888 => out.copy_from_slice(&[color.b, color.g, color.r, 0xff]),
+                PixelFormat::Rgb565 => {
+                    let value = ((color.r as u16 >> 3) << 11)
+                        | ((color.g as u16 >> 2) << 5)
+                        | (color.b as u16 >> 3);
+                    out.copy_from_slice(&value.to_le_bytes());
+                }
+                PixelFormat::Shades => out[0] = shade,
+            }
+        }
+    }
+}
+
 impl Color {
-    fn black() -> Self {
-        Color { r: 0, g: 0, b: 0 }
+    /// Creates a color from a hex value like 0xrrggbb
+    pub const fn rgb(value: u32) -> Self {
+        Color {
+            r: (value >> 16) as u8,
+            g: (value >> 8) as u8,
+            b: value as u8,
+        }
     }
 
     fn white() -> Self {
@@ -40,30 +106,42 @@ impl Color {
             b: 0xff,
         }
     }
+}
 
-    fn lightgrey() -> Self {
-        Color {
-            r: 0xaa,
-            g: 0xaa,
-            b: 0xaa,
-        }
+/// Attributes of a background or window tile, which are stored in VRAM bank 1 on the CGB
+#[derive(Copy, Clone)]
+struct TileAttributes(u8);
+
+impl TileAttributes {
+    fn palette(self) -> u8 {
+        self.0 & 0x07
     }
 
-    fn darkgrey() -> Self {
-        Color {
-            r: 0x55,
-            g: 0x55,
-            b: 0x55,
-        }
+    fn bank(self) -> usize {
+        ((self.0 >> 3) & 1) as usize
+    }
+
+    fn x_flip(self) -> bool {
+        (self.0 & (1 << 5)) != 0
+    }
+
+    fn y_flip(self) -> bool {
+        (self.0 & (1 << 6)) != 0
+    }
+
+    fn bg_priority(self) -> bool {
+        (self.0 & (1 << 7)) != 0
     }
 }
 
 /// Picture Processing Unit
 pub(crate) struct Ppu {
-    irq: Rc<RefCell<Irq>>,
+    // whether the CGB features are enabled
+    cgb: bool,
 
-    // video ram
+    // video ram, the CGB has two banks
     vram: [u8; VRAM_SIZE],
+    vram_bank: usize,
 
     // sprite attribute table
     oam: [u8; OAM_SIZE],
@@ -87,17 +165,42 @@ pub(crate) struct Ppu {
     obp0: u8, // gray shades of objects
     obp1: u8, // gray shades of objects
 
+    // colors of the shades of monochrome games
+    dmg_palette: DmgPalette,
+
+    // CGB color palettes, every color is stored as RGB555 in little endian
+    bg_palette_ram: [u8; PALETTE_RAM_SIZE],
+    obj_palette_ram: [u8; PALETTE_RAM_SIZE],
+    bcps: u8, // background palette index, bit 7 enables auto increment
+    ocps: u8, // object palette index, bit 7 enables auto increment
+    // output color of every 15 bit color value
+    cgb_colors: Vec<Color>,
+
+    // color index and priority of the background pixels of the current line, which
+    // decide whether sprites are drawn on top
+    line_color_indices: [u8; WIDTH],
+    line_bg_priority: [bool; WIDTH],
+
     // emulator internal position of the current mode
     clock: usize,
 
-    screen: [[Color; WIDTH]; HEIGHT],
+    // set when HBlank starts, which lets the VRAM DMA copy its next block
+    hblank_started: bool,
+    vblank_started: bool,
+
+    // the screen buffers are boxed to keep the board small enough to be moved around on
+    // the stack
+    screen: Box<[[Color; WIDTH]; HEIGHT]>,
+    // the monochrome shades behind the colors of the screen, which the SGB colors
+    shades: Box<[[u8; WIDTH]; HEIGHT]>,
 }
 
 impl Ppu {
-    pub fn new(irq: Rc<RefCell<Irq>>) -> Self {
+    pub fn new(cgb: bool) -> Self {
         Self {
-            irq,
+            cgb,
             vram: [0; VRAM_SIZE],
+            vram_bank: 0,
             oam: [0; OAM_SIZE],
             lcdc: 0,
             stat: 0,
@@ -110,45 +213,87 @@ impl Ppu {
             bgp: 0,
             obp0: 0,
             obp1: 0,
+            dmg_palette: DmgPalette::default(),
+            // the boot rom initializes all background colors to white
+            bg_palette_ram: [0xff; PALETTE_RAM_SIZE],
+            obj_palette_ram: [0; PALETTE_RAM_SIZE],
+            bcps: 0,
+            ocps: 0,
+            cgb_colors: palette::cgb_colors(false),
+            line_color_indices: [0; WIDTH],
+            line_bg_priority: [false; WIDTH],
             clock: 0,
-            screen: [[Color::white(); WIDTH]; HEIGHT],
+            hblank_started: false,
+            vblank_started: false,
+            screen: Box::new([[Color::white(); WIDTH]; HEIGHT]),
+            shades: Box::new([[0; WIDTH]; HEIGHT]),
         }
     }
 
-    pub fn frame(&self) -> [[Color; WIDTH]; HEIGHT] {
-        self.screen
+    pub fn frame(&self) -> &[[Color; WIDTH]; HEIGHT] {
+        &self.screen
+    }
+
+    pub fn shades(&self) -> &[[u8; WIDTH]; HEIGHT] {
+        &self.shades
+    }
+
+    pub fn set_dmg_palette(&mut self, palette: DmgPalette) {
+        self.dmg_palette = palette;
+    }
+
+    pub fn set_color_correction(&mut self, enabled: bool) {
+        self.cgb_colors = palette::cgb_colors(enabled);
     }
 
     /// The emulator is driven by the CPU and the other components have to catch up.
-    pub fn step(&mut self, steps: u8) {
+    pub fn step(&mut self, steps: u8, irq: &mut Irq) {
         if !self.lcd_enabled() {
             return;
         }
         for _ in 0..steps {
-            self.single_step();
+            self.single_step(irq);
         }
     }
 
-    fn single_step(&mut self) {
+    pub fn vram_bank(&self) -> usize {
+        self.vram_bank
+    }
+
+    /// Returns whether HBlank started since the last call
+    pub fn take_hblank_started(&mut self) -> bool {
+        core::mem::replace(&mut self.hblank_started, false)
+    }
+
+    /// Returns whether VBlank started since the last call
+    pub fn take_vblank_started(&mut self) -> bool {
+        core::mem::replace(&mut self.vblank_started, false)
+    }
+
+    pub fn ly(&self) -> u8 {
+        self.ly
+    }
+
+    fn single_step(&mut self, irq: &mut Irq) {
         self.clock = (self.clock + 1) % 456;
         if self.clock == 0 {
             self.ly = (self.ly + 1) % 154;
-            self.set_lyc_ly_flag();
+            self.set_lyc_ly_flag(irq);
         }
 
         match (self.clock, self.ly as usize) {
             (0, 0..HEIGHT) => {
-                self.set_mode(Mode::OAMSearch);
+                self.set_mode(Mode::OAMSearch, irq);
             }
             (OAM_SCAN_END, 0..HEIGHT) => {
-                self.set_mode(Mode::Transfer);
+                self.set_mode(Mode::Transfer, irq);
             }
             (TRANSFER_END, 0..HEIGHT) => {
-                self.set_mode(Mode::HBlank);
+                self.set_mode(Mode::HBlank, irq);
                 self.draw_line();
             }
             (0, HEIGHT) => {
-                self.set_mode(Mode::VBlank);
+                self.set_mode(Mode::VBlank, irq);
             }
             _ => {}
         }
@@ -156,7 +301,7 @@ impl Ppu {
 
     pub fn read_byte(&self, addr: u16) -> u8 {
         match addr {
-            0x8000..=0x9fff => self.vram[addr as usize - 0x8000],
+            0x8000..=0x9fff => self.vram_byte(self.vram_bank, addr),
             0xfe00..=0xfe9f => self.oam[addr as usize - 0xfe00],
             0xff40 => self.lcdc,
             0xff41 => self.stat,
@@ -169,6 +314,11 @@ impl Ppu {
             0xff49 => self.obp1,
             0xff4a => self.wy,
             0xff4b => self.wx,
+            0xff4f => self.vram_bank as u8 | 0xfe,
+            0xff68 => self.bcps | 0x40,
+            0xff69 => self.bg_palette_ram[(self.bcps & 0x3f) as usize],
+            0xff6a => self.ocps | 0x40,
+            0xff6b => self.obj_palette_ram[(self.ocps & 0x3f) as usize],
             _ => {
                 error!("Unimplemented read byte from addr {:04x}", addr);
                 unimplemented!();
@@ -178,7 +328,9 @@ impl Ppu {
 
     pub fn write_byte(&mut self, addr: u16, val: u8) {
         match addr {
-            0x8000..=0x9fff => self.vram[addr as usize - 0x8000] = val,
+            0x8000..=0x9fff => {
+                self.vram[self.vram_bank * VRAM_BANK_SIZE + addr as usize - 0x8000] = val
+            }
             0xfe00..=0xfe9f => self.oam[addr as usize - 0xfe00] = val,
             0xff40 => self.lcdc = val,
             0xff41 => self.stat = val,
@@ -191,6 +343,17 @@ impl Ppu {
             0xff49 => self.obp1 = val,
             0xff4a => self.wy = val,
             0xff4b => self.wx = val,
+            0xff4f => self.vram_bank = (val & 1) as usize,
+            0xff68 => self.bcps = val & 0xbf,
+            0xff69 => {
+                self.bg_palette_ram[(self.bcps & 0x3f) as usize] = val;
+                self.bcps = next_palette_index(self.bcps);
+            }
+            0xff6a => self.ocps = val & 0xbf,
+            0xff6b => {
+                self.obj_palette_ram[(self.ocps & 0x3f) as usize] = val;
+                self.ocps = next_palette_index(self.ocps);
+            }
             _ => {
                 error!("Unimplemented write byte to addr {:04x}", addr);
                 unimplemented!();
@@ -198,14 +361,16 @@ impl Ppu {
         }
     }
 
-    fn set_mode(&mut self, mode: Mode) {
+    fn set_mode(&mut self, mode: Mode, irq: &mut Irq) {
         let (fire_stat_interrupt, mask) = match mode {
             Mode::HBlank => {
+                self.hblank_started = true;
                 let interrupt = (self.stat & (1 << 3)) != 0;
                 (interrupt, 0)
             }
             Mode::VBlank => {
-                self.irq.borrow_mut().vblank_interrupt();
+                self.vblank_started = true;
+                irq.vblank_interrupt();
                 let interrupt = (self.stat & (1 << 4)) != 0;
                 (interrupt, 1)
             }
@@ -217,15 +382,15 @@ impl Ppu {
         };
         self.stat = (self.stat & 0xfc) | mask;
         if fire_stat_interrupt {
-            self.irq.borrow_mut().lcd_stat_interrupt();
+            irq.lcd_stat_interrupt();
         }
     }
 
-    fn set_lyc_ly_flag(&mut self) {
+    fn set_lyc_ly_flag(&mut self, irq: &mut Irq) {
         if self.lyc == self.ly {
             self.stat |= 1 << 2;
             if self.stat & (1 << 6) != 0 {
-                self.irq.borrow_mut().lcd_stat_interrupt();
+                irq.lcd_stat_interrupt();
             }
         } else {
             self.stat &= !(1 << 2);
@@ -292,10 +457,14 @@ impl Ppu {
         }
     }
 
-    fn tile_color_index(&self, tile_addr: u16, tile_x: u8, tile_y: u8) -> u8 {
+    fn vram_byte(&self, bank: usize, addr: u16) -> u8 {
+        self.vram[bank * VRAM_BANK_SIZE + addr as usize - 0x8000]
+    }
+
+    fn tile_color_index(&self, bank: usize, tile_addr: u16, tile_x: u8, tile_y: u8) -> u8 {
         let offset = (tile_y * 2) as u16;
-        let low_byte = self.read_byte(tile_addr + offset);
-        let high_byte = self.read_byte(tile_addr + offset + 1);
+        let low_byte = self.vram_byte(bank, tile_addr + offset);
+        let high_byte = self.vram_byte(bank, tile_addr + offset + 1);
 
         let low = (low_byte >> (7 - tile_x)) & 1;
         let high = ((high_byte >> (7 - tile_x)) & 1) << 1;
@@ -303,30 +472,59 @@ impl Ppu {
         high | low
     }
 
-    fn bg_window_color(&self, tile_id: u8, tile_x: u8, tile_y: u8) -> Color {
-        let tile_addr = self.bg_window_tile_addr(tile_id);
+    /// Looks up the tile id at an address of a tile map, together with its attributes
+    fn tile_map_entry(&self, addr: u16) -> (u8, TileAttributes) {
+        let tile_id = self.vram_byte(0, addr);
+        let attributes = if self.cgb { self.vram_byte(1, addr) } else { 0 };
+        (tile_id, TileAttributes(attributes))
+    }
 
-        let color_index = self.tile_color_index(tile_addr, tile_x, tile_y);
-        let color_value = (self.bgp >> (2 * color_index)) & 0x03;
+    fn cgb_color(&self, palette_ram: &[u8; PALETTE_RAM_SIZE], palette: u8, index: u8) -> Color {
+        let offset = (palette * 8 + index * 2) as usize;
+        let value = u16::from_le_bytes([palette_ram[offset], palette_ram[offset + 1]]);
+        self.cgb_colors[(value & 0x7fff) as usize]
+    }
 
-        match color_value {
-            0x00 => Color::white(),
-            0x01 => Color::lightgrey(),
-            0x02 => Color::darkgrey(),
-            0x03 => Color::black(),
-            _ => {
-                error!(
-                    "Color value in BGP register for index {} should be between {} and {}, but was {}",
-                    color_index, 0, 3, color_value);
-                unreachable!();
-            }
-        }
+    fn draw_bg_window_pixel(&mut self, x: usize, tile_map_addr: u16, tile_x: u8, tile_y: u8) {
+        let (tile_id, attributes) = self.tile_map_entry(tile_map_addr);
+        let tile_x = if attributes.x_flip() {
+            7 - tile_x
+        } else {
+            tile_x
+        };
+        let tile_y = if attributes.y_flip() {
+            7 - tile_y
+        } else {
+            tile_y
+        };
+
+        let tile_addr = self.bg_window_tile_addr(tile_id);
+        let color_index = self.tile_color_index(attributes.bank(), tile_addr, tile_x, tile_y);
+        let y = self.ly as usize;
+        let color = if self.cgb {
+            self.cgb_color(&self.bg_palette_ram, attributes.palette(), color_index)
+        } else {
+            let shade = dmg_shade(self.bgp, color_index);
+            self.shades[y][x] = shade;
+            self.dmg_palette.bg[shade as usize]
+        };
+
+        self.screen[y][x] = color;
+        self.line_color_indices[x] = color_index;
+        self.line_bg_priority[x] = attributes.bg_priority();
     }
 
     fn draw_line(&mut self) {
-        if self.bg_and_window_enabled() {
+        if self.bg_and_window_enabled() || self.cgb {
             self.draw_background_line();
             self.draw_window_line();
+        } else {
+            self.screen[self.ly as usize] = [self.dmg_palette.bg[0]; WIDTH];
+            self.shades[self.ly as usize] = [0; WIDTH];
+        }
+        if !self.bg_and_window_enabled() {
+            // on the CGB, background and window only lose their priority over sprites
+            self.line_color_indices = [0; WIDTH];
         }
         if self.sprites_enabled() {
             self.draw_sprites();
@@ -335,18 +533,20 @@ impl Ppu {
 
     fn draw_background_line(&mut self) {
         let y = self.ly;
+        let tile_map_base = self.bg_tile_map_base();
         for x in 0..WIDTH {
             let pixel_x = (x as u16 + self.scx as u16) % 256;
             let pixel_y = (y as u16 + self.scy as u16) % 256;
 
             let tile_map_index = (pixel_x / 8) + 32 * (pixel_y / 8);
-            let tile_map_base = self.bg_tile_map_base();
-            let tile_id = self.read_byte(tile_map_base + tile_map_index);
-
             let tile_x = pixel_x % 8;
             let tile_y = pixel_y % 8;
-            let color = self.bg_window_color(tile_id, tile_x as u8, tile_y as u8);
-            self.screen[y as usize][x as usize] = color;
+            self.draw_bg_window_pixel(
+                x,
+                tile_map_base + tile_map_index,
+                tile_x as u8,
+                tile_y as u8,
+            );
         }
     }
 
@@ -356,19 +556,21 @@ impl Ppu {
         }
 
         let y = self.ly;
-        let window_start = (if self.wx < 7 { 0 } else { self.wx - 7 }) as usize;
+        let tile_map_base = self.window_tile_map_base();
+        let window_start = self.wx.saturating_sub(7) as usize;
         for x in window_start..WIDTH {
             let pixel_x = x as u16 + 7 - self.wx as u16;
             let pixel_y = (y - self.wy) as u16;
 
             let tile_map_index = (pixel_x / 8) + 32 * (pixel_y / 8);
-            let tile_map_base = self.window_tile_map_base();
-            let tile_id = self.read_byte(tile_map_base + tile_map_index);
-
             let tile_x = pixel_x % 8;
             let tile_y = pixel_y % 8;
-            let color = self.bg_window_color(tile_id, tile_x as u8, tile_y as u8);
-            self.screen[y as usize][x as usize] = color;
+            self.draw_bg_window_pixel(
+                x,
+                tile_map_base + tile_map_index,
+                tile_x as u8,
+                tile_y as u8,
+            );
         }
     }
 
@@ -376,18 +578,31 @@ impl Ppu {
         let y = self.ly;
         let tile_y_size = self.vertical_sprite_size();
 
+        // sprites on the current line in the order of their priority. The CGB only looks
+        // at the position in OAM, the DMG prefers sprites with a lower x coordinate.
+        let mut sprites = [0u8; 40];
+        let mut count = 0;
         for sprite in 0..40 {
-            let sprite_base_addr = 0xfe00 + 4 * sprite;
+            let y_pos = self.oam[4 * sprite as usize];
+            if y + 16 >= y_pos && y + 16 - y_pos < tile_y_size {
+                sprites[count] = sprite;
+                count += 1;
+            }
+        }
+        let sprites = &mut sprites[..count];
+        if !self.cgb {
+            // sorting by the OAM index as well keeps the order without a stable sort
+            sprites.sort_unstable_by_key(|&sprite| (self.oam[4 * sprite as usize + 1], sprite));
+        }
+
+        // pixels which are taken by a sprite of higher priority
+        let mut covered = [false; WIDTH];
+
+        for &sprite in sprites.iter() {
+            let sprite_base_addr = 0xfe00 + 4 * sprite as u16;
             let y_pos = self.read_byte(sprite_base_addr);
             let x_pos = self.read_byte(sprite_base_addr + 1);
-
-            if y + 16 < y_pos {
-                continue;
-            }
             let tile_y = y + 16 - y_pos;
-            if tile_y >= tile_y_size {
-                continue;
-            }
 
             let tile_id = self.read_byte(sprite_base_addr + 2);
             let attributes = self.read_byte(sprite_base_addr + 3);
@@ -397,10 +612,15 @@ impl Ppu {
             let y_flip = (attributes & (1 << 6)) != 0;
             let bg_over_sprite = (attributes & (1 << 7)) != 0;
 
-            let palette = if palette_number == 0 {
-                self.obp0
+            let (palette, colors) = if palette_number == 0 {
+                (self.obp0, self.dmg_palette.obj0)
+            } else {
+                (self.obp1, self.dmg_palette.obj1)
+            };
+            let bank = if self.cgb {
+                ((attributes >> 3) & 1) as usize
             } else {
-                self.obp1
+                0
             };
 
             let tile_y = if y_flip {
@@ -409,26 +629,23 @@ impl Ppu {
                 tile_y
             };
 
+            // tall sprites continue in the following tile
             let tile_id = if tile_y_size == 8 {
                 tile_id
             } else {
-                if tile_y < 8 {
-                    tile_id & 0xfe
-                } else {
-                    tile_id | 1
-                }
+                tile_id & 0xfe
             } as u16;
 
-            for x in 0..WIDTH {
-                if bg_over_sprite && self.screen[y as usize][x as usize] != Color::white() {
+            for (x, covered) in covered.iter_mut().enumerate() {
+                if *covered {
                     continue;
                 }
-                let x = x as u8;
-                if x + 8 < x_pos {
+                let screen_x = x as u8;
+                if screen_x + 8 < x_pos {
                     continue;
                 }
 
-                let tile_x = x + 8 - x_pos;
+                let tile_x = screen_x + 8 - x_pos;
                 if tile_x >= 8 {
                     continue;
                 }
@@ -436,25 +653,132 @@ impl Ppu {
                 let tile_x = if x_flip { 7 - tile_x } else { tile_x };
 
                 let tile_addr = 0x8000 + tile_id * 16;
-                let color_index = self.tile_color_index(tile_addr, tile_x, tile_y);
+                let color_index = self.tile_color_index(bank, tile_addr, tile_x, tile_y);
                 if color_index == 0 {
                     // transparent
                     continue;
                 }
+                *covered = true;
 
-                let color_value = (palette >> (2 * color_index)) & 0x03;
-                let color = match color_value {
-                    0x00 => Color::white(),
-                    0x01 => Color::lightgrey(),
-                    0x02 => Color::darkgrey(),
-                    0x03 => Color::black(),
-                    _ => {
-                        unreachable!();
-                    }
+                let bg_priority = bg_over_sprite || self.line_bg_priority[x];
+                if bg_priority && self.line_color_indices[x] != 0 {
+                    continue;
+                }
+
+                let color = if self.cgb {
+                    self.cgb_color(&self.obj_palette_ram, attributes & 0x07, color_index)
+                } else {
+                    let shade = dmg_shade(palette, color_index);
+                    self.shades[y as usize][x] = shade;
+                    colors[shade as usize]
                 };
 
-                self.screen[y as usize][x as usize] = color;
+                self.screen[y as usize][x] = color;
             }
         }
     }
 }
+
+/// Looks up the shade of a color index in a palette register
+fn dmg_shade(palette: u8, color_index: u8) -> u8 {
+    (palette >> (2 * color_index)) & 0x03
+}
+
+/// Increments the index of BCPS or OCPS after a write to the palette data, if enabled
+fn next_palette_index(index: u8) -> u8 {
+    if (index & 0x80) == 0 {
+        return index;
+    }
+    0x80 | (index.wrapping_add(1) & 0x3f)
+}
+
+impl Snapshot for Ppu {
+    fn snapshot(&mut self, state: &mut State) {
+        state.bytes(&mut self.vram);
+        state.usize(&mut self.vram_bank);
+        state.bytes(&mut self.oam);
+        for register in [
+            &mut self.lcdc,
+            &mut self.stat,
+            &mut self.scy,
+            &mut self.scx,
+            &mut self.ly,
+            &mut self.lyc,
+            &mut self.wy,
+            &mut self.wx,
+            &mut self.bgp,
+            &mut self.obp0,
+            &mut self.obp1,
+            &mut self.bcps,
+            &mut self.ocps,
+        ] {
+            state.u8(register);
+        }
+        state.bytes(&mut self.bg_palette_ram);
+        state.bytes(&mut self.obj_palette_ram);
+        state.bytes(&mut self.line_color_indices);
+        for priority in self.line_bg_priority.iter_mut() {
+            state.bool(priority);
+        }
+        state.usize(&mut self.clock);
+        state.bool(&mut self.hblank_started);
+        state.bool(&mut self.vblank_started);
+        // the screen is part of the state, so that a rewound frame is shown right away
+        for color in self.screen.iter_mut().flatten() {
+            state.u8(&mut color.r);
+            state.u8(&mut color.g);
+            state.u8(&mut color.b);
+        }
+        for row in self.shades.iter_mut() {
+            state.bytes(row);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Color, PixelFormat, Ppu};
+
+    #[test]
+    fn pixel_formats() {
+        let pixel = (Color::rgb(0xff8040), 2);
+        let encode = |format: PixelFormat| {
+            let mut out = [0; 4];
+            format.write([pixel].into_iter(), 1, &mut out, 4);
+            out
+        };
+        assert_eq!(encode(PixelFormat::Rgb24), [0xff, 0x80, 0x40, 0]);
+        assert_eq!(encode(PixelFormat::Rgba8888), [0xff, 0x80, 0x40, 0xff]);
+        assert_eq!(encode(PixelFormat::Bgra8888), [0x40, 0x80, 0xff, 0xff]);
+        // 11111 100000 01000
+        assert_eq!(encode(PixelFormat::Rgb565), [0x08, 0xfc, 0, 0]);
+        assert_eq!(encode(PixelFormat::Shades), [2, 0, 0, 0]);
+
+        // the bytes between rows are left alone
+        let pixels = [(Color::rgb(0x010203), 0), (Color::rgb(0x040506), 1)];
+        let mut out = [0xaa; 7];
+        PixelFormat::Rgb24.write(pixels.into_iter(), 1, &mut out, 4);
+        assert_eq!(out, [1, 2, 3, 0xaa, 4, 5, 6]);
+    }
+
+    #[test]
+    fn palette_index_auto_increment() {
+        let mut ppu = Ppu::new(true);
+        // the index wraps around after the last byte
+        ppu.write_byte(0xff68, 0x80 | 0x3f);
+        ppu.write_byte(0xff69, 0x12);
+        ppu.write_byte(0xff69, 0x34);
+        assert_eq!(ppu.read_byte(0xff68), 0xc1);
+        ppu.write_byte(0xff68, 0x3f);
+        assert_eq!(ppu.read_byte(0xff69), 0x12);
+        ppu.write_byte(0xff68, 0x00);
+        assert_eq!(ppu.read_byte(0xff69), 0x34);
+
+        // without bit 7, writes stay at the same index
+        ppu.write_byte(0xff6a, 0x05);
+        ppu.write_byte(0xff6b, 0x56);
+        ppu.write_byte(0xff6b, 0x78);
+        assert_eq!(ppu.read_byte(0xff6a), 0x45);
+        assert_eq!(ppu.read_byte(0xff6b), 0x78);
+    }
+}
@@ -1,3 +1,7 @@
+use crate::model::Model;
+use crate::palette;
+use crate::state::{Snapshot, State};
+
 const ZERO_FLAG: u8 = 0b10000000;
 const NEGATIVE_FLAG: u8 = 0b01000000;
 const HALF_CARRY_FLAG: u8 = 0b00100000;
@@ -32,16 +36,77 @@ impl Registers {
         }
     }
 
-    pub fn no_boot() -> Self {
+    /// The registers after the boot rom of the model ends, given the cartridge header at
+    /// 0x0100-0x014f. Games tell the models apart by them, mostly by the accumulator.
+    pub fn no_boot(model: Model, header: &[u8]) -> Self {
+        // the DMG boot roms leave the half carry and carry flags set by the header checksum
+        let header_checksum_flags = if header[0x4d] == 0 {
+            ZERO_FLAG
+        } else {
+            ZERO_FLAG | HALF_CARRY_FLAG | CARRY_FLAG
+        };
+        let cgb_mode = (header[0x43] & 0x80) != 0;
+
+        let [a, f, b, c, d, e, h, l] = match model {
+            Model::Dmg0 => [0x01, 0x00, 0xff, 0x13, 0x00, 0xc1, 0x84, 0x03],
+            Model::Dmg => [
+                0x01,
+                header_checksum_flags,
+                0x00,
+                0x13,
+                0x00,
+                0xd8,
+                0x01,
+                0x4d,
+            ],
+            Model::Mgb => [
+                0xff,
+                header_checksum_flags,
+                0x00,
+                0x13,
+                0x00,
+                0xd8,
+                0x01,
+                0x4d,
+            ],
+            Model::Sgb => [0x01, 0x00, 0x00, 0x14, 0x00, 0x00, 0xc0, 0x60],
+            Model::Sgb2 => [0xff, 0x00, 0x00, 0x14, 0x00, 0x00, 0xc0, 0x60],
+            Model::Cgb0 | Model::Cgb | Model::Agb => {
+                let [mut b, d, e, h, l] = if cgb_mode {
+                    [0x00, 0xff, 0x56, 0x00, 0x0d]
+                } else {
+                    // monochrome games get the title checksum, which picked their palette
+                    let checksum = palette::title_checksum(header).unwrap_or(0);
+                    // HL points into the tile map for the two games which need the tile
+                    // map of the DMG boot rom
+                    let [h, l] = match checksum {
+                        0x43 | 0x58 => [0x99, 0x1a],
+                        _ => [0x00, 0x7c],
+                    };
+                    [checksum, 0x00, 0x08, h, l]
+                };
+                let mut f = ZERO_FLAG;
+                if model == Model::Agb {
+                    // the AGB boot rom ends with an additional INC B
+                    b = b.wrapping_add(1);
+                    f = if b == 0 { ZERO_FLAG } else { 0 };
+                    if (b & 0x0f) == 0 {
+                        f |= HALF_CARRY_FLAG;
+                    }
+                }
+                [0x11, f, b, 0x00, d, e, h, l]
+            }
+        };
+
         Self {
-            a: 0x01,
-            f: 0xb0,
-            b: 0x00,
-            c: 0x13,
-            d: 0x00,
-            e: 0xd8,
-            h: 0x01,
-            l: 0x4d,
+            a,
+            f,
+            b,
+            c,
+            d,
+            e,
+            h,
+            l,
             pc: 0x0100,
             sp: 0xfffe,
         }
@@ -202,3 +267,22 @@ impl Registers {
         self.set_flag(set, CARRY_FLAG)
     }
 }
+
+impl Snapshot for Registers {
+    fn snapshot(&mut self, state: &mut State) {
+        for register in [
+            &mut self.a,
+            &mut self.b,
+            &mut self.c,
+            &mut self.d,
+            &mut self.e,
+            &mut self.h,
+            &mut self.l,
+            &mut self.f,
+        ] {
+            state.u8(register);
+        }
+        state.u16(&mut self.sp);
+        state.u16(&mut self.pc);
+    }
+}
@@ -0,0 +1,216 @@
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+/// Snapshots of the previous frames in a ring buffer with a limited size.
+///
+/// Only the newest snapshot is kept whole. Every older one is stored as the difference to
+/// the snapshot after it, with unchanged bytes run length encoded. From frame to frame most
+/// of the machine state stays the same, so a delta is a small fraction of a snapshot.
+pub(crate) struct Rewind {
+    // newest snapshot, which older ones are restored from
+    latest: Vec<u8>,
+    // older snapshots, oldest first, each relative to the one after it
+    deltas: VecDeque<Vec<u8>>,
+    // bytes used by the deltas and the newest snapshot
+    size: usize,
+    capacity: usize,
+    // frames between snapshots, and frames since the last one
+    interval: usize,
+    frames: usize,
+}
+
+impl Rewind {
+    pub fn new(capacity: usize, interval: usize) -> Self {
+        Self {
+            latest: Vec::new(),
+            deltas: VecDeque::new(),
+            size: 0,
+            capacity,
+            interval: interval.max(1),
+            frames: 0,
+        }
+    }
+
+    /// Counts a finished frame and returns whether a snapshot is due
+    pub fn frame_ended(&mut self) -> bool {
+        self.frames += 1;
+        if self.frames >= self.interval {
+            self.frames = 0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Adds the snapshot of the current frame and drops the oldest ones which don't fit
+    pub fn push(&mut self, snapshot: Vec<u8>) {
+        if !self.latest.is_empty() {
+            let delta = encode(&snapshot, &self.latest);
+            self.size += delta.len();
+            self.deltas.push_back(delta);
+        }
+        self.size = self.size - self.latest.len() + snapshot.len();
+        self.latest = snapshot;
+
+        while self.size > self.capacity {
+            match self.deltas.pop_front() {
+                Some(delta) => self.size -= delta.len(),
+                // a snapshot which doesn't fit on its own isn't kept either
+                None => {
+                    self.latest = Vec::new();
+                    self.size = 0;
+                }
+            }
+        }
+    }
+
+    /// Steps back to the previous snapshot and returns it. When frames ran since the
+    /// newest snapshot, that one is returned first.
+    pub fn pop(&mut self) -> Option<&[u8]> {
+        if self.frames > 0 && !self.latest.is_empty() {
+            self.frames = 0;
+            return Some(&self.latest);
+        }
+        let delta = self.deltas.pop_back()?;
+        let previous = decode(&self.latest, &delta);
+        self.size = self.size - delta.len() - self.latest.len() + previous.len();
+        self.latest = previous;
+        Some(&self.latest)
+    }
+
+    /// Number of snapshots which `pop` returns, including the newest one if frames ran
+    /// since it was taken
+    pub fn len(&self) -> usize {
+        let latest = self.frames > 0 && !self.latest.is_empty();
+        self.deltas.len() + latest as usize
+    }
+}
+
+// A delta starts with the length of the old snapshot, followed by pairs of a run of
+// unchanged bytes and a run of bytes which are XORed onto the new snapshot. Bytes beyond
+// the end of a snapshot count as zero.
+fn encode(new: &[u8], old: &[u8]) -> Vec<u8> {
+    let len = new.len().max(old.len());
+    let xor = |i: usize| new.get(i).copied().unwrap_or(0) ^ old.get(i).copied().unwrap_or(0);
+
+    let mut delta = Vec::new();
+    write_length(&mut delta, old.len());
+    let mut i = 0;
+    while i < len {
+        let unchanged = (i..len).take_while(|&j| xor(j) == 0).count();
+        i += unchanged;
+        let changed = (i..len).take_while(|&j| xor(j) != 0).count();
+        write_length(&mut delta, unchanged);
+        write_length(&mut delta, changed);
+        delta.extend((i..i + changed).map(xor));
+        i += changed;
+    }
+    delta
+}
+
+fn decode(new: &[u8], delta: &[u8]) -> Vec<u8> {
+    let mut input = delta;
+    let len = read_length(&mut input);
+    let mut old = new.to_vec();
+    old.resize(len.max(new.len()), 0);
+
+    let mut i = 0;
+    while !input.is_empty() {
+        i += read_length(&mut input);
+        let changed = read_length(&mut input);
+        for (byte, x) in old[i..i + changed].iter_mut().zip(&input[..changed]) {
+            *byte ^= x;
+        }
+        input = &input[changed..];
+        i += changed;
+    }
+    old.truncate(len);
+    old
+}
+
+// lengths take 7 bits per byte, the high bit marks that more bytes follow
+fn write_length(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_length(input: &mut &[u8]) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    while let Some((&byte, rest)) = input.split_first() {
+        *input = rest;
+        value |= ((byte & 0x7f) as usize) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, vec::Vec};
+
+    use super::Rewind;
+
+    #[test]
+    fn steps_back_within_capacity() {
+        let frames: Vec<Vec<u8>> = (0..8u8)
+            .map(|frame| {
+                let mut snapshot = vec![0u8; 1000];
+                snapshot[frame as usize * 10] = frame + 1;
+                snapshot.resize(1000 + frame as usize, frame);
+                snapshot
+            })
+            .collect();
+
+        // room for the newest snapshot and a few small deltas
+        let mut rewind = Rewind::new(1050, 1);
+        for frame in frames.iter() {
+            assert!(rewind.frame_ended());
+            rewind.push(frame.clone());
+        }
+        assert!(rewind.len() < frames.len() - 1);
+
+        let mut expected = frames.iter().rev().skip(1);
+        while let Some(snapshot) = rewind.pop() {
+            assert_eq!(snapshot, expected.next().unwrap().as_slice());
+        }
+    }
+
+    #[test]
+    fn len_counts_every_pop() {
+        let mut rewind = Rewind::new(1 << 20, 3);
+        for frame in 0..7u8 {
+            if rewind.frame_ended() {
+                rewind.push(vec![frame; 100]);
+            }
+        }
+        // two snapshots and a frame since the newest one
+        assert_eq!(rewind.len(), 2);
+        let mut pops = 0;
+        while rewind.pop().is_some() {
+            pops += 1;
+            assert_eq!(rewind.len(), 2 - pops);
+        }
+        assert_eq!(pops, 2);
+    }
+
+    #[test]
+    fn snapshots_above_capacity_are_dropped() {
+        let mut rewind = Rewind::new(100, 1);
+        rewind.push(vec![1; 150]);
+        assert_eq!(rewind.size, 0);
+        assert!(rewind.pop().is_none());
+
+        // smaller snapshots are kept again
+        rewind.push(vec![2; 40]);
+        rewind.push(vec![3; 40]);
+        assert_eq!(rewind.len(), 1);
+        assert_eq!(rewind.pop(), Some(&[2; 40][..]));
+    }
+}
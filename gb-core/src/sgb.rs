@@ -0,0 +1,647 @@
+use alloc::{boxed::Box, vec, vec::Vec};
+
+use log::debug;
+
+use crate::palette;
+use crate::ppu::{Color, HEIGHT, WIDTH};
+use crate::state::{Snapshot, State};
+
+pub const SGB_WIDTH: usize = 256;
+pub const SGB_HEIGHT: usize = 224;
+
+// Position of the game screen inside the border
+pub(crate) const SCREEN_X: usize = (SGB_WIDTH - WIDTH) / 2;
+pub(crate) const SCREEN_Y: usize = (SGB_HEIGHT - HEIGHT) / 2;
+
+// Palettes are assigned to the game screen in cells of 8x8 pixels
+const CELLS_X: usize = WIDTH / 8;
+const CELLS_Y: usize = HEIGHT / 8;
+
+const PACKET_SIZE: usize = 16;
+const SYSTEM_PALETTES: usize = 512;
+// An attribute file holds the palettes of all cells with 2 bits each
+const ATTR_FILE_SIZE: usize = CELLS_X * CELLS_Y / 4;
+const ATTR_FILES: usize = 45;
+
+// A VRAM transfer reads the top 20x13 tiles of the game screen
+const TRANSFER_SIZE: usize = 0x1000;
+
+const BORDER_TILES: usize = 256;
+const BORDER_TILE_SIZE: usize = 32;
+const BORDER_MAP_WIDTH: usize = SGB_WIDTH / 8;
+const BORDER_MAP_HEIGHT: usize = SGB_HEIGHT / 8;
+
+// Grey shades until the game sets its own palettes
+const DEFAULT_COLORS: [u16; 4] = [0x7fff, 0x56b5, 0x294a, 0x0000];
+
+/// How the game screen is hidden while the game prepares a transfer
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Mask {
+    None,
+    Freeze,
+    Black,
+    Color0,
+}
+
+/// Data the next frame transfers from VRAM
+#[derive(Copy, Clone)]
+enum Transfer {
+    Palettes,
+    BorderTiles(usize),
+    Border,
+    Attributes,
+}
+
+/// Super Game Boy, which receives commands through the joypad register and colors the
+/// game screen and its border
+pub(crate) struct Sgb {
+    // only games which announce SGB support in their header may send commands
+    commands: bool,
+    // last state of the P14 and P15 lines
+    joypad_lines: u8,
+    // whether a packet is being received and how many of its bits arrived
+    receiving: bool,
+    bits: usize,
+    packet: [u8; PACKET_SIZE],
+    // all packets of the current command
+    command: Vec<u8>,
+
+    // number of joypads and the one which is currently read
+    players: u8,
+    player: u8,
+
+    // colors of the game screen, color 0 is shared by all palettes
+    palettes: [[u16; 4]; 4],
+    // palette of every cell of the game screen
+    attributes: [[u8; CELLS_X]; CELLS_Y],
+    system_palettes: Vec<[u16; 4]>,
+    attr_files: Vec<u8>,
+    mask: Mask,
+    frozen: Option<Box<[[u8; WIDTH]; HEIGHT]>>,
+
+    // border tiles in the 4 bits per pixel format of the SNES, the map of tiles with
+    // their palette and flip flags, and the border palettes 4 to 7
+    border_tiles: Vec<u8>,
+    border_map: Vec<u16>,
+    border_palettes: [[u16; 16]; 4],
+
+    transfer: Option<Transfer>,
+}
+
+impl Sgb {
+    pub fn new(commands: bool) -> Self {
+        Self {
+            commands,
+            joypad_lines: 0x30,
+            receiving: false,
+            bits: 0,
+            packet: [0; PACKET_SIZE],
+            command: Vec::new(),
+            players: 1,
+            player: 0,
+            palettes: [DEFAULT_COLORS; 4],
+            attributes: [[0; CELLS_X]; CELLS_Y],
+            system_palettes: vec![DEFAULT_COLORS; SYSTEM_PALETTES],
+            attr_files: vec![0; ATTR_FILES * ATTR_FILE_SIZE],
+            mask: Mask::None,
+            frozen: None,
+            border_tiles: vec![0; BORDER_TILES * BORDER_TILE_SIZE],
+            border_map: vec![0; BORDER_MAP_WIDTH * BORDER_MAP_HEIGHT],
+            border_palettes: [[0; 16]; 4],
+            transfer: None,
+        }
+    }
+
+    /// The joypad which is read, if several are connected
+    pub fn player(&self) -> usize {
+        self.player as usize
+    }
+
+    pub fn multiplayer(&self) -> bool {
+        self.players > 1
+    }
+
+    /// Receives packets bit by bit from writes to the joypad register. Pulling both lines
+    /// low starts a packet, then every pulse of P14 sends a 0 and every pulse of P15 a 1.
+    pub fn write_joypad(&mut self, value: u8) {
+        if !self.commands {
+            return;
+        }
+        let lines = value & 0x30;
+        let previous = core::mem::replace(&mut self.joypad_lines, lines);
+        if lines == previous {
+            return;
+        }
+
+        match lines {
+            0x00 => {
+                self.receiving = true;
+                self.bits = 0;
+                self.packet = [0; PACKET_SIZE];
+            }
+            0x10 | 0x20 if self.receiving && previous == 0x30 => {
+                let bit = lines == 0x10;
+                if self.bits < PACKET_SIZE * 8 {
+                    self.packet[self.bits / 8] |= (bit as u8) << (self.bits % 8);
+                    self.bits += 1;
+                } else {
+                    // the packet ends with a stop bit of 0
+                    self.receiving = false;
+                    if !bit {
+                        self.packet_received();
+                    }
+                }
+            }
+            0x30 if !self.receiving && (previous & 0x20) == 0 => {
+                // releasing P15 switches to the next joypad
+                self.player = (self.player + 1) % self.players;
+            }
+            _ => {}
+        }
+    }
+
+    fn packet_received(&mut self) {
+        self.command.extend_from_slice(&self.packet);
+        let packets = (self.command[0] & 0x07).max(1) as usize;
+        if self.command.len() >= packets * PACKET_SIZE {
+            let command = core::mem::take(&mut self.command);
+            self.execute(&command);
+        }
+    }
+
+    fn execute(&mut self, data: &[u8]) {
+        match data[0] >> 3 {
+            0x00 => self.set_palettes(0, 1, data),
+            0x01 => self.set_palettes(2, 3, data),
+            0x02 => self.set_palettes(0, 3, data),
+            0x03 => self.set_palettes(1, 2, data),
+            0x04 => self.attr_blk(data),
+            0x05 => self.attr_lin(data),
+            0x06 => self.attr_div(data),
+            0x07 => self.attr_chr(data),
+            0x0a => self.pal_set(data),
+            0x0b => self.transfer = Some(Transfer::Palettes),
+            0x11 => {
+                self.players = match data[1] & 0x03 {
+                    1 => 2,
+                    3 => 4,
+                    _ => 1,
+                };
+                self.player = 0;
+            }
+            0x13 => self.transfer = Some(Transfer::BorderTiles((data[1] & 1) as usize)),
+            0x14 => self.transfer = Some(Transfer::Border),
+            0x15 => self.transfer = Some(Transfer::Attributes),
+            0x16 => {
+                self.apply_attr_file(data[1] & 0x3f);
+                if (data[1] & 0x40) != 0 {
+                    self.mask = Mask::None;
+                }
+            }
+            0x17 => {
+                self.mask = match data[1] & 0x03 {
+                    0 => Mask::None,
+                    1 => Mask::Freeze,
+                    2 => Mask::Black,
+                    _ => Mask::Color0,
+                }
+            }
+            command => debug!("Unsupported SGB command {:02x}", command),
+        }
+    }
+
+    /// PAL01, PAL23, PAL03 and PAL12 set color 0 and three colors of two palettes
+    fn set_palettes(&mut self, first: usize, second: usize, data: &[u8]) {
+        let color = |i: usize| u16::from_le_bytes([data[1 + 2 * i], data[2 + 2 * i]]);
+        for palette in self.palettes.iter_mut() {
+            palette[0] = color(0);
+        }
+        for i in 1..4 {
+            self.palettes[first][i] = color(i);
+            self.palettes[second][i] = color(i + 3);
+        }
+    }
+
+    /// Assigns palettes to the inside, the border and the outside of rectangles
+    fn attr_blk(&mut self, data: &[u8]) {
+        let count = data[1] as usize;
+        for set in data[2..].chunks_exact(6).take(count) {
+            let control = set[0] & 0x07;
+            let inside = set[1] & 0x03;
+            let mut border = (set[1] >> 2) & 0x03;
+            let outside = (set[1] >> 4) & 0x03;
+            // setting only the inside or the outside also sets the border
+            let mut change_border = (control & 0x02) != 0;
+            if control == 0x01 {
+                border = inside;
+                change_border = true;
+            } else if control == 0x04 {
+                border = outside;
+                change_border = true;
+            }
+
+            let (x1, y1) = ((set[2] & 0x1f) as usize, (set[3] & 0x1f) as usize);
+            let (x2, y2) = ((set[4] & 0x1f) as usize, (set[5] & 0x1f) as usize);
+            for (y, row) in self.attributes.iter_mut().enumerate() {
+                for (x, attribute) in row.iter_mut().enumerate() {
+                    let in_rect = (x1..=x2).contains(&x) && (y1..=y2).contains(&y);
+                    let in_inside = x > x1 && x < x2 && y > y1 && y < y2;
+                    if in_inside && (control & 0x01) != 0 {
+                        *attribute = inside;
+                    } else if in_rect && !in_inside && change_border {
+                        *attribute = border;
+                    } else if !in_rect && (control & 0x04) != 0 {
+                        *attribute = outside;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Assigns palettes to whole rows or columns
+    fn attr_lin(&mut self, data: &[u8]) {
+        let count = data[1] as usize;
+        for &line in data[2..].iter().take(count) {
+            let index = (line & 0x1f) as usize;
+            let palette = (line >> 5) & 0x03;
+            if (line & 0x80) != 0 {
+                if let Some(row) = self.attributes.get_mut(index) {
+                    *row = [palette; CELLS_X];
+                }
+            } else if index < CELLS_X {
+                for row in self.attributes.iter_mut() {
+                    row[index] = palette;
+                }
+            }
+        }
+    }
+
+    /// Divides the screen at a row or column into two palettes
+    fn attr_div(&mut self, data: &[u8]) {
+        let after = data[1] & 0x03;
+        let before = (data[1] >> 2) & 0x03;
+        let on_line = (data[1] >> 4) & 0x03;
+        let horizontal = (data[1] & 0x40) != 0;
+        let line = (data[2] & 0x1f) as usize;
+
+        for (y, row) in self.attributes.iter_mut().enumerate() {
+            for (x, attribute) in row.iter_mut().enumerate() {
+                let position = if horizontal { y } else { x };
+                *attribute = match position.cmp(&line) {
+                    core::cmp::Ordering::Less => before,
+                    core::cmp::Ordering::Equal => on_line,
+                    core::cmp::Ordering::Greater => after,
+                };
+            }
+        }
+    }
+
+    /// Assigns palettes cell by cell, starting at a position
+    fn attr_chr(&mut self, data: &[u8]) {
+        let (mut x, mut y) = (data[1] as usize, data[2] as usize);
+        let count = u16::from_le_bytes([data[3], data[4]]) as usize;
+        let vertical = data[5] == 1;
+
+        for i in 0..count.min((data.len() - 6) * 4) {
+            if x >= CELLS_X || y >= CELLS_Y {
+                break;
+            }
+            let byte = data[6 + i / 4];
+            self.attributes[y][x] = (byte >> (6 - 2 * (i % 4))) & 0x03;
+
+            if vertical {
+                y += 1;
+                if y == CELLS_Y {
+                    y = 0;
+                    x += 1;
+                }
+            } else {
+                x += 1;
+                if x == CELLS_X {
+                    x = 0;
+                    y += 1;
+                }
+            }
+        }
+    }
+
+    /// Copies four of the transferred system palettes and optionally an attribute file
+    fn pal_set(&mut self, data: &[u8]) {
+        for (i, palette) in self.palettes.iter_mut().enumerate() {
+            let id = u16::from_le_bytes([data[1 + 2 * i], data[2 + 2 * i]]) & 0x1ff;
+            *palette = self.system_palettes[id as usize];
+        }
+        let color0 = self.palettes[0][0];
+        for palette in self.palettes.iter_mut() {
+            palette[0] = color0;
+        }
+
+        let flags = data[9];
+        if (flags & 0x80) != 0 {
+            self.apply_attr_file(flags & 0x3f);
+        }
+        if (flags & 0x40) != 0 {
+            self.mask = Mask::None;
+        }
+    }
+
+    fn apply_attr_file(&mut self, file: u8) {
+        let file = file as usize;
+        if file >= ATTR_FILES {
+            return;
+        }
+        let data = &self.attr_files[file * ATTR_FILE_SIZE..(file + 1) * ATTR_FILE_SIZE];
+        for (i, attribute) in self.attributes.iter_mut().flatten().enumerate() {
+            *attribute = (data[i / 4] >> (6 - 2 * (i % 4))) & 0x03;
+        }
+    }
+
+    /// Whether a transfer waits for the VRAM contents of the next frame
+    pub fn transfer_pending(&self) -> bool {
+        self.transfer.is_some()
+    }
+
+    /// Completes a pending transfer with the data shown on the game screen
+    pub fn transfer(&mut self, shades: &[[u8; WIDTH]; HEIGHT]) {
+        let data = transfer_data(shades);
+        match self.transfer.take() {
+            Some(Transfer::Palettes) => {
+                for (palette, colors) in self.system_palettes.iter_mut().zip(data.chunks(8)) {
+                    for (color, bytes) in palette.iter_mut().zip(colors.chunks_exact(2)) {
+                        *color = u16::from_le_bytes([bytes[0], bytes[1]]);
+                    }
+                }
+            }
+            Some(Transfer::BorderTiles(half)) => {
+                let start = half * self.border_tiles.len() / 2;
+                let end = start + self.border_tiles.len() / 2;
+                self.border_tiles[start..end].copy_from_slice(&data[..end - start]);
+            }
+            Some(Transfer::Border) => {
+                for (entry, bytes) in self.border_map.iter_mut().zip(data.chunks_exact(2)) {
+                    *entry = u16::from_le_bytes([bytes[0], bytes[1]]);
+                }
+                let colors = data[0x800..0x880].chunks_exact(2);
+                for (color, bytes) in self.border_palettes.iter_mut().flatten().zip(colors) {
+                    *color = u16::from_le_bytes([bytes[0], bytes[1]]);
+                }
+            }
+            Some(Transfer::Attributes) => {
+                let size = self.attr_files.len();
+                self.attr_files.copy_from_slice(&data[..size]);
+            }
+            None => {}
+        }
+    }
+
+    /// Keeps the last frame while the screen is frozen
+    pub fn end_frame(&mut self, shades: &[[u8; WIDTH]; HEIGHT]) {
+        if self.mask != Mask::Freeze {
+            self.frozen = None;
+        } else if self.frozen.is_none() {
+            self.frozen = Some(Box::new(*shades));
+        }
+    }
+
+    /// Colors the shades of the game screen with the palettes of its cells
+    pub fn screen(&self, shades: &[[u8; WIDTH]; HEIGHT]) -> [[Color; WIDTH]; HEIGHT] {
+        let mut screen = [[Color::rgb(0); WIDTH]; HEIGHT];
+        for (y, row) in screen.iter_mut().enumerate() {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                *pixel = self.screen_pixel(shades, x, y);
+            }
+        }
+        screen
+    }
+
+    /// Draws the border around the colored game screen
+    pub fn frame(&self, shades: &[[u8; WIDTH]; HEIGHT]) -> [[Color; SGB_WIDTH]; SGB_HEIGHT] {
+        let mut frame = [[Color::rgb(0); SGB_WIDTH]; SGB_HEIGHT];
+        for (y, row) in frame.iter_mut().enumerate() {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                *pixel = self.frame_pixel(shades, x, y);
+            }
+        }
+        frame
+    }
+
+    /// Returns a single pixel of `screen`, which lets frontends draw without a copy of it
+    pub(crate) fn screen_pixel(&self, shades: &[[u8; WIDTH]; HEIGHT], x: usize, y: usize) -> Color {
+        let shades = self.frozen.as_deref().unwrap_or(shades);
+        let color = match self.mask {
+            Mask::Black => 0,
+            Mask::Color0 => self.palettes[0][0],
+            Mask::None | Mask::Freeze => {
+                let palette = self.attributes[y / 8][x / 8] as usize;
+                self.palettes[palette][shades[y][x] as usize]
+            }
+        };
+        palette::rgb555(color)
+    }
+
+    /// Returns a single pixel of `frame`, where the game screen covers the border
+    pub(crate) fn frame_pixel(&self, shades: &[[u8; WIDTH]; HEIGHT], x: usize, y: usize) -> Color {
+        match (y.checked_sub(SCREEN_Y), x.checked_sub(SCREEN_X)) {
+            (Some(y), Some(x)) if y < HEIGHT && x < WIDTH => self.screen_pixel(shades, x, y),
+            _ => self.border_pixel(x, y),
+        }
+    }
+
+    fn border_pixel(&self, x: usize, y: usize) -> Color {
+        let entry = self.border_map[(y / 8) * BORDER_MAP_WIDTH + x / 8];
+        let tile = &self.border_tiles[(entry & 0xff) as usize * BORDER_TILE_SIZE..];
+        // only the palettes 4 to 7 are available for the border
+        let palette = &self.border_palettes[((entry >> 10) & 0x03) as usize];
+        let x_flip = (entry & (1 << 14)) != 0;
+        let y_flip = (entry & (1 << 15)) != 0;
+
+        let row = if y_flip { 7 - y % 8 } else { y % 8 };
+        let bit = if x_flip { x % 8 } else { 7 - x % 8 };
+        let planes = [
+            tile[2 * row],
+            tile[2 * row + 1],
+            tile[16 + 2 * row],
+            tile[16 + 2 * row + 1],
+        ];
+        let index = planes.iter().enumerate().fold(0, |index, (plane, &byte)| {
+            index | (((byte >> bit) & 1) << plane)
+        });
+        // color 0 is transparent and shows the backdrop
+        if index == 0 {
+            palette::rgb555(self.palettes[0][0])
+        } else {
+            palette::rgb555(palette[index as usize])
+        }
+    }
+}
+
+impl Snapshot for Sgb {
+    fn snapshot(&mut self, state: &mut State) {
+        state.u8(&mut self.joypad_lines);
+        state.bool(&mut self.receiving);
+        state.usize(&mut self.bits);
+        state.bytes(&mut self.packet);
+        state.vec(&mut self.command);
+        state.u8(&mut self.players);
+        state.u8(&mut self.player);
+        for color in self.palettes.iter_mut().flatten() {
+            state.u16(color);
+        }
+        for row in self.attributes.iter_mut() {
+            state.bytes(row);
+        }
+        for color in self.system_palettes.iter_mut().flatten() {
+            state.u16(color);
+        }
+        state.bytes(&mut self.attr_files);
+
+        let mut mask = self.mask as u8;
+        state.u8(&mut mask);
+        self.mask = match mask {
+            1 => Mask::Freeze,
+            2 => Mask::Black,
+            3 => Mask::Color0,
+            _ => Mask::None,
+        };
+
+        let mut frozen = self.frozen.is_some();
+        state.bool(&mut frozen);
+        if !frozen {
+            self.frozen = None;
+        } else {
+            let shades = self
+                .frozen
+                .get_or_insert_with(|| Box::new([[0; WIDTH]; HEIGHT]));
+            for row in shades.iter_mut() {
+                state.bytes(row);
+            }
+        }
+
+        state.bytes(&mut self.border_tiles);
+        for entry in self.border_map.iter_mut() {
+            state.u16(entry);
+        }
+        for color in self.border_palettes.iter_mut().flatten() {
+            state.u16(color);
+        }
+
+        let (mut kind, mut tile_block) = match self.transfer {
+            None => (0, 0),
+            Some(Transfer::Palettes) => (1, 0),
+            Some(Transfer::BorderTiles(block)) => (2, block),
+            Some(Transfer::Border) => (3, 0),
+            Some(Transfer::Attributes) => (4, 0),
+        };
+        state.u8(&mut kind);
+        state.usize(&mut tile_block);
+        self.transfer = match kind {
+            1 => Some(Transfer::Palettes),
+            2 => Some(Transfer::BorderTiles(tile_block)),
+            3 => Some(Transfer::Border),
+            4 => Some(Transfer::Attributes),
+            _ => None,
+        };
+    }
+}
+
+// The SGB reads the LCD output like tile data. Every tile of the screen, row by row, is
+// turned back into 2 bits per pixel from the shades which BGP and OBP0/1 produced.
+fn transfer_data(shades: &[[u8; WIDTH]; HEIGHT]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(TRANSFER_SIZE);
+    for tile in 0..TRANSFER_SIZE / 16 {
+        let (tile_x, tile_y) = (tile % CELLS_X, tile / CELLS_X);
+        for row in &shades[tile_y * 8..tile_y * 8 + 8] {
+            let pixels = &row[tile_x * 8..tile_x * 8 + 8];
+            let plane = |bit: u8| {
+                pixels
+                    .iter()
+                    .fold(0, |byte, &shade| (byte << 1) | ((shade >> bit) & 1))
+            };
+            data.push(plane(0));
+            data.push(plane(1));
+        }
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Sgb;
+    use crate::ppu::{HEIGHT, WIDTH};
+
+    fn send_packet(sgb: &mut Sgb, packet: &[u8; 16]) {
+        sgb.write_joypad(0x00);
+        sgb.write_joypad(0x30);
+        let bits = packet
+            .iter()
+            .flat_map(|byte| (0..8).map(move |i| (byte >> i) & 1));
+        for bit in bits.chain(core::iter::once(0)) {
+            sgb.write_joypad(if bit == 1 { 0x10 } else { 0x20 });
+            sgb.write_joypad(0x30);
+        }
+    }
+
+    #[test]
+    fn palettes_and_attributes() {
+        let mut sgb = Sgb::new(true);
+        let mut pal01 = [0u8; 16];
+        pal01[0] = 0x01;
+        pal01[1..3].copy_from_slice(&0x1234u16.to_le_bytes());
+        pal01[9..11].copy_from_slice(&0x7c00u16.to_le_bytes());
+        send_packet(&mut sgb, &pal01);
+        assert_eq!(sgb.palettes[0][0], 0x1234);
+        assert_eq!(sgb.palettes[3][0], 0x1234);
+        assert_eq!(sgb.palettes[1][1], 0x7c00);
+
+        // inside of the rectangle 1,1 to 3,3 gets palette 2
+        let attr_blk = [0x21, 1, 0x01, 0x02, 1, 1, 3, 3, 0, 0, 0, 0, 0, 0, 0, 0];
+        send_packet(&mut sgb, &attr_blk);
+        assert_eq!(sgb.attributes[2][2], 2);
+        assert_eq!(sgb.attributes[1][1], 2);
+        assert_eq!(sgb.attributes[0][0], 0);
+        assert_eq!(sgb.attributes[4][4], 0);
+    }
+
+    #[test]
+    fn multiplayer() {
+        let mut sgb = Sgb::new(true);
+        let mut mlt_req = [0u8; 16];
+        mlt_req[0] = 0x89;
+        mlt_req[1] = 0x03;
+        send_packet(&mut sgb, &mlt_req);
+        assert!(sgb.multiplayer());
+
+        assert_eq!(sgb.player(), 0);
+        sgb.write_joypad(0x10);
+        sgb.write_joypad(0x30);
+        assert_eq!(sgb.player(), 1);
+    }
+
+    #[test]
+    fn transfer_reads_the_screen() {
+        let mut sgb = Sgb::new(true);
+        let mut pal_trn = [0u8; 16];
+        pal_trn[0] = 0x59;
+        send_packet(&mut sgb, &pal_trn);
+        assert!(sgb.transfer_pending());
+
+        let mut shades = [[0u8; WIDTH]; HEIGHT];
+        shades[0][0] = 3;
+        shades[0][7] = 1;
+        // first pixel of the second tile in the second row of tiles, which is tile 21
+        shades[8][8] = 2;
+        sgb.transfer(&shades);
+        assert_eq!(sgb.system_palettes[0][0], 0x8081);
+        assert_eq!(sgb.system_palettes[42][0], 0x8000);
+    }
+
+    #[test]
+    fn commands_need_sgb_support() {
+        let mut sgb = Sgb::new(false);
+        let mut pal01 = [0u8; 16];
+        pal01[0] = 0x01;
+        pal01[1..3].copy_from_slice(&0x1234u16.to_le_bytes());
+        send_packet(&mut sgb, &pal01);
+        assert_eq!(sgb.palettes[0][0], 0x7fff);
+    }
+}
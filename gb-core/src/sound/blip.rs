@@ -0,0 +1,134 @@
+use alloc::{vec, vec::Vec};
+use core::f64::consts::PI;
+
+// Number of output samples a single amplitude step is spread over
+const KERNEL_WIDTH: usize = 16;
+
+// Number of sub-sample positions a step kernel is precomputed for
+const PHASES: usize = 64;
+
+// Cutoff of the low pass filter, relative to the output sample rate
+const CUTOFF: f64 = 0.45;
+
+/// Band-limited step synthesis buffer.
+///
+/// Channels don't produce samples, they only report when and by how much their
+/// amplitude changes. Every change is added as a band-limited step, which is then
+/// integrated into output samples at an arbitrary sample rate. Compared to point
+/// sampling, this neither aliases nor drifts against the output rate.
+pub(in crate::sound) struct BlipBuf {
+    // output samples per input clock
+    factor: f64,
+    // position of clock 0 of the current frame in output samples, relative to the first
+    // entry of `deltas`
+    offset: f64,
+    // pending amplitude changes per output sample
+    deltas: Vec<f32>,
+    integrator: f32,
+    kernel: Vec<[f32; KERNEL_WIDTH]>,
+}
+
+impl BlipBuf {
+    pub fn new(clock_rate: u32, sample_rate: u32) -> Self {
+        Self {
+            factor: sample_rate as f64 / clock_rate as f64,
+            offset: 0.0,
+            deltas: vec![0.0; KERNEL_WIDTH],
+            integrator: 0.0,
+            kernel: (0..PHASES).map(step_kernel).collect(),
+        }
+    }
+
+    /// Changes the output sample rate, starting with the current frame
+    pub fn set_sample_rate(&mut self, clock_rate: u32, sample_rate: f64) {
+        self.factor = sample_rate / clock_rate as f64;
+    }
+
+    /// Adds an amplitude change at the given clock of the current frame
+    pub fn add_delta(&mut self, clock: u32, delta: f32) {
+        let position = self.offset + clock as f64 * self.factor;
+        let index = position as usize;
+        let phase = ((position - index as f64) * PHASES as f64) as usize;
+
+        if self.deltas.len() < index + KERNEL_WIDTH {
+            self.deltas.resize(index + KERNEL_WIDTH, 0.0);
+        }
+        let kernel = &self.kernel[phase.min(PHASES - 1)];
+        for (d, k) in self.deltas[index..].iter_mut().zip(kernel.iter()) {
+            *d += delta * k;
+        }
+    }
+
+    /// Ends the current frame after the given amount of clocks. Samples up to this point
+    /// become available for reading and the next frame starts at clock 0.
+    pub fn end_frame(&mut self, clocks: u32) {
+        self.offset += clocks as f64 * self.factor;
+    }
+
+    /// Moves all finished samples into `out`
+    pub fn read_samples(&mut self, out: &mut Vec<f32>) {
+        let available = self.offset as usize;
+        if available == 0 {
+            return;
+        }
+        if self.deltas.len() < available + KERNEL_WIDTH {
+            self.deltas.resize(available + KERNEL_WIDTH, 0.0);
+        }
+
+        for delta in self.deltas.drain(..available) {
+            self.integrator += delta;
+            out.push(self.integrator);
+        }
+        self.offset -= available as f64;
+    }
+}
+
+/// Computes the derivative of a band-limited step which starts `phase / PHASES` samples
+/// after the first kernel entry, normalized so that the step reaches exactly 1.
+fn step_kernel(phase: usize) -> [f32; KERNEL_WIDTH] {
+    let shift = phase as f64 / PHASES as f64;
+    let half_width = (KERNEL_WIDTH / 2) as f64;
+
+    let mut kernel = [0f64; KERNEL_WIDTH];
+    for (i, k) in kernel.iter_mut().enumerate() {
+        let x = i as f64 - half_width + 0.5 - shift;
+        // windowed sinc impulse response
+        let sinc = if x == 0.0 {
+            1.0
+        } else {
+            libm::sin(2.0 * PI * CUTOFF * x) / (2.0 * PI * CUTOFF * x)
+        };
+        let window = 0.42
+            + 0.5 * libm::cos(PI * x / half_width)
+            + 0.08 * libm::cos(2.0 * PI * x / half_width);
+        *k = sinc * window;
+    }
+
+    let sum: f64 = kernel.iter().sum();
+    let mut normalized = [0f32; KERNEL_WIDTH];
+    for (n, k) in normalized.iter_mut().zip(kernel.iter()) {
+        *n = (k / sum) as f32;
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::BlipBuf;
+
+    #[test]
+    fn step_settles_at_amplitude() {
+        let mut blip = BlipBuf::new(4_194_304, 48_000);
+        blip.add_delta(100, 0.5);
+        blip.end_frame(4_194_304);
+
+        let mut samples = Vec::new();
+        blip.read_samples(&mut samples);
+
+        assert_eq!(samples.len(), 48_000);
+        assert!(samples[..1].iter().all(|&s| s.abs() < 0.01));
+        assert!(samples[100..].iter().all(|&s| (s - 0.5).abs() < 0.001));
+    }
+}
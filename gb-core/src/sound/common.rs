@@ -1,2 +1,5 @@
 // The frame sequencer is clocked by a 512Hz timer, so we have to wait 8192 cycles for a step
 pub(in crate::sound) const FRAME_TICKS: u16 = 8192;
+
+// The CPU and therefore all sound channels are clocked with 4194304Hz
+pub(in crate::sound) const CPU_CLOCK: u32 = 4_194_304;
@@ -0,0 +1,25 @@
+use crate::sound::common::CPU_CLOCK;
+
+/// Models the capacitor in the output stage of the DMG, which slowly removes any
+/// DC offset from the signal.
+pub(in crate::sound) struct HighPass {
+    charge_factor: f32,
+    capacitor: f32,
+}
+
+impl HighPass {
+    pub fn new(sample_rate: u32) -> Self {
+        // the capacitor discharges by this factor on every CPU cycle
+        let charge_factor = libm::pow(0.999958, CPU_CLOCK as f64 / sample_rate as f64);
+        Self {
+            charge_factor: charge_factor as f32,
+            capacitor: 0.0,
+        }
+    }
+
+    pub fn filter(&mut self, input: f32) -> f32 {
+        let output = input - self.capacitor;
+        self.capacitor = input - output * self.charge_factor;
+        output
+    }
+}
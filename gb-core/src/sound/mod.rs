@@ -1,21 +1,71 @@
+use alloc::vec::Vec;
+
 use log::error;
 
+use crate::state::{Snapshot, State};
+
+mod blip;
 pub(in crate::sound) mod common;
+mod filter;
 mod noise;
+mod output;
 mod pulse;
 mod pulsesweep;
+mod ring;
+mod vgm;
 mod wave;
 
 use noise::NoiseChannel;
+use output::ChannelOutput;
 use pulse::PulseChannel;
 use pulsesweep::PulseSweepChannel;
+use ring::AudioRing;
+use vgm::VgmLogger;
 use wave::WaveChannel;
 
-pub const SAMPLE_RATE: usize = 44_100;
+pub const DEFAULT_SAMPLE_RATE: usize = 44_100;
+
+// The rate adjustment of frontends is limited, so that it isn't audible as pitch change
+const MAX_RATE_ADJUSTMENT: f64 = 0.005;
+
+/// Fill level and error counters of the audio buffer
+#[derive(Copy, Clone, Debug, Default)]
+pub struct AudioStatus {
+    /// Samples which are ready to be fetched
+    pub buffered: usize,
+    /// Samples the buffer can hold, before new samples overrun it
+    pub capacity: usize,
+    /// Samples which were dropped, because the buffer was full
+    pub overruns: usize,
+    /// Samples which were requested, but not available yet
+    pub underruns: usize,
+}
+
+/// The four sound channels of the APU
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Channel {
+    /// Channel 1, square wave with frequency sweep
+    Pulse1,
+    /// Channel 2, square wave
+    Pulse2,
+    /// Channel 3, custom wave pattern
+    Wave,
+    /// Channel 4, noise
+    Noise,
+}
 
-// The CPU ticks with 4194304Hz, the PCM sample rate is 44.1kHz, so for single sample,
-// we have to wait around 95 cycles.
-const SAMPLE_TICKS: u8 = 95;
+impl Channel {
+    pub const ALL: [Channel; 4] = [
+        Channel::Pulse1,
+        Channel::Pulse2,
+        Channel::Wave,
+        Channel::Noise,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
 
 /// Audio Processing Unit
 pub(crate) struct Apu {
@@ -30,16 +80,28 @@ pub(crate) struct Apu {
     output_register: u8,
     on_off_register: u8,
 
-    // emulator internal counter of cycles in current sample
-    sample_counter: u8,
-    // audio buffer stores PCM data and is cleared every time a frontend implementation
-    // fetches it. To prevent it from growing to infinity, in case it is never fetched, it's
-    // regularly cleared when it reaches a certain size.
-    audio_buffer: Vec<f32>,
+    // emulator internal counter of cycles in the current blip frame
+    clock: u32,
+    // emulator internal counter of all cycles
+    cycles: u64,
+    // output stage of every channel, indexed by `Channel::index`
+    outputs: [ChannelOutput; 4],
+    muted: [bool; 4],
+    solo: Option<Channel>,
+    // clamped ratio of `adjust_rate`
+    rate: f64,
+    // audio buffer stores interleaved stereo PCM data until a frontend fetches it. It holds
+    // half a second of audio, anything beyond is counted as overrun.
+    audio_buffer: AudioRing,
+    // same as the audio buffer, but mono and for every channel on its own, regardless of
+    // whether it is muted. They are only filled while the taps are on.
+    channel_buffers: [AudioRing; 4],
+    channel_taps: bool,
+    vgm_logger: Option<VgmLogger>,
 }
 
 impl Apu {
-    pub fn new() -> Self {
+    pub fn new(sample_rate: usize) -> Self {
         Self {
             pulse_channel: PulseChannel::new(),
             pulsesweep_channel: PulseSweepChannel::new(),
@@ -48,8 +110,26 @@ impl Apu {
             volume_register: 0,
             output_register: 0,
             on_off_register: 0,
-            sample_counter: 0,
-            audio_buffer: Vec::new(),
+            clock: 0,
+            cycles: 0,
+            outputs: [
+                ChannelOutput::new(sample_rate),
+                ChannelOutput::new(sample_rate),
+                ChannelOutput::new(sample_rate),
+                ChannelOutput::new(sample_rate),
+            ],
+            muted: [false; 4],
+            solo: None,
+            rate: 1.0,
+            audio_buffer: AudioRing::new(sample_rate),
+            channel_buffers: [
+                AudioRing::new(sample_rate / 2),
+                AudioRing::new(sample_rate / 2),
+                AudioRing::new(sample_rate / 2),
+                AudioRing::new(sample_rate / 2),
+            ],
+            channel_taps: false,
+            vgm_logger: None,
         }
     }
 
@@ -57,24 +137,116 @@ impl Apu {
         for _ in 0..steps {
             self.pulsesweep_channel.single_step();
 
-            self.sample_counter += 1;
-            if self.sample_counter == SAMPLE_TICKS {
-                self.sample_counter = 0;
-                self.audio_buffer.push(self.pulsesweep_channel.get_volume());
-                self.audio_buffer.push(self.pulsesweep_channel.get_volume());
+            let amplitudes = [
+                self.pulsesweep_channel.get_volume(),
+                self.pulse_channel.get_volume(),
+                self.wave_channel.get_volume(),
+                self.noise_channel.get_volume(),
+            ];
+            for (output, amplitude) in self.outputs.iter_mut().zip(amplitudes) {
+                output.set_amplitude(self.clock, amplitude);
+            }
+            self.clock += 1;
+        }
+
+        for output in self.outputs.iter_mut() {
+            output.end_frame(self.clock);
+        }
+        self.cycles += self.clock as u64;
+        self.clock = 0;
+        self.mix();
+    }
 
-                // clear the audio buffer if it wasn't requested for half a second.
-                if self.audio_buffer.len() == SAMPLE_RATE {
-                    self.audio_buffer.clear();
+    /// Moves the samples of all channels into the channel buffers and their sum into the
+    /// audio buffer
+    fn mix(&mut self) {
+        let audible = Channel::ALL.map(|channel| self.audible(channel));
+        let samples = self.outputs[0].samples().len();
+
+        for i in 0..samples {
+            let mut mixed = 0.0;
+            for (channel, output) in self.outputs.iter().enumerate() {
+                let sample = output.samples()[i];
+                if audible[channel] {
+                    mixed += sample;
+                }
+                if self.channel_taps {
+                    self.channel_buffers[channel].push(sample);
                 }
             }
+
+            self.audio_buffer.push(mixed);
+            self.audio_buffer.push(mixed);
+        }
+
+        for output in self.outputs.iter_mut() {
+            output.clear();
+        }
+    }
+
+    fn audible(&self, channel: Channel) -> bool {
+        match self.solo {
+            Some(solo) => solo == channel,
+            None => !self.muted[channel.index()],
+        }
+    }
+
+    pub fn set_muted(&mut self, channel: Channel, muted: bool) {
+        self.muted[channel.index()] = muted;
+    }
+
+    pub fn set_solo(&mut self, channel: Option<Channel>) {
+        self.solo = channel;
+    }
+
+    /// Scales the amount of produced samples by `ratio`, which is clamped to a small
+    /// range around 1. Frontends use it to keep the fill level of the audio buffer
+    /// stable, when their audio device runs slightly faster or slower than the emulation.
+    pub fn adjust_rate(&mut self, ratio: f64) {
+        let ratio = ratio.clamp(1.0 - MAX_RATE_ADJUSTMENT, 1.0 + MAX_RATE_ADJUSTMENT);
+        for output in self.outputs.iter_mut() {
+            output.adjust_rate(ratio);
+        }
+        self.rate = ratio;
+    }
+
+    /// Applies the mix, rate adjustment and channel taps of another APU, when its board is
+    /// replaced by a new one
+    pub fn copy_settings(&mut self, other: &Apu) {
+        self.muted = other.muted;
+        self.solo = other.solo;
+        self.adjust_rate(other.rate);
+        self.set_channel_taps(other.channel_taps);
+    }
+
+    pub fn audio_into(&mut self, out: &mut [f32], needed: usize) -> usize {
+        self.audio_buffer.drain_into(out, needed)
+    }
+
+    pub fn audio_status(&self) -> AudioStatus {
+        AudioStatus {
+            buffered: self.audio_buffer.len(),
+            capacity: self.audio_buffer.capacity(),
+            overruns: self.audio_buffer.overruns(),
+            underruns: self.audio_buffer.underruns(),
+        }
+    }
+
+    /// Starts or stops buffering the output of every channel. Starting drops what is left
+    /// from an earlier tap, so that the channels are in step with the mixed output.
+    pub fn set_channel_taps(&mut self, enabled: bool) {
+        self.channel_taps = enabled;
+        for buffer in self.channel_buffers.iter_mut() {
+            buffer.clear();
         }
     }
 
-    pub fn audio_buffer(&mut self) -> Vec<f32> {
-        let buffer = self.audio_buffer.clone();
-        self.audio_buffer.clear();
-        buffer
+    pub fn channel_audio_into(&mut self, channel: Channel, out: &mut [f32]) -> usize {
+        self.channel_buffers[channel.index()].drain_into(out, 0)
+    }
+
+    pub fn channel_audio_len(&self, channel: Channel) -> usize {
+        self.channel_buffers[channel.index()].len()
     }
 
     pub fn read_byte(&self, addr: u16) -> u8 {
@@ -93,7 +265,39 @@ impl Apu {
         }
     }
 
+    /// Starts recording all register writes. The log begins with the current state of all
+    /// registers, so that it can be played back on its own.
+    pub fn start_vgm_log(&mut self) {
+        let mut logger = VgmLogger::new(self.cycles);
+        let registers = [0xff26]
+            .into_iter()
+            .chain(0xff30..=0xff3f)
+            .chain(0xff10..=0xff14)
+            .chain(0xff16..=0xff1e)
+            .chain(0xff20..=0xff25);
+        for addr in registers {
+            let value = match addr {
+                // don't retrigger the channels
+                0xff14 | 0xff19 | 0xff1e | 0xff23 => self.read_byte(addr) & 0x7f,
+                _ => self.read_byte(addr),
+            };
+            logger.log(self.cycles, addr, value);
+        }
+        self.vgm_logger = Some(logger);
+    }
+
+    /// Stops recording register writes and returns them as VGM file
+    pub fn stop_vgm_log(&mut self) -> Option<Vec<u8>> {
+        self.vgm_logger
+            .take()
+            .map(|logger| logger.finish(self.cycles))
+    }
+
     pub fn write_byte(&mut self, addr: u16, value: u8) {
+        if let Some(logger) = self.vgm_logger.as_mut() {
+            logger.log(self.cycles, addr, value);
+        }
+
         match addr {
             0xff10..=0xff14 => self.pulsesweep_channel.write_byte(addr, value),
             0xff16..=0xff19 => self.pulse_channel.write_byte(addr, value),
@@ -109,3 +313,17 @@ impl Apu {
         }
     }
 }
+
+/// Only the channels and registers are restored. The output stage keeps running, so that
+/// the blip buffers stay in step with the audio which is already queued.
+impl Snapshot for Apu {
+    fn snapshot(&mut self, state: &mut State) {
+        self.pulse_channel.snapshot(state);
+        self.pulsesweep_channel.snapshot(state);
+        self.wave_channel.snapshot(state);
+        self.noise_channel.snapshot(state);
+        state.u8(&mut self.volume_register);
+        state.u8(&mut self.output_register);
+        state.u8(&mut self.on_off_register);
+    }
+}
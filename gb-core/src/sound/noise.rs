@@ -1,3 +1,5 @@
+use crate::state::{Snapshot, State};
+
 pub(in crate::sound) struct NoiseChannel {
     length_register: u8,
     volume_envelope_register: u8,
@@ -15,6 +17,11 @@ impl NoiseChannel {
         }
     }
 
+    pub fn get_volume(&self) -> f32 {
+        // TODO: the channel doesn't produce any sound yet
+        0.0
+    }
+
     pub fn read_byte(&self, addr: u16) -> u8 {
         match addr {
             0xff20 => self.length_register,
@@ -35,3 +42,12 @@ impl NoiseChannel {
         }
     }
 }
+
+impl Snapshot for NoiseChannel {
+    fn snapshot(&mut self, state: &mut State) {
+        state.u8(&mut self.length_register);
+        state.u8(&mut self.volume_envelope_register);
+        state.u8(&mut self.polynomial_counter_register);
+        state.u8(&mut self.consecutive_register);
+    }
+}
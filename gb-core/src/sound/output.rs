@@ -0,0 +1,58 @@
+use alloc::vec::Vec;
+
+use crate::sound::blip::BlipBuf;
+use crate::sound::common::CPU_CLOCK;
+use crate::sound::filter::HighPass;
+
+/// Turns the amplitude changes of a single channel into filtered PCM samples
+pub(in crate::sound) struct ChannelOutput {
+    sample_rate: usize,
+    // amplitude of the last cycle, changes to it are fed into the blip buffer
+    amplitude: f32,
+    blip: BlipBuf,
+    high_pass: HighPass,
+    // filtered samples of the current frame, they are consumed by the mixer
+    samples: Vec<f32>,
+}
+
+impl ChannelOutput {
+    pub fn new(sample_rate: usize) -> Self {
+        Self {
+            sample_rate,
+            amplitude: 0.0,
+            blip: BlipBuf::new(CPU_CLOCK, sample_rate as u32),
+            high_pass: HighPass::new(sample_rate as u32),
+            samples: Vec::new(),
+        }
+    }
+
+    /// Produces `ratio` times as many samples as the nominal sample rate
+    pub fn adjust_rate(&mut self, ratio: f64) {
+        self.blip
+            .set_sample_rate(CPU_CLOCK, self.sample_rate as f64 * ratio);
+    }
+
+    pub fn set_amplitude(&mut self, clock: u32, amplitude: f32) {
+        if amplitude != self.amplitude {
+            self.blip.add_delta(clock, amplitude - self.amplitude);
+            self.amplitude = amplitude;
+        }
+    }
+
+    pub fn end_frame(&mut self, clocks: u32) {
+        self.blip.end_frame(clocks);
+        let start = self.samples.len();
+        self.blip.read_samples(&mut self.samples);
+        for sample in self.samples[start..].iter_mut() {
+            *sample = self.high_pass.filter(*sample);
+        }
+    }
+
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+}
@@ -1,3 +1,5 @@
+use crate::state::{Snapshot, State};
+
 pub(in crate::sound) struct PulseChannel {
     length_pattern_register: u8,
     volume_envelope_register: u8,
@@ -14,6 +16,11 @@ impl PulseChannel {
             frequency_high_register: 0,
         }
     }
+
+    pub fn get_volume(&self) -> f32 {
+        // TODO: the channel doesn't produce any sound yet
+        0.0
+    }
     pub fn read_byte(&self, addr: u16) -> u8 {
         match addr {
             0xff16 => self.length_pattern_register,
@@ -34,3 +41,12 @@ impl PulseChannel {
         }
     }
 }
+
+impl Snapshot for PulseChannel {
+    fn snapshot(&mut self, state: &mut State) {
+        state.u8(&mut self.length_pattern_register);
+        state.u8(&mut self.volume_envelope_register);
+        state.u8(&mut self.frequency_low_register);
+        state.u8(&mut self.frequency_high_register);
+    }
+}
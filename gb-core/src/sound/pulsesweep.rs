@@ -1,4 +1,5 @@
 use crate::sound::common::FRAME_TICKS;
+use crate::state::{Snapshot, State};
 
 pub(in crate::sound) struct PulseSweepChannel {
     nr10: u8,
@@ -231,3 +232,27 @@ impl PulseSweepChannel {
         }
     }
 }
+
+impl Snapshot for PulseSweepChannel {
+    fn snapshot(&mut self, state: &mut State) {
+        for register in [
+            &mut self.nr10,
+            &mut self.nr11,
+            &mut self.nr12,
+            &mut self.nr13,
+            &mut self.nr14,
+        ] {
+            state.u8(register);
+        }
+        state.bool(&mut self.enabled);
+        state.u16(&mut self.duty_advance_countdown);
+        state.u8(&mut self.frame_step);
+        state.u8(&mut self.length_counter);
+        state.u8(&mut self.volume);
+        state.u8(&mut self.duty_index);
+        state.u8(&mut self.sweep_counter);
+        state.bool(&mut self.volume_envelope_active);
+        state.u8(&mut self.volume_envelope_sweep_counter);
+        state.u16(&mut self.frame_counter);
+    }
+}
@@ -0,0 +1,113 @@
+use alloc::{vec, vec::Vec};
+
+/// Fixed capacity FIFO of PCM samples.
+///
+/// Nothing is ever discarded silently: samples which don't fit anymore count as
+/// overrun, requested samples which aren't available yet count as underrun. Together
+/// with the fill level, this allows a frontend to adapt its pace to the emulation.
+pub(in crate::sound) struct AudioRing {
+    samples: Vec<f32>,
+    // index of the oldest sample
+    read: usize,
+    len: usize,
+    overruns: usize,
+    underruns: usize,
+}
+
+impl AudioRing {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            // an empty ring couldn't wrap around
+            samples: vec![0.0; capacity.max(1)],
+            read: 0,
+            len: 0,
+            overruns: 0,
+            underruns: 0,
+        }
+    }
+
+    /// Appends a sample, or counts an overrun if the ring is full
+    pub fn push(&mut self, sample: f32) {
+        if self.len == self.capacity() {
+            self.overruns += 1;
+            return;
+        }
+        let write = (self.read + self.len) % self.capacity();
+        self.samples[write] = sample;
+        self.len += 1;
+    }
+
+    /// Moves as many samples as available into `out` and returns their number. The
+    /// consumer needed `needed` of them right away, each one missing counts as underrun.
+    pub fn drain_into(&mut self, out: &mut [f32], needed: usize) -> usize {
+        let count = self.len.min(out.len());
+        self.underruns += needed.saturating_sub(count);
+
+        let first = count.min(self.capacity() - self.read);
+        out[..first].copy_from_slice(&self.samples[self.read..self.read + first]);
+        out[first..count].copy_from_slice(&self.samples[..count - first]);
+
+        self.read = (self.read + count) % self.capacity();
+        self.len -= count;
+        count
+    }
+
+    /// Drops all samples, but keeps the counters
+    pub fn clear(&mut self) {
+        self.read = 0;
+        self.len = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn overruns(&self) -> usize {
+        self.overruns
+    }
+
+    pub fn underruns(&self) -> usize {
+        self.underruns
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AudioRing;
+
+    #[test]
+    fn wraps_around_and_counts_overruns_and_underruns() {
+        let mut ring = AudioRing::new(4);
+        for sample in [1.0, 2.0, 3.0] {
+            ring.push(sample);
+        }
+
+        let mut out = [0.0; 2];
+        assert_eq!(ring.drain_into(&mut out, 2), 2);
+        assert_eq!(out, [1.0, 2.0]);
+
+        for sample in [4.0, 5.0, 6.0, 7.0] {
+            ring.push(sample);
+        }
+        assert_eq!(ring.len(), 4);
+        assert_eq!(ring.overruns(), 1);
+
+        // space left in `out` is no underrun, only samples which were needed
+        let mut out = [0.0; 8];
+        assert_eq!(ring.drain_into(&mut out, 6), 4);
+        assert_eq!(out[..4], [3.0, 4.0, 5.0, 6.0]);
+        assert_eq!(ring.underruns(), 2);
+    }
+
+    #[test]
+    fn zero_capacity() {
+        let mut ring = AudioRing::new(0);
+        ring.push(1.0);
+        let mut out = [0.0; 2];
+        assert_eq!(ring.drain_into(&mut out, 0), 1);
+    }
+}
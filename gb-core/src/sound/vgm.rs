@@ -0,0 +1,106 @@
+use alloc::{vec, vec::Vec};
+
+use crate::sound::common::CPU_CLOCK;
+
+// VGM files count time in samples of 44.1kHz, independent of the audio output
+const VGM_SAMPLE_RATE: u64 = 44_100;
+const VGM_VERSION: u32 = 0x161;
+// The GB DMG clock field at 0x80 requires the full header of version 1.61
+const HEADER_SIZE: usize = 0x100;
+
+const CMD_GB_DMG_WRITE: u8 = 0xb3;
+const CMD_WAIT: u8 = 0x61;
+const CMD_WAIT_SHORT: u8 = 0x70;
+const CMD_END: u8 = 0x66;
+
+/// Records writes to the sound registers 0xff10-0xff3f and exports them as VGM file
+pub(in crate::sound) struct VgmLogger {
+    // cycle at which the log was started
+    start: u64,
+    // cycle, register offset from 0xff10 and value of every write
+    writes: Vec<(u64, u8, u8)>,
+}
+
+impl VgmLogger {
+    pub fn new(start: u64) -> Self {
+        Self {
+            start,
+            writes: Vec::new(),
+        }
+    }
+
+    pub fn log(&mut self, cycle: u64, addr: u16, value: u8) {
+        if let 0xff10..=0xff3f = addr {
+            self.writes.push((cycle, (addr - 0xff10) as u8, value));
+        }
+    }
+
+    /// Builds the VGM file of all writes up to the given cycle
+    pub fn finish(self, end: u64) -> Vec<u8> {
+        let mut vgm = vec![0u8; HEADER_SIZE];
+
+        let mut samples = 0;
+        for &(cycle, register, value) in self.writes.iter() {
+            let at = self.to_samples(cycle);
+            write_wait(&mut vgm, at - samples);
+            samples = at;
+            vgm.extend_from_slice(&[CMD_GB_DMG_WRITE, register, value]);
+        }
+        let total_samples = self.to_samples(end);
+        write_wait(&mut vgm, total_samples - samples);
+        vgm.push(CMD_END);
+
+        let eof_offset = vgm.len() as u32 - 0x04;
+        let data_offset = HEADER_SIZE as u32 - 0x34;
+        vgm[0x00..0x04].copy_from_slice(b"Vgm ");
+        vgm[0x04..0x08].copy_from_slice(&eof_offset.to_le_bytes());
+        vgm[0x08..0x0c].copy_from_slice(&VGM_VERSION.to_le_bytes());
+        vgm[0x18..0x1c].copy_from_slice(&(total_samples as u32).to_le_bytes());
+        vgm[0x34..0x38].copy_from_slice(&data_offset.to_le_bytes());
+        vgm[0x80..0x84].copy_from_slice(&CPU_CLOCK.to_le_bytes());
+        vgm
+    }
+
+    fn to_samples(&self, cycle: u64) -> u64 {
+        (cycle - self.start) * VGM_SAMPLE_RATE / CPU_CLOCK as u64
+    }
+}
+
+fn write_wait(vgm: &mut Vec<u8>, mut samples: u64) {
+    while samples > 0 {
+        if samples <= 16 {
+            vgm.push(CMD_WAIT_SHORT + (samples - 1) as u8);
+            return;
+        }
+        let wait = samples.min(u16::MAX as u64);
+        vgm.push(CMD_WAIT);
+        vgm.extend_from_slice(&(wait as u16).to_le_bytes());
+        samples -= wait;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VgmLogger;
+
+    #[test]
+    fn export_writes_with_waits() {
+        let mut logger = VgmLogger::new(1000);
+        logger.log(1000, 0xff26, 0x80);
+        logger.log(1000, 0xff40, 0x91);
+        // a second later
+        logger.log(1000 + 4_194_304, 0xff3f, 0x12);
+
+        let vgm = logger.finish(1000 + 4_194_304 + 950);
+
+        assert_eq!(vgm[0..4], *b"Vgm ");
+        assert_eq!(vgm[0x04..0x08], (vgm.len() as u32 - 4).to_le_bytes());
+        assert_eq!(vgm[0x08..0x0c], 0x161u32.to_le_bytes());
+        assert_eq!(vgm[0x18..0x1c], 44_109u32.to_le_bytes());
+        assert_eq!(vgm[0x80..0x84], 4_194_304u32.to_le_bytes());
+        assert_eq!(
+            vgm[0x100..],
+            [0xb3, 0x16, 0x80, 0x61, 0x44, 0xac, 0xb3, 0x2f, 0x12, 0x78, 0x66]
+        );
+    }
+}
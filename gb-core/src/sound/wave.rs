@@ -1,3 +1,5 @@
+use crate::state::{Snapshot, State};
+
 use log::error;
 
 const WAVE_PATTERN_SIZE: usize = 0x40;
@@ -23,6 +25,11 @@ impl WaveChannel {
         }
     }
 
+    pub fn get_volume(&self) -> f32 {
+        // TODO: the channel doesn't produce any sound yet
+        0.0
+    }
+
     pub fn read_byte(&self, addr: u16) -> u8 {
         match addr {
             0xff1a => self.on_off_register,
@@ -59,3 +66,14 @@ impl WaveChannel {
         }
     }
 }
+
+impl Snapshot for WaveChannel {
+    fn snapshot(&mut self, state: &mut State) {
+        state.u8(&mut self.on_off_register);
+        state.u8(&mut self.length_register);
+        state.u8(&mut self.output_level_register);
+        state.u8(&mut self.frequency_low_register);
+        state.u8(&mut self.frequency_high_register);
+        state.bytes(&mut self.wave_pattern);
+    }
+}
@@ -0,0 +1,63 @@
+use alloc::vec::Vec;
+
+/// Direction of a state transfer. Components describe their state once, field by field,
+/// and the same description either appends the fields to a buffer or reads them back.
+pub(crate) enum State<'a> {
+    Save(&'a mut Vec<u8>),
+    Load(&'a [u8]),
+}
+
+impl State<'_> {
+    pub fn bytes(&mut self, value: &mut [u8]) {
+        match self {
+            State::Save(out) => out.extend_from_slice(value),
+            State::Load(input) => {
+                let (head, rest) = core::mem::take(input).split_at(value.len());
+                value.copy_from_slice(head);
+                *input = rest;
+            }
+        }
+    }
+
+    pub fn u8(&mut self, value: &mut u8) {
+        self.bytes(core::slice::from_mut(value));
+    }
+
+    pub fn bool(&mut self, value: &mut bool) {
+        let mut byte = *value as u8;
+        self.u8(&mut byte);
+        *value = byte != 0;
+    }
+
+    pub fn u16(&mut self, value: &mut u16) {
+        let mut bytes = value.to_le_bytes();
+        self.bytes(&mut bytes);
+        *value = u16::from_le_bytes(bytes);
+    }
+
+    pub fn u64(&mut self, value: &mut u64) {
+        let mut bytes = value.to_le_bytes();
+        self.bytes(&mut bytes);
+        *value = u64::from_le_bytes(bytes);
+    }
+
+    pub fn usize(&mut self, value: &mut usize) {
+        let mut wide = *value as u64;
+        self.u64(&mut wide);
+        *value = wide as usize;
+    }
+
+    /// A buffer whose length changes, stored with its length in front
+    pub fn vec(&mut self, value: &mut Vec<u8>) {
+        let mut len = value.len();
+        self.usize(&mut len);
+        value.resize(len, 0);
+        self.bytes(value);
+    }
+}
+
+/// Machine state which is saved for rewinding. Host side state like audio output,
+/// hooks and the debugger is left out.
+pub(crate) trait Snapshot {
+    fn snapshot(&mut self, state: &mut State);
+}
@@ -0,0 +1,348 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+#[cfg(feature = "png")]
+use std::{
+    fs::File,
+    io,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "png")]
+use crate::board::Config;
+use crate::board::{Board, FRAME_TICKS};
+use crate::debugger::StopReason;
+use crate::ppu::{HEIGHT, WIDTH};
+
+/// How the result of a test rom is detected
+#[derive(Copy, Clone, Debug)]
+pub enum TestRom<'a> {
+    /// Blargg's tests, which print their result over the serial port and into the
+    /// cartridge ram
+    Blargg,
+    /// Mooneye's tests, which signal their result through the registers at `LD B,B`
+    Mooneye,
+    /// Tests which draw a screen to compare against a reference, such as dmg-acid2 and
+    /// cgb-acid2. The reference holds the RGB bytes of every pixel. The test ends at
+    /// `LD B,B` or when the frames run out.
+    Screenshot { reference: &'a [u8] },
+}
+
+/// The suite of a test rom file, which decides how its result is detected
+#[cfg(feature = "png")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TestSuite {
+    Blargg,
+    Mooneye,
+    Acid2,
+}
+
+#[cfg(feature = "png")]
+impl TestSuite {
+    /// Recognizes the suite by the path of a rom below the rom directory. Roms below a
+    /// `mooneye` directory are mooneye tests, roms named like `*-acid2` are compared
+    /// against the PNG screenshot next to them, and all others are blargg tests.
+    pub fn of(path: &Path) -> Self {
+        let stem = path.file_stem().map(|stem| stem.to_string_lossy());
+        let mooneye = path
+            .parent()
+            .into_iter()
+            .flat_map(Path::components)
+            .any(|dir| dir.as_os_str().to_string_lossy().contains("mooneye"));
+        if stem.is_some_and(|stem| stem.ends_with("acid2")) {
+            TestSuite::Acid2
+        } else if mooneye {
+            TestSuite::Mooneye
+        } else {
+            TestSuite::Blargg
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TestOutcome {
+    Passed,
+    /// Failed with the output of the test
+    Failed(String),
+    TimedOut,
+}
+
+// Registers B, C, D, E, H and L of a passed mooneye test
+const MOONEYE_PASS: [u8; 6] = [3, 5, 8, 13, 21, 34];
+const MOONEYE_FAIL: [u8; 6] = [0x42; 6];
+
+// Blargg's tests mark the result in cartridge ram with this signature
+const BLARGG_SIGNATURE: [u8; 3] = [0xde, 0xb0, 0x61];
+const BLARGG_RUNNING: u8 = 0x80;
+
+/// Runs a test rom headlessly for at most `max_frames` and reports its result
+pub fn run_test_rom(board: &mut Board, test: TestRom, max_frames: usize) -> TestOutcome {
+    match test {
+        TestRom::Blargg => run_blargg(board, max_frames),
+        TestRom::Mooneye => {
+            board.set_software_breakpoints(true);
+            match board.run_until_break(max_frames * FRAME_TICKS) {
+                StopReason::SoftwareBreakpoint(_) => {
+                    let r = board.registers();
+                    match [r.b, r.c, r.d, r.e, r.h, r.l] {
+                        MOONEYE_PASS => TestOutcome::Passed,
+                        MOONEYE_FAIL => TestOutcome::Failed("failure registers".to_string()),
+                        registers => TestOutcome::Failed(format!("registers {:02x?}", registers)),
+                    }
+                }
+                _ => TestOutcome::TimedOut,
+            }
+        }
+        TestRom::Screenshot { reference } => {
+            board.set_software_breakpoints(true);
+            board.run_until_break(max_frames * FRAME_TICKS);
+            // let the last frame be drawn completely
+            board.run_to_next_frame();
+            let mismatches = frame_mismatches(board, reference);
+            if mismatches == 0 {
+                TestOutcome::Passed
+            } else {
+                TestOutcome::Failed(format!("{} pixels differ", mismatches))
+            }
+        }
+    }
+}
+
+fn run_blargg(board: &mut Board, max_frames: usize) -> TestOutcome {
+    let mut serial = Vec::new();
+    for _ in 0..max_frames {
+        board.run_to_next_frame();
+        serial.extend(board.take_serial_output());
+
+        let output = String::from_utf8_lossy(&serial);
+        if output.contains("Passed") {
+            return TestOutcome::Passed;
+        }
+        if output.contains("Failed") {
+            return TestOutcome::Failed(output.trim().to_string());
+        }
+
+        let signature = [board.peek(0xa001), board.peek(0xa002), board.peek(0xa003)];
+        let status = board.peek(0xa000);
+        if signature == BLARGG_SIGNATURE && status != BLARGG_RUNNING {
+            let text = blargg_text(board);
+            return if status == 0 {
+                TestOutcome::Passed
+            } else {
+                TestOutcome::Failed(text)
+            };
+        }
+    }
+    TestOutcome::TimedOut
+}
+
+// The zero terminated text after the signature
+fn blargg_text(board: &Board) -> String {
+    let text: Vec<u8> = (0xa004..0xc000)
+        .map(|addr| board.peek(addr))
+        .take_while(|&c| c != 0)
+        .collect();
+    String::from_utf8_lossy(&text).trim().to_string()
+}
+
+fn frame_mismatches(board: &Board, reference: &[u8]) -> usize {
+    if reference.len() != WIDTH * HEIGHT * 3 {
+        return WIDTH * HEIGHT;
+    }
+    board
+        .frame()
+        .iter()
+        .flatten()
+        .zip(reference.chunks(3))
+        .filter(|(color, rgb)| [color.r, color.g, color.b] != **rgb)
+        .count()
+}
+
+/// Finds the `.gb` and `.gbc` roms below `directory`, sorted and relative to it
+#[cfg(feature = "png")]
+pub fn find_test_roms(directory: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut roms = Vec::new();
+    collect_roms(directory, Path::new(""), &mut roms)?;
+    Ok(roms)
+}
+
+#[cfg(feature = "png")]
+fn collect_roms(directory: &Path, relative: &Path, roms: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut entries = Vec::new();
+    for entry in std::fs::read_dir(directory.join(relative))? {
+        entries.push(relative.join(entry?.file_name()));
+    }
+    entries.sort();
+    for entry in entries {
+        if directory.join(&entry).is_dir() {
+            collect_roms(directory, &entry, roms)?;
+        } else if matches!(
+            entry.extension().and_then(|e| e.to_str()),
+            Some("gb") | Some("gbc")
+        ) {
+            roms.push(entry);
+        }
+    }
+    Ok(())
+}
+
+/// Runs the rom at `rom` below `directory` as a test of the suite its relative path
+/// belongs to. A rom which makes the emulator panic counts as failed, so that it
+/// doesn't stop the remaining roms.
+#[cfg(feature = "png")]
+pub fn run_test_rom_file(
+    directory: &Path,
+    rom: &Path,
+    max_frames: usize,
+) -> Result<(TestSuite, TestOutcome), String> {
+    let path = directory.join(rom);
+    let data = std::fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let suite = TestSuite::of(rom);
+    let reference = match suite {
+        TestSuite::Acid2 => load_png(&path.with_extension("png"))?,
+        _ => Vec::new(),
+    };
+    let test = match suite {
+        TestSuite::Blargg => TestRom::Blargg,
+        TestSuite::Mooneye => TestRom::Mooneye,
+        TestSuite::Acid2 => TestRom::Screenshot {
+            reference: &reference,
+        },
+    };
+
+    let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut board = Board::no_boot(&data, Config::default());
+        run_test_rom(&mut board, test, max_frames)
+    }));
+    let outcome = match outcome {
+        Ok(outcome) => outcome,
+        Err(payload) => {
+            let message = match payload.downcast_ref::<&str>() {
+                Some(message) => message.to_string(),
+                None => payload
+                    .downcast_ref::<String>()
+                    .cloned()
+                    .unwrap_or_default(),
+            };
+            TestOutcome::Failed(format!("panicked: {}", message))
+        }
+    };
+    Ok((suite, outcome))
+}
+
+/// Reads a PNG file as RGB bytes
+#[cfg(feature = "png")]
+fn load_png(path: &Path) -> Result<Vec<u8>, String> {
+    let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+    let mut buffer = alloc::vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(|e| e.to_string())?;
+    buffer.truncate(info.buffer_size());
+
+    let rgb = match info.color_type {
+        png::ColorType::Rgb => buffer,
+        png::ColorType::Rgba => buffer.chunks(4).flat_map(|p| [p[0], p[1], p[2]]).collect(),
+        png::ColorType::Grayscale => buffer.iter().flat_map(|&g| [g, g, g]).collect(),
+        png::ColorType::GrayscaleAlpha => buffer.chunks(2).flat_map(|p| [p[0]; 3]).collect(),
+        png::ColorType::Indexed => return Err("Unexpanded palette image".to_string()),
+    };
+    Ok(rgb)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec, vec::Vec};
+
+    use super::{run_test_rom, TestOutcome, TestRom};
+    use crate::board::{Board, Config};
+
+    fn game(code: &[u8]) -> Vec<u8> {
+        let mut game = vec![0u8; 0x8000];
+        game[0x0100..0x0100 + code.len()].copy_from_slice(code);
+        game
+    }
+
+    fn mooneye_game(registers: [u8; 6]) -> Vec<u8> {
+        let mut code = Vec::new();
+        // LD B,n to LD L,n, then LD B,B and JR -2
+        for (opcode, value) in [0x06, 0x0e, 0x16, 0x1e, 0x26, 0x2e].iter().zip(registers) {
+            code.extend([*opcode, value]);
+        }
+        code.extend([0x40, 0x18, 0xfe]);
+        game(&code)
+    }
+
+    fn mooneye(registers: [u8; 6]) -> TestOutcome {
+        let mut board = Board::no_boot(&mooneye_game(registers), Config::default());
+        run_test_rom(&mut board, TestRom::Mooneye, 10)
+    }
+
+    #[test]
+    fn mooneye_registers() {
+        assert_eq!(mooneye([3, 5, 8, 13, 21, 34]), TestOutcome::Passed);
+        assert_eq!(
+            mooneye([0x42; 6]),
+            TestOutcome::Failed("failure registers".to_string())
+        );
+    }
+
+    fn blargg(status: u8) -> TestOutcome {
+        let mut code = vec![0x3e, 0x0a, 0xea, 0x00, 0x00]; // enable the cartridge ram
+                                                           // the signature, a text and the status, each stored by LD A,n and LD (nn),A
+        for (addr, value) in [
+            (0xa001u16, 0xde),
+            (0xa002, 0xb0),
+            (0xa003, 0x61),
+            (0xa004, b'X'),
+            (0xa000, status),
+        ] {
+            let [low, high] = addr.to_le_bytes();
+            code.extend([0x3e, value, 0xea, low, high]);
+        }
+        code.extend([0x18, 0xfe]);
+        let mut game = game(&code);
+        game[0x0147] = 0x03; // MBC1 with ram
+        game[0x0149] = 0x02; // 8KB
+        let mut board = Board::no_boot(&game, Config::default());
+        run_test_rom(&mut board, TestRom::Blargg, 10)
+    }
+
+    #[test]
+    fn blargg_signature() {
+        assert_eq!(blargg(0), TestOutcome::Passed);
+        assert_eq!(blargg(1), TestOutcome::Failed("X".to_string()));
+        assert_eq!(blargg(0x80), TestOutcome::TimedOut);
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn suites_of_rom_files() {
+        use super::{find_test_roms, run_test_rom_file, TestSuite};
+        use std::path::Path;
+
+        // the names in the path above the rom directory don't matter
+        let directory = std::env::temp_dir().join(format!("acid2-mooneye-{}", std::process::id()));
+        std::fs::create_dir_all(directory.join("mooneye-test-suite")).unwrap();
+        std::fs::create_dir_all(directory.join("blargg")).unwrap();
+        let passed = mooneye_game([3, 5, 8, 13, 21, 34]);
+        std::fs::write(directory.join("mooneye-test-suite/pass.gb"), &passed).unwrap();
+        std::fs::write(directory.join("blargg/notes.txt"), "").unwrap();
+
+        let roms = find_test_roms(&directory).unwrap();
+        assert_eq!(roms, [Path::new("mooneye-test-suite/pass.gb")]);
+        let result = run_test_rom_file(&directory, &roms[0], 10);
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(result, Ok((TestSuite::Mooneye, TestOutcome::Passed)));
+
+        assert_eq!(TestSuite::of(Path::new("cpu_instrs.gb")), TestSuite::Blargg);
+        assert_eq!(
+            TestSuite::of(Path::new("acid2/dmg-acid2.gb")),
+            TestSuite::Acid2
+        );
+    }
+}
@@ -1,10 +1,7 @@
-use std::cell::RefCell;
-use std::rc::Rc;
-
 use crate::irq::Irq;
+use crate::state::{Snapshot, State};
 
 pub(crate) struct Timer {
-    irq: Rc<RefCell<Irq>>,
     divider: u8,
     timer_counter: u8,
     timer_modulo: u8,
@@ -14,9 +11,8 @@ pub(crate) struct Timer {
 }
 
 impl Timer {
-    pub fn new(irq: Rc<RefCell<Irq>>) -> Self {
+    pub fn new() -> Self {
         Self {
-            irq,
             divider: 0,
             timer_counter: 0,
             timer_modulo: 0,
@@ -26,7 +22,13 @@ impl Timer {
         }
     }
 
-    pub fn step(&mut self, steps: u8) {
+    /// Sets the internal counter, whose upper byte is DIV
+    pub fn set_divider(&mut self, value: u16) {
+        self.divider = (value >> 8) as u8;
+        self.internal_divider = value & 0xff;
+    }
+
+    pub fn step(&mut self, steps: u8, irq: &mut Irq) {
         let steps = steps as u16;
         self.internal_divider += steps;
         if self.internal_divider >= 256 {
@@ -49,7 +51,7 @@ impl Timer {
                 self.timer_counter = self.timer_counter.wrapping_add(1);
                 if self.timer_counter == 0 {
                     self.timer_counter = self.timer_modulo;
-                    self.irq.borrow_mut().timer_interrupt();
+                    irq.timer_interrupt();
                 }
             }
         }
@@ -75,3 +77,14 @@ impl Timer {
         }
     }
 }
+
+impl Snapshot for Timer {
+    fn snapshot(&mut self, state: &mut State) {
+        state.u8(&mut self.divider);
+        state.u8(&mut self.timer_counter);
+        state.u8(&mut self.timer_modulo);
+        state.u8(&mut self.timer_control);
+        state.u16(&mut self.internal_divider);
+        state.u16(&mut self.internal_timer);
+    }
+}
@@ -0,0 +1,80 @@
+use alloc::{boxed::Box, format};
+
+use crate::debugger::CpuRegisters;
+
+/// Layout of the lines of an execution trace
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// `A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02`, as
+    /// compared by Gameboy Doctor
+    Doctor,
+    /// The Gameboy Doctor line followed by the executed clock cycles and LY, such as
+    /// `CY:0 LY:00`
+    Cycles,
+}
+
+/// Receives one line per executed instruction, before it's executed
+pub type TraceSink = Box<dyn FnMut(&str) + Send>;
+
+pub(crate) struct Tracer {
+    sink: TraceSink,
+    format: TraceFormat,
+}
+
+impl Tracer {
+    pub fn new(sink: TraceSink, format: TraceFormat) -> Self {
+        Self { sink, format }
+    }
+
+    pub fn trace(&mut self, registers: &CpuRegisters, pc_memory: [u8; 4], cycles: u64, ly: u8) {
+        let r = registers;
+        let mut line = format!(
+            "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+            r.a, r.f, r.b, r.c, r.d, r.e, r.h, r.l, r.sp, r.pc,
+            pc_memory[0], pc_memory[1], pc_memory[2], pc_memory[3]
+        );
+        if self.format == TraceFormat::Cycles {
+            line.push_str(&format!(" CY:{} LY:{:02X}", cycles, ly));
+        }
+        (self.sink)(&line);
+    }
+}
+
+// the sink has to be Send, so the test collects lines through a Mutex
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::{TraceFormat, Tracer};
+    use crate::debugger::CpuRegisters;
+
+    #[test]
+    fn doctor_format() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let sink_lines = Arc::clone(&lines);
+        let sink = Box::new(move |line: &str| sink_lines.lock().unwrap().push(line.to_string()));
+        let registers = CpuRegisters {
+            a: 0x01,
+            f: 0xb0,
+            b: 0x00,
+            c: 0x13,
+            d: 0x00,
+            e: 0xd8,
+            h: 0x01,
+            l: 0x4d,
+            sp: 0xfffe,
+            pc: 0x0100,
+            ime: false,
+        };
+
+        let mut tracer = Tracer::new(sink, TraceFormat::Doctor);
+        tracer.trace(&registers, [0x00, 0xc3, 0x13, 0x02], 0, 0x90);
+        tracer.format = TraceFormat::Cycles;
+        tracer.trace(&registers, [0x00, 0xc3, 0x13, 0x02], 4, 0x90);
+
+        let doctor = "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02";
+        let lines = lines.lock().unwrap();
+        assert_eq!(lines[0], doctor);
+        assert_eq!(lines[1], format!("{} CY:4 LY:90", doctor));
+    }
+}
@@ -0,0 +1,350 @@
+"""Writes the smoke vectors in smoke/ from a model of the SM83 written for them.
+
+The vectors use the JSON format of the SingleStepTests sm83 suite, so that the
+same runner works for both. They only cover a few opcodes of every group. Run
+the full upstream suite by pointing $SM83_TESTS at its v1 directory.
+"""
+
+import json, os, random
+
+OUT = os.path.join(os.path.dirname(os.path.abspath(__file__)), 'smoke')
+Z, N, H, C = 0x80, 0x40, 0x20, 0x10
+R8 = ['b', 'c', 'd', 'e', 'h', 'l', None, 'a']
+
+
+class M:
+    def __init__(self, rng, pc, regs, ime):
+        self.rng = rng
+        self.r = dict(regs)
+        self.r['pc'] = pc
+        self.ime = ime
+        self.ei = 0
+        self.mem = {}
+        self.init_mem = {}
+        self.cycles = []
+
+    def peek(self, a):
+        a &= 0xffff
+        if a not in self.mem:
+            v = self.rng.randrange(256)
+            self.mem[a] = v
+            self.init_mem[a] = v
+        return self.mem[a]
+
+    def read(self, a):
+        a &= 0xffff
+        v = self.peek(a)
+        self.cycles.append([a, v, 'r-m'])
+        return v
+
+    def write(self, a, v):
+        a &= 0xffff
+        if a not in self.mem:
+            self.init_mem[a] = self.rng.randrange(256)
+        self.mem[a] = v & 0xff
+        self.cycles.append([a, v & 0xff, '-wm'])
+
+    def internal(self):
+        self.cycles.append(None)
+
+    def fetch(self):
+        v = self.read(self.r['pc'])
+        self.r['pc'] = (self.r['pc'] + 1) & 0xffff
+        return v
+
+    def fetch16(self):
+        lo = self.fetch()
+        return lo | (self.fetch() << 8)
+
+    def pair(self, p):
+        if p == 'af':
+            return (self.r['a'] << 8) | self.r['f']
+        if p == 'sp':
+            return self.r['sp']
+        return (self.r[p[0]] << 8) | self.r[p[1]]
+
+    def set_pair(self, p, v):
+        v &= 0xffff
+        if p == 'sp':
+            self.r['sp'] = v
+        elif p == 'af':
+            self.r['a'], self.r['f'] = v >> 8, v & 0xf0
+        else:
+            self.r[p[0]], self.r[p[1]] = v >> 8, v & 0xff
+
+    def flag(self, f):
+        return 1 if self.r['f'] & f else 0
+
+    def set_flags(self, z=None, n=None, h=None, c=None):
+        f = self.r['f']
+        for bit, v in ((Z, z), (N, n), (H, h), (C, c)):
+            if v is not None:
+                f = (f | bit) if v else (f & ~bit)
+        self.r['f'] = f & 0xf0
+
+    def get8(self, i):
+        if R8[i] is None:
+            return self.read(self.pair('hl'))
+        return self.r[R8[i]]
+
+    def set8(self, i, v):
+        if R8[i] is None:
+            self.write(self.pair('hl'), v)
+        else:
+            self.r[R8[i]] = v & 0xff
+
+    def push(self, v):
+        self.internal()
+        sp = self.r['sp']
+        self.write(sp - 1, v >> 8)
+        self.write(sp - 2, v & 0xff)
+        self.r['sp'] = (sp - 2) & 0xffff
+
+    def pop(self):
+        sp = self.r['sp']
+        lo = self.read(sp)
+        hi = self.read(sp + 1)
+        self.r['sp'] = (sp + 2) & 0xffff
+        return lo | (hi << 8)
+
+    def cond(self, y):
+        return [not self.flag(Z), self.flag(Z), not self.flag(C), self.flag(C)][y]
+
+
+def alu(m, op, b):
+    a = m.r['a']
+    c = m.flag(C)
+    if op in (0, 1):
+        carry = c if op == 1 else 0
+        r = a + b + carry
+        m.set_flags(r & 0xff == 0, 0, (a & 0xf) + (b & 0xf) + carry > 0xf, r > 0xff)
+        m.r['a'] = r & 0xff
+    elif op in (2, 3, 7):
+        carry = c if op == 3 else 0
+        r = a - b - carry
+        m.set_flags(r & 0xff == 0, 1, (a & 0xf) - (b & 0xf) - carry < 0, r < 0)
+        if op != 7:
+            m.r['a'] = r & 0xff
+    elif op == 4:
+        m.r['a'] = a & b
+        m.set_flags(m.r['a'] == 0, 0, 1, 0)
+    elif op == 5:
+        m.r['a'] = a ^ b
+        m.set_flags(m.r['a'] == 0, 0, 0, 0)
+    else:
+        m.r['a'] = a | b
+        m.set_flags(m.r['a'] == 0, 0, 0, 0)
+
+
+def rotate(m, y, v):
+    c = m.flag(C)
+    if y == 0:
+        out = v >> 7; r = (v << 1 | out)
+    elif y == 1:
+        out = v & 1; r = (v >> 1 | out << 7)
+    elif y == 2:
+        out = v >> 7; r = (v << 1 | c)
+    elif y == 3:
+        out = v & 1; r = (v >> 1 | c << 7)
+    elif y == 4:
+        out = v >> 7; r = v << 1
+    elif y == 5:
+        out = v & 1; r = (v >> 1) | (v & 0x80)
+    elif y == 6:
+        out = 0; r = ((v & 0xf) << 4) | (v >> 4)
+    else:
+        out = v & 1; r = v >> 1
+    r &= 0xff
+    m.set_flags(r == 0, 0, 0, out)
+    return r
+
+
+def execute(m):
+    op = m.fetch()
+    x, y, z = op >> 6, (op >> 3) & 7, op & 7
+    pairs = ['bc', 'de', 'hl', 'sp']
+    if op == 0x00:
+        pass
+    elif x == 0 and z == 1 and y % 2 == 0:
+        m.set_pair(pairs[y // 2], m.fetch16())
+    elif op == 0x02:
+        m.write(m.pair('bc'), m.r['a'])
+    elif x == 0 and z == 3:
+        p = pairs[y // 2]
+        m.set_pair(p, m.pair(p) + (1 if y % 2 == 0 else -1))
+        m.internal()
+    elif x == 0 and z == 4:
+        v = m.get8(y)
+        r = (v + 1) & 0xff
+        m.set_flags(r == 0, 0, v & 0xf == 0xf)
+        m.set8(y, r)
+    elif x == 0 and z == 5:
+        v = m.get8(y)
+        r = (v - 1) & 0xff
+        m.set_flags(r == 0, 1, v & 0xf == 0)
+        m.set8(y, r)
+    elif x == 0 and z == 6:
+        v = m.fetch()
+        m.set8(y, v)
+    elif op == 0x07:
+        m.r['a'] = rotate(m, 0, m.r['a'])
+        m.set_flags(z=0)
+    elif op == 0x08:
+        a = m.fetch16()
+        sp = m.r['sp']
+        m.write(a, sp & 0xff)
+        m.write(a + 1, sp >> 8)
+    elif x == 0 and z == 1:
+        hl, v = m.pair('hl'), m.pair(pairs[y // 2])
+        r = hl + v
+        m.set_flags(n=0, h=(hl & 0xfff) + (v & 0xfff) > 0xfff, c=r > 0xffff)
+        m.set_pair('hl', r)
+        m.internal()
+    elif op == 0x0a:
+        m.r['a'] = m.read(m.pair('bc'))
+    elif op == 0x18 or (x == 0 and z == 0 and y >= 4):
+        e = m.fetch()
+        if op == 0x18 or m.cond(y - 4):
+            m.r['pc'] = (m.r['pc'] + (e ^ 0x80) - 0x80) & 0xffff
+            m.internal()
+    elif op == 0x27:
+        a, c = m.r['a'], m.flag(C)
+        if not m.flag(N):
+            if c or a > 0x99:
+                a += 0x60; c = 1
+            if m.flag(H) or (a & 0xf) > 9:
+                a += 0x06
+        else:
+            if c:
+                a -= 0x60
+            if m.flag(H):
+                a -= 0x06
+        a &= 0xff
+        m.r['a'] = a
+        m.set_flags(z=a == 0, h=0, c=c)
+    elif op == 0x2a:
+        hl = m.pair('hl')
+        m.r['a'] = m.read(hl)
+        m.set_pair('hl', hl + 1)
+    elif op == 0x3f:
+        m.set_flags(n=0, h=0, c=not m.flag(C))
+    elif x == 1 and op != 0x76:
+        m.set8(y, m.get8(z))
+    elif x == 2:
+        alu(m, y, m.get8(z))
+    elif x == 3 and z == 6:
+        alu(m, y, m.fetch())
+    elif x == 3 and z == 0 and y < 4:
+        m.internal()
+        if m.cond(y):
+            m.r['pc'] = m.pop()
+            m.internal()
+    elif op in (0xc9, 0xd9):
+        m.r['pc'] = m.pop()
+        m.internal()
+        if op == 0xd9:
+            m.ime = 1
+    elif x == 3 and z == 1 and y % 2 == 0:
+        m.set_pair(['bc', 'de', 'hl', 'af'][y // 2], m.pop())
+    elif op == 0xc3 or (x == 3 and z == 2 and y < 4):
+        a = m.fetch16()
+        if op == 0xc3 or m.cond(y):
+            m.r['pc'] = a
+            m.internal()
+    elif op == 0xcd or (x == 3 and z == 4 and y < 4):
+        a = m.fetch16()
+        if op == 0xcd or m.cond(y):
+            m.push(m.r['pc'])
+            m.r['pc'] = a
+    elif x == 3 and z == 5:
+        m.push(m.pair(['bc', 'de', 'hl', 'af'][y // 2]))
+    elif x == 3 and z == 7:
+        m.push(m.r['pc'])
+        m.r['pc'] = y * 8
+    elif op == 0xe0:
+        m.write(0xff00 | m.fetch(), m.r['a'])
+    elif op == 0xf0:
+        m.r['a'] = m.read(0xff00 | m.fetch())
+    elif op in (0xe8, 0xf8):
+        e = m.fetch()
+        sp = m.r['sp']
+        m.set_flags(0, 0, (sp & 0xf) + (e & 0xf) > 0xf, (sp & 0xff) + e > 0xff)
+        r = (sp + (e ^ 0x80) - 0x80) & 0xffff
+        m.internal()
+        if op == 0xe8:
+            m.internal()
+            m.r['sp'] = r
+        else:
+            m.set_pair('hl', r)
+    elif op == 0xe9:
+        m.r['pc'] = m.pair('hl')
+    elif op == 0xf9:
+        m.r['sp'] = m.pair('hl')
+        m.internal()
+    elif op == 0xfa:
+        m.r['a'] = m.read(m.fetch16())
+    elif op == 0xf3:
+        m.ime = 0
+    elif op == 0xfb:
+        m.ei = 1
+    elif op == 0xcb:
+        cb = m.fetch()
+        x, y, z = cb >> 6, (cb >> 3) & 7, cb & 7
+        v = m.get8(z)
+        if x == 0:
+            m.set8(z, rotate(m, y, v))
+        elif x == 1:
+            m.set_flags(z=not (v >> y) & 1, n=0, h=1)
+        elif x == 2:
+            m.set8(z, v & ~(1 << y))
+        else:
+            m.set8(z, v | (1 << y))
+    else:
+        raise Exception('opcode %02x' % op)
+
+
+def vector(rng, code, name):
+    regs = {r: rng.randrange(256) for r in 'abcdehl'}
+    regs['f'] = rng.randrange(16) << 4
+    regs['sp'] = rng.randrange(0x10000)
+    pc = rng.randrange(0x10000)
+    ime = 0 if code[0] in (0xfb, 0xd9) else rng.randrange(2)
+    m = M(rng, pc, regs, ime)
+    for i, byte in enumerate(code + [rng.randrange(256), rng.randrange(256)]):
+        m.mem[(pc + i) & 0xffff] = byte
+        m.init_mem[(pc + i) & 0xffff] = byte
+    initial = dict(m.r, ime=m.ime)
+    execute(m)
+    ram = sorted(m.init_mem.items())
+    state_i = {k: initial[k] for k in ['pc', 'sp', 'a', 'b', 'c', 'd', 'e', 'f', 'h', 'l', 'ime']}
+    state_i['ie'] = 0
+    state_i['ram'] = [[a, v] for a, v in ram]
+    state_f = {k: m.r[k] for k in ['pc', 'sp', 'a', 'b', 'c', 'd', 'e', 'f', 'h', 'l']}
+    state_f['ime'] = m.ime
+    state_f['ie'] = 0
+    if m.ei:
+        state_f['ei'] = 1
+    state_f['ram'] = [[a, m.mem[a]] for a, _ in ram]
+    return {'name': name, 'initial': state_i, 'final': state_f, 'cycles': m.cycles}
+
+
+OPCODES = [
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x18, 0x20, 0x27,
+    0x2a, 0x31, 0x34, 0x35, 0x36, 0x38, 0x3f, 0x41, 0x46, 0x70, 0x80, 0x8e, 0x98, 0xa6, 0xaf,
+    0xb1, 0xb8, 0xc0, 0xc1, 0xc2, 0xc3, 0xc4, 0xc5, 0xc6, 0xc9, 0xcd, 0xd6, 0xd9, 0xdf, 0xe0,
+    0xe8, 0xe9, 0xee, 0xf0, 0xf1, 0xf3, 0xf5, 0xf8, 0xf9, 0xfa, 0xfb, 0xfe,
+]
+CB_OPCODES = [0x00, 0x06, 0x11, 0x1e, 0x27, 0x2f, 0x37, 0x3f, 0x46, 0x7f, 0x86, 0xc7, 0xfe]
+
+os.makedirs(OUT, exist_ok=True)
+rng = random.Random(83)
+for op in OPCODES:
+    vectors = [vector(rng, [op], '%02x %04d' % (op, i)) for i in range(6)]
+    with open(os.path.join(OUT, '%02x.json' % op), 'w') as f:
+        json.dump(vectors, f, separators=(', ', ': '))
+        f.write('\n')
+for op in CB_OPCODES:
+    vectors = [vector(rng, [0xcb, op], 'cb %02x %04d' % (op, i)) for i in range(6)]
+    with open(os.path.join(OUT, 'cb %02x.json' % op), 'w') as f:
+        json.dump(vectors, f, separators=(', ', ': '))
+        f.write('\n')
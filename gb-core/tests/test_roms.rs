@@ -0,0 +1,45 @@
+//! Runs the test roms found in `$GB_TEST_ROMS`, or `tests/roms` by default. The roms
+//! aren't part of the repository, so the test passes without them.
+
+use std::path::{Path, PathBuf};
+
+use gb_core::{find_test_roms, run_test_rom_file, TestOutcome};
+
+const MAX_FRAMES: usize = 60 * 60;
+
+fn rom_directory() -> PathBuf {
+    match std::env::var_os("GB_TEST_ROMS") {
+        Some(dir) => PathBuf::from(dir),
+        None => Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/roms"),
+    }
+}
+
+#[test]
+fn test_roms() {
+    let directory = rom_directory();
+    if !directory.is_dir() {
+        eprintln!("No test roms at {}, skipping", directory.display());
+        return;
+    }
+
+    let known_failures = include_str!("known_failures.txt");
+    let known_failures: Vec<&str> = known_failures
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+
+    let mut regressions = Vec::new();
+    for rom in find_test_roms(&directory).unwrap() {
+        let (_, outcome) = run_test_rom_file(&directory, &rom, MAX_FRAMES).unwrap();
+        let name = rom.to_string_lossy();
+        println!("{}: {:?}", name, outcome);
+        if outcome != TestOutcome::Passed && !known_failures.contains(&name.as_ref()) {
+            regressions.push(name.into_owned());
+        }
+    }
+    assert!(
+        regressions.is_empty(),
+        "Failing test roms: {:?}",
+        regressions
+    );
+}
@@ -1,20 +1,50 @@
 use std::fs::File;
-use std::io::Read;
-use std::path::PathBuf;
+use std::io::{BufWriter, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
 
 #[macro_use]
 extern crate clap;
 
+mod recorder;
+mod wav;
+
 use sdl2::audio;
 use sdl2::event;
 use sdl2::keyboard;
 
-use gb_core::{Board, Button, AUDIO_SAMPLE_RATE, HEIGHT, WIDTH};
+use gb_core::{
+    AudioChannel, Board, Button, Color, Config, DmgPalette, GbsPlayer, GdbStub, Model, PixelFormat,
+    TraceFormat, HEIGHT, SGB_HEIGHT, SGB_WIDTH, WIDTH,
+};
+
+use recorder::Recorder;
 
 const PIXEL_SCALE: usize = 2;
+const SAMPLE_RATE: usize = 48_000;
+const RECORDING_PATH: &str = "recording.wav";
+// Amount of stereo samples the audio device should have queued at any time
+const AUDIO_LATENCY: usize = 2 * 2048;
+// Strongest rate adjustment, when the audio queue is empty or twice as full as desired
+const MAX_RATE_ADJUSTMENT: f64 = 0.005;
+const TITLE: &str = "Rostiger Spieljunge";
+// Megabytes of snapshots which are kept for rewinding, unless set on the command line
+const REWIND_MEGABYTES: usize = 64;
+
+/// What the board is running
+enum Source {
+    Cartridge(Board),
+    Gbs(GbsPlayer),
+}
 
 struct GameBoy {
-    board: Board,
+    source: Source,
+    // debugger connection, which controls when the board runs
+    gdb: Option<GdbStub<TcpStream>>,
+    // steps back a frame instead of running one, while the rewind key is held
+    rewinding: bool,
+    // file the VGM log is written to, in GBS mode one per song next to it
+    vgm_path: Option<PathBuf>,
 }
 
 fn keycode_to_button(keycode: keyboard::Keycode) -> Option<Button> {
@@ -32,30 +62,141 @@ fn keycode_to_button(keycode: keyboard::Keycode) -> Option<Button> {
 }
 
 impl GameBoy {
-    fn new(board: Board) -> Self {
-        Self { board }
+    fn new(source: Source, gdb: Option<GdbStub<TcpStream>>, vgm_path: Option<PathBuf>) -> Self {
+        let mut gameboy = Self {
+            source,
+            gdb,
+            rewinding: false,
+            vgm_path,
+        };
+        if gameboy.vgm_path.is_some() {
+            gameboy.board().start_vgm_log();
+        }
+        gameboy
+    }
+
+    fn board(&mut self) -> &mut Board {
+        match &mut self.source {
+            Source::Cartridge(board) => board,
+            Source::Gbs(player) => player.board(),
+        }
+    }
+
+    fn title(&self) -> String {
+        match &self.source {
+            Source::Cartridge(_) => TITLE.to_string(),
+            Source::Gbs(player) => format!(
+                "{} - {} ({}/{})",
+                TITLE,
+                player.gbs().title(),
+                player.song() + 1,
+                player.gbs().song_count()
+            ),
+        }
+    }
+
+    /// Switches to the next or previous song in GBS mode, returns whether the key was
+    /// used for it. The VGM log of the previous song is written right away.
+    fn change_song(&mut self, keycode: keyboard::Keycode) -> Result<bool, String> {
+        let player = match &mut self.source {
+            Source::Gbs(player) => player,
+            Source::Cartridge(_) => return Ok(false),
+        };
+        let song = player.song();
+        let vgm = match keycode {
+            keyboard::Keycode::Right | keyboard::Keycode::N => player.next_song(),
+            keyboard::Keycode::Left | keyboard::Keycode::P => player.previous_song(),
+            _ => return Ok(false),
+        };
+        if let Some(vgm) = vgm {
+            self.write_vgm(vgm, song)?;
+        }
+        Ok(true)
+    }
+
+    /// Stops the VGM log and writes it
+    fn finish_vgm(&mut self) -> Result<(), String> {
+        let song = match &self.source {
+            Source::Gbs(player) => player.song(),
+            Source::Cartridge(_) => 0,
+        };
+        match self.board().stop_vgm_log() {
+            Some(vgm) => self.write_vgm(vgm, song),
+            None => Ok(()),
+        }
+    }
+
+    fn write_vgm(&self, vgm: Vec<u8>, song: u8) -> Result<(), String> {
+        let base = match &self.vgm_path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let path = match self.source {
+            Source::Gbs(_) => {
+                let stem = base.file_stem().unwrap_or_default().to_string_lossy();
+                base.with_file_name(format!("{}-{}.vgm", stem, song + 1))
+            }
+            Source::Cartridge(_) => base.clone(),
+        };
+        std::fs::write(path, vgm).map_err(|e| e.to_string())
+    }
+
+    fn next_frame(&mut self) {
+        match self.gdb.take() {
+            Some(mut gdb) => match gdb.poll(self.board()) {
+                Ok(()) if gdb.connected() => self.gdb = Some(gdb),
+                Ok(()) => println!("gdb disconnected"),
+                Err(e) => println!("gdb connection failed: {}", e),
+            },
+            // the oldest snapshot stays on screen once all were rewound
+            None if self.rewinding => {
+                self.board().rewind();
+            }
+            // present frames when the game finished drawing them
+            None => {
+                self.board().run_until_vblank();
+            }
+        }
+    }
+
+    /// Renders the last frame into the pixels of the texture, whose rows start `pitch`
+    /// bytes apart
+    fn render(&mut self, buffer: &mut [u8], pitch: usize) {
+        // the SGB shows the screen inside its border
+        if !self
+            .board()
+            .render_sgb_into(buffer, pitch, PixelFormat::Rgb24)
+        {
+            self.board().render_into(buffer, pitch, PixelFormat::Rgb24);
+        }
+    }
+
+    /// Fetches the audio of the last frame into `buffer` and returns it. Samples count as
+    /// underrun if the device has less than the desired latency queued without them.
+    fn audio<'a>(&mut self, buffer: &'a mut [f32], queued: usize) -> &'a [f32] {
+        let needed = AUDIO_LATENCY.saturating_sub(queued);
+        let samples = self.board().audio_into(buffer, needed);
+        &buffer[..samples]
     }
 
-    fn next_frame(&mut self) -> Vec<u8> {
-        self.board.run_to_next_frame();
-        let frame = self.board.frame();
-        frame
-            .into_iter()
-            .flat_map(|row| row.into_iter())
-            .flat_map(|c| [c.r, c.g, c.b])
-            .collect()
+    /// Speeds up or slows down audio production depending on how many samples the audio
+    /// device has queued, so that audio neither runs dry nor lags behind the video
+    fn adjust_audio_rate(&mut self, queued: usize) {
+        let deviation = (AUDIO_LATENCY as f64 - queued as f64) / AUDIO_LATENCY as f64;
+        let ratio = 1.0 + MAX_RATE_ADJUSTMENT * deviation.clamp(-1.0, 1.0);
+        self.board().adjust_audio_rate(ratio);
     }
 
-    fn audio(&mut self) -> Vec<f32> {
-        self.board.audio()
+    fn channel_audio(&mut self, channel: AudioChannel) -> Vec<f32> {
+        self.board().channel_audio(channel)
     }
 
     fn button_pressed(&mut self, button: Button) {
-        self.board.button_pressed(button);
+        self.board().button_pressed(button);
     }
 
     fn button_released(&mut self, button: Button) {
-        self.board.button_released(button);
+        self.board().button_released(button);
     }
 }
 
@@ -69,6 +210,74 @@ fn load_file(path: &str) -> Vec<u8> {
     data
 }
 
+/// Returns the path for the n-th recording of this session, so that toggling the
+/// recording doesn't overwrite earlier ones
+fn recording_path(base: &Path, recording: usize) -> PathBuf {
+    if recording == 0 {
+        return base.to_path_buf();
+    }
+    let stem = base.file_stem().unwrap_or_default().to_string_lossy();
+    base.with_file_name(format!("{}-{}.wav", stem, recording + 1))
+}
+
+/// Parses a palette preset name and applies the colors given for single palettes
+fn dmg_palette(matches: &clap::ArgMatches) -> Result<Option<DmgPalette>, String> {
+    let preset = match matches.value_of("PALETTE") {
+        None => None,
+        Some("grey") => Some(DmgPalette::GREY),
+        Some("pea-soup") => Some(DmgPalette::PEA_SOUP),
+        Some("pocket") => Some(DmgPalette::POCKET),
+        Some("light") => Some(DmgPalette::LIGHT),
+        Some(_) => return Err("Invalid palette, expected grey, pea-soup, pocket or light".into()),
+    };
+
+    let bg = matches
+        .value_of("BG_COLORS")
+        .map(parse_colors)
+        .transpose()?;
+    let obj0 = matches
+        .value_of("OBJ0_COLORS")
+        .map(parse_colors)
+        .transpose()?;
+    let obj1 = matches
+        .value_of("OBJ1_COLORS")
+        .map(parse_colors)
+        .transpose()?;
+    if preset.is_none() && bg.is_none() && obj0.is_none() && obj1.is_none() {
+        return Ok(None);
+    }
+
+    let mut palette = preset.unwrap_or_default();
+    palette.bg = bg.unwrap_or(palette.bg);
+    palette.obj0 = obj0.unwrap_or(palette.obj0);
+    palette.obj1 = obj1.unwrap_or(palette.obj1);
+    Ok(Some(palette))
+}
+
+/// Parses four comma separated hex colors like `e0f8d0,88c070,346856,081820`
+fn parse_colors(value: &str) -> Result<[Color; 4], String> {
+    let colors = value
+        .split(',')
+        .map(|color| u32::from_str_radix(color.trim(), 16).map(Color::rgb))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| format!("Invalid colors {}", value))?;
+    colors
+        .try_into()
+        .map_err(|_| format!("Expected four colors, got {}", value))
+}
+
+/// Accepts a single gdb connection on the local port
+fn wait_for_gdb(port: &str) -> Result<GdbStub<TcpStream>, String> {
+    let port: u16 = port.parse().map_err(|_| "Invalid gdb port")?;
+    let listener = TcpListener::bind(("127.0.0.1", port)).map_err(|e| e.to_string())?;
+    println!("Waiting for gdb on port {}", port);
+    let (stream, address) = listener.accept().map_err(|e| e.to_string())?;
+    println!("gdb connected from {}", address);
+    stream.set_nonblocking(true).map_err(|e| e.to_string())?;
+    stream.set_nodelay(true).map_err(|e| e.to_string())?;
+    Ok(GdbStub::new(stream))
+}
+
 fn main() -> Result<(), String> {
     env_logger::init();
 
@@ -77,6 +286,21 @@ fn main() -> Result<(), String> {
         (author: "radogost")
         (about: "A GameBoy emulator written in Rust")
         (@arg BOOT: --boot +takes_value "Boot rom file")
+        (@arg GBS: --gbs "Play a GBS music rip instead of a game, switch songs with left and right")
+        (@arg SAMPLE_RATE: --("sample-rate") +takes_value "Audio output sample rate in Hz")
+        (@arg RECORD: --record +takes_value "Record audio into a WAV file, toggled with R")
+        (@arg RECORD_CHANNELS: --("record-channels") "Additionally record every channel on its own")
+        (@arg MODEL: --model +takes_value "Force the model to emulate: dmg0, dmg, mgb, sgb, sgb2, cgb0, cgb or agb")
+        (@arg PALETTE: --palette +takes_value "Colors of monochrome games: grey, pea-soup, pocket or light")
+        (@arg BG_COLORS: --("bg-colors") +takes_value "Four hex colors for the background of monochrome games")
+        (@arg OBJ0_COLORS: --("obj0-colors") +takes_value "Four hex colors for the first object palette")
+        (@arg OBJ1_COLORS: --("obj1-colors") +takes_value "Four hex colors for the second object palette")
+        (@arg COLOR_CORRECTION: --("color-correction") "Show CGB colors as the real LCD does")
+        (@arg GDB: --gdb +takes_value "Wait for gdb to connect on the local TCP port")
+        (@arg TRACE: --trace +takes_value "Log the CPU state before every instruction into a file")
+        (@arg TRACE_CYCLES: --("trace-cycles") "Add the clock cycles and LY to every line of the trace")
+        (@arg VGM: --vgm +takes_value "Log all sound register writes into a VGM file, one per song with --gbs")
+        (@arg REWIND: --rewind +takes_value "Megabytes of snapshots kept for rewinding with Backspace, 0 turns it off")
         (@arg CARTRIDGE: +required "file with game data")
     )
     .get_matches();
@@ -84,24 +308,90 @@ fn main() -> Result<(), String> {
     let cartridge = matches.value_of("CARTRIDGE").unwrap();
     let cartridge_data = load_file(cartridge);
 
-    let board = if let Some(path) = matches.value_of("BOOT") {
+    let sample_rate = matches
+        .value_of("SAMPLE_RATE")
+        .map(|rate| match rate.parse() {
+            Ok(0) | Err(_) => Err("Invalid sample rate"),
+            Ok(rate) => Ok(rate),
+        })
+        .transpose()?
+        .unwrap_or(SAMPLE_RATE);
+    let model = match matches.value_of("MODEL") {
+        None => None,
+        Some("dmg0") => Some(Model::Dmg0),
+        Some("dmg") => Some(Model::Dmg),
+        Some("mgb") => Some(Model::Mgb),
+        Some("sgb") => Some(Model::Sgb),
+        Some("sgb2") => Some(Model::Sgb2),
+        Some("cgb0") => Some(Model::Cgb0),
+        Some("cgb") => Some(Model::Cgb),
+        Some("agb") => Some(Model::Agb),
+        Some(_) => {
+            return Err(
+                "Invalid model, expected dmg0, dmg, mgb, sgb, sgb2, cgb0, cgb or agb".to_string(),
+            )
+        }
+    };
+    let config = Config {
+        sample_rate,
+        model,
+        dmg_palette: dmg_palette(&matches)?,
+        color_correction: matches.is_present("COLOR_CORRECTION"),
+    };
+
+    let source = if matches.is_present("GBS") {
+        let player = GbsPlayer::new(&cartridge_data, config).map_err(|e| e.to_string())?;
+        Source::Gbs(player)
+    } else if let Some(path) = matches.value_of("BOOT") {
         let boot_data = load_file(path);
-        Board::new(&boot_data, &cartridge_data)
+        Source::Cartridge(Board::new(&boot_data, &cartridge_data, config))
     } else {
         println!("No boot rom provided.");
-        Board::no_boot(&cartridge_data)
+        Source::Cartridge(Board::no_boot(&cartridge_data, config))
     };
 
-    let mut gameboy = GameBoy::new(board);
+    let gdb = matches.value_of("GDB").map(wait_for_gdb).transpose()?;
+    let vgm_path = matches.value_of("VGM").map(PathBuf::from);
+    let mut gameboy = GameBoy::new(source, gdb, vgm_path);
+    if let Some(path) = matches.value_of("TRACE") {
+        let mut file = BufWriter::new(File::create(path).map_err(|e| e.to_string())?);
+        let format = if matches.is_present("TRACE_CYCLES") {
+            TraceFormat::Cycles
+        } else {
+            TraceFormat::Doctor
+        };
+        let sink = Box::new(move |line: &str| {
+            writeln!(file, "{}", line).expect("Failed to write the trace");
+        });
+        gameboy.board().set_trace(Some(sink), format);
+    }
+    let rewind_megabytes = matches
+        .value_of("REWIND")
+        .map(|size| size.parse().map_err(|_| "Invalid rewind size"))
+        .transpose()?
+        .unwrap_or(REWIND_MEGABYTES);
+    // music rips have nothing worth rewinding
+    match &mut gameboy.source {
+        Source::Cartridge(board) if rewind_megabytes > 0 => {
+            board.enable_rewind(rewind_megabytes << 20, 1)
+        }
+        _ => (),
+    }
+
+    let (width, height) = if matches!(model, Some(Model::Sgb | Model::Sgb2)) {
+        (SGB_WIDTH, SGB_HEIGHT)
+    } else {
+        (WIDTH, HEIGHT)
+    };
 
     let sdl_context = sdl2::init()?;
     let video_subsystem = sdl_context.video()?;
 
     let window = video_subsystem
         .window(
-            "Rostiger Spieljunge",
-            (WIDTH * PIXEL_SCALE) as u32,
-            (HEIGHT * PIXEL_SCALE) as u32,
+            &gameboy.title(),
+            (width * PIXEL_SCALE) as u32,
+            (height * PIXEL_SCALE) as u32,
         )
         .position_centered()
         .build()
@@ -117,8 +407,8 @@ fn main() -> Result<(), String> {
     let mut texture = texture_creator
         .create_texture_streaming(
             sdl2::pixels::PixelFormatEnum::RGB24,
-            WIDTH as u32,
-            HEIGHT as u32,
+            width as u32,
+            height as u32,
         )
         .map_err(|e| format!("{:?}", e))?;
 
@@ -126,31 +416,103 @@ fn main() -> Result<(), String> {
 
     let audio_subsystem = sdl_context.audio()?;
     let desired_spec = audio::AudioSpecDesired {
-        freq: Some(AUDIO_SAMPLE_RATE as i32),
+        freq: Some(sample_rate as i32),
         channels: Some(2),
         samples: Some(4096),
     };
-    let device = audio_subsystem.open_queue(None, &desired_spec)?;
+    let device = audio_subsystem.open_queue::<f32, _>(None, &desired_spec)?;
     device.resume();
+    let mut audio_buffer = vec![0.0; gameboy.board().audio_status().capacity];
+
+    let recording_base = PathBuf::from(matches.value_of("RECORD").unwrap_or(RECORDING_PATH));
+    let record_channels = matches.is_present("RECORD_CHANNELS");
+    let mut recordings = 0;
+    let mut recorder = None;
+    if matches.is_present("RECORD") {
+        recorder = Some(
+            Recorder::start(&recording_base, sample_rate as u32, record_channels)
+                .map_err(|e| e.to_string())?,
+        );
+        recordings += 1;
+        gameboy.board().set_channel_taps(record_channels);
+    }
 
     loop {
-        let frame = gameboy.next_frame();
-        texture.with_lock(None, |buffer, _| buffer.clone_from_slice(frame.as_slice()))?;
+        gameboy.next_frame();
+        texture.with_lock(None, |buffer, pitch| gameboy.render(buffer, pitch))?;
         canvas.clear();
         canvas.copy(&texture, None, None)?;
         canvas.present();
 
-        let audio = gameboy.audio();
-        device.queue(&audio);
+        // no audio is produced while rewinding, so it is muted until the game runs again
+        if !gameboy.rewinding {
+            let queued = device.size() as usize / std::mem::size_of::<f32>();
+            let audio = gameboy.audio(&mut audio_buffer, queued);
+            device.queue(audio);
+            if let Some(recorder) = recorder.as_mut() {
+                recorder
+                    .record(audio, |channel| gameboy.channel_audio(channel))
+                    .map_err(|e| e.to_string())?;
+            }
+
+            let queued = device.size() as usize / std::mem::size_of::<f32>();
+            gameboy.adjust_audio_rate(queued);
+        }
 
         for event in event_pump.poll_iter() {
             match event {
-                event::Event::Quit { .. } => return Ok(()),
+                event::Event::Quit { .. } => {
+                    if let Some(recorder) = recorder.take() {
+                        recorder.finish().map_err(|e| e.to_string())?;
+                    }
+                    gameboy.finish_vgm()?;
+                    return Ok(());
+                }
+                event::Event::KeyDown {
+                    keycode: Some(keyboard::Keycode::R),
+                    repeat: false,
+                    ..
+                } => match recorder.take() {
+                    Some(recorder) => {
+                        gameboy.board().set_channel_taps(false);
+                        recorder.finish().map_err(|e| e.to_string())?;
+                    }
+                    None => {
+                        let path = recording_path(&recording_base, recordings);
+                        recorder = Some(
+                            Recorder::start(&path, sample_rate as u32, record_channels)
+                                .map_err(|e| e.to_string())?,
+                        );
+                        recordings += 1;
+                        gameboy.board().set_channel_taps(record_channels);
+                    }
+                },
+                // without snapshots, such as with rewinding turned off or for music
+                // rips, the game keeps running. Key repeats are ignored, so the oldest
+                // snapshot stays on screen once all were rewound.
+                event::Event::KeyDown {
+                    keycode: Some(keyboard::Keycode::Backspace),
+                    repeat: false,
+                    ..
+                } => gameboy.rewinding = gameboy.board().rewind_len() > 0,
+                event::Event::KeyDown {
+                    keycode: Some(keyboard::Keycode::Backspace),
+                    ..
+                } => {}
+                event::Event::KeyUp {
+                    keycode: Some(keyboard::Keycode::Backspace),
+                    ..
+                } => gameboy.rewinding = false,
                 event::Event::KeyDown {
                     keycode: Some(keycode),
                     ..
                 } => {
-                    if let Some(button) = keycode_to_button(keycode) {
+                    if gameboy.change_song(keycode)? {
+                        canvas
+                            .window_mut()
+                            .set_title(&gameboy.title())
+                            .map_err(|e| e.to_string())?;
+                    } else if let Some(button) = keycode_to_button(keycode) {
                         gameboy.button_pressed(button);
                     }
                 }
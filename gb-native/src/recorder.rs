@@ -0,0 +1,63 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use gb_core::AudioChannel;
+
+use crate::wav::WavWriter;
+
+/// Records the mixed audio output and optionally every channel on its own
+pub struct Recorder {
+    mixed: WavWriter,
+    channels: Vec<(AudioChannel, WavWriter)>,
+}
+
+impl Recorder {
+    /// Starts a recording into `path`. If `per_channel` is set, every channel is
+    /// additionally written next to it, e.g. `song-pulse1.wav` for `song.wav`.
+    pub fn start(path: &Path, sample_rate: u32, per_channel: bool) -> io::Result<Self> {
+        let mixed = WavWriter::create(path, 2, sample_rate)?;
+
+        let mut channels = Vec::new();
+        if per_channel {
+            for channel in AudioChannel::ALL {
+                let path = channel_path(path, channel);
+                channels.push((channel, WavWriter::create(&path, 1, sample_rate)?));
+            }
+        }
+
+        println!("Recording audio to {}", path.display());
+        Ok(Self { mixed, channels })
+    }
+
+    pub fn record(
+        &mut self,
+        mixed: &[f32],
+        mut channel_audio: impl FnMut(AudioChannel) -> Vec<f32>,
+    ) -> io::Result<()> {
+        self.mixed.write(mixed)?;
+        for (channel, writer) in self.channels.iter_mut() {
+            writer.write(&channel_audio(*channel))?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        self.mixed.finish()?;
+        for (_, writer) in self.channels {
+            writer.finish()?;
+        }
+        println!("Recording finished");
+        Ok(())
+    }
+}
+
+fn channel_path(path: &Path, channel: AudioChannel) -> PathBuf {
+    let name = match channel {
+        AudioChannel::Pulse1 => "pulse1",
+        AudioChannel::Pulse2 => "pulse2",
+        AudioChannel::Wave => "wave",
+        AudioChannel::Noise => "noise",
+    };
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}-{}.wav", stem, name))
+}
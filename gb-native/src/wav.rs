@@ -0,0 +1,99 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+const HEADER_SIZE: u32 = 44;
+
+/// Writes samples as 16 bit PCM into a WAV file. The sizes in the header are only known
+/// when the writer is finished, which happens on drop at the latest.
+pub struct WavWriter {
+    file: BufWriter<File>,
+    channels: u16,
+    data_size: u32,
+    finished: bool,
+}
+
+impl WavWriter {
+    pub fn create(path: &Path, channels: u16, sample_rate: u32) -> io::Result<Self> {
+        let mut writer = Self {
+            file: BufWriter::new(File::create(path)?),
+            channels,
+            data_size: 0,
+            finished: false,
+        };
+        writer.write_header(sample_rate)?;
+        Ok(writer)
+    }
+
+    fn write_header(&mut self, sample_rate: u32) -> io::Result<()> {
+        let block_align = self.channels * 2;
+        let file = &mut self.file;
+        file.write_all(b"RIFF")?;
+        file.write_all(&(HEADER_SIZE - 8).to_le_bytes())?;
+        file.write_all(b"WAVE")?;
+        file.write_all(b"fmt ")?;
+        file.write_all(&16u32.to_le_bytes())?;
+        file.write_all(&1u16.to_le_bytes())?; // PCM
+        file.write_all(&self.channels.to_le_bytes())?;
+        file.write_all(&sample_rate.to_le_bytes())?;
+        file.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+        file.write_all(&block_align.to_le_bytes())?;
+        file.write_all(&16u16.to_le_bytes())?; // bits per sample
+        file.write_all(b"data")?;
+        file.write_all(&0u32.to_le_bytes())
+    }
+
+    /// Appends samples, interleaved if there is more than one channel
+    pub fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+        for &sample in samples {
+            let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            self.file.write_all(&sample.to_le_bytes())?;
+        }
+        self.data_size += 2 * samples.len() as u32;
+        Ok(())
+    }
+
+    /// Fills in the sizes of the header
+    pub fn finish(mut self) -> io::Result<()> {
+        self.finished = true;
+        self.write_sizes()
+    }
+
+    fn write_sizes(&mut self) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file
+            .write_all(&(HEADER_SIZE - 8 + self.data_size).to_le_bytes())?;
+        self.file.seek(SeekFrom::Start(HEADER_SIZE as u64 - 4))?;
+        self.file.write_all(&self.data_size.to_le_bytes())?;
+        self.file.flush()
+    }
+}
+
+/// Keeps the file playable when a recording ends with an error
+impl Drop for WavWriter {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.write_sizes();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WavWriter;
+
+    #[test]
+    fn header_is_written_on_drop() {
+        let path = std::env::temp_dir().join("gb-native-wav-drop.wav");
+        {
+            let mut writer = WavWriter::create(&path, 2, 48_000).unwrap();
+            writer.write(&[0.0, 0.5, -0.5, 1.0]).unwrap();
+        }
+        let data = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(data.len(), 44 + 8);
+        assert_eq!(data[4..8], (36u32 + 8).to_le_bytes());
+        assert_eq!(data[40..44], 8u32.to_le_bytes());
+    }
+}